@@ -0,0 +1,219 @@
+use core::convert::TryInto;
+
+// AES-256 (FIPS 197) and the XTS mode of IEEE 1619 built on it. Plain
+// table-based software AES: the device it protects sits in the same
+// memory as the code, so cache timing is not part of the threat.
+
+pub const KEY_SIZE: usize = 32;
+const BLOCK: usize = 16;
+const ROUNDS: usize = 14;
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+const fn inverse(sbox: &[u8; 256]) -> [u8; 256] {
+    let mut inv = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        inv[sbox[i] as usize] = i as u8;
+        i += 1;
+    }
+    inv
+}
+
+const INV_SBOX: [u8; 256] = inverse(&SBOX);
+
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ if b & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    product
+}
+
+/// AES-256 with its expanded key schedule.
+#[derive(Clone)]
+pub struct Aes256 {
+    round_keys: [[u8; BLOCK]; ROUNDS + 1],
+}
+
+impl Aes256 {
+    pub fn new(key: &[u8; KEY_SIZE]) -> Self {
+        let mut words = [[0u8; 4]; 4 * (ROUNDS + 1)];
+        for (i, word) in key.chunks_exact(4).enumerate() {
+            words[i] = word.try_into().unwrap_or([0; 4]);
+        }
+        let mut rcon = 1u8;
+        for i in 8..words.len() {
+            let mut temp = words[i - 1];
+            if i % 8 == 0 {
+                temp = [SBOX[temp[1] as usize] ^ rcon, SBOX[temp[2] as usize], SBOX[temp[3] as usize], SBOX[temp[0] as usize]];
+                rcon = xtime(rcon);
+            } else if i % 8 == 4 {
+                temp = temp.map(|b| SBOX[b as usize]);
+            }
+            for j in 0..4 {
+                words[i][j] = words[i - 8][j] ^ temp[j];
+            }
+        }
+        let mut round_keys = [[0u8; BLOCK]; ROUNDS + 1];
+        for (round, key) in round_keys.iter_mut().enumerate() {
+            for j in 0..4 {
+                key[j * 4..j * 4 + 4].copy_from_slice(&words[round * 4 + j]);
+            }
+        }
+        Aes256 { round_keys }
+    }
+
+    pub fn encrypt(&self, block: &mut [u8; BLOCK]) {
+        add_round_key(block, &self.round_keys[0]);
+        for round in 1..=ROUNDS {
+            for b in block.iter_mut() {
+                *b = SBOX[*b as usize];
+            }
+            shift_rows(block);
+            if round != ROUNDS {
+                mix_columns(block);
+            }
+            add_round_key(block, &self.round_keys[round]);
+        }
+    }
+
+    pub fn decrypt(&self, block: &mut [u8; BLOCK]) {
+        add_round_key(block, &self.round_keys[ROUNDS]);
+        for round in (0..ROUNDS).rev() {
+            inv_shift_rows(block);
+            for b in block.iter_mut() {
+                *b = INV_SBOX[*b as usize];
+            }
+            add_round_key(block, &self.round_keys[round]);
+            if round != 0 {
+                inv_mix_columns(block);
+            }
+        }
+    }
+}
+
+fn add_round_key(block: &mut [u8; BLOCK], key: &[u8; BLOCK]) {
+    for (b, k) in block.iter_mut().zip(key.iter()) {
+        *b ^= k;
+    }
+}
+
+// The state is column-major: byte r + 4c is row r of column c
+fn shift_rows(s: &mut [u8; BLOCK]) {
+    let old = *s;
+    for c in 0..4 {
+        for r in 1..4 {
+            s[r + 4 * c] = old[r + 4 * ((c + r) % 4)];
+        }
+    }
+}
+
+fn inv_shift_rows(s: &mut [u8; BLOCK]) {
+    let old = *s;
+    for c in 0..4 {
+        for r in 1..4 {
+            s[r + 4 * ((c + r) % 4)] = old[r + 4 * c];
+        }
+    }
+}
+
+fn mix_columns(s: &mut [u8; BLOCK]) {
+    for column in s.chunks_exact_mut(4) {
+        let [a, b, c, d]: [u8; 4] = (&*column).try_into().unwrap_or([0; 4]);
+        let all = a ^ b ^ c ^ d;
+        column[0] = a ^ all ^ xtime(a ^ b);
+        column[1] = b ^ all ^ xtime(b ^ c);
+        column[2] = c ^ all ^ xtime(c ^ d);
+        column[3] = d ^ all ^ xtime(d ^ a);
+    }
+}
+
+fn inv_mix_columns(s: &mut [u8; BLOCK]) {
+    for column in s.chunks_exact_mut(4) {
+        let [a, b, c, d]: [u8; 4] = (&*column).try_into().unwrap_or([0; 4]);
+        column[0] = mul(a, 14) ^ mul(b, 11) ^ mul(c, 13) ^ mul(d, 9);
+        column[1] = mul(a, 9) ^ mul(b, 14) ^ mul(c, 11) ^ mul(d, 13);
+        column[2] = mul(a, 13) ^ mul(b, 9) ^ mul(c, 14) ^ mul(d, 11);
+        column[3] = mul(a, 11) ^ mul(b, 13) ^ mul(c, 9) ^ mul(d, 14);
+    }
+}
+
+/// XTS-AES-256: one key encrypts the data, the other the data unit
+/// number into the tweak, so equal blocks at different positions encrypt
+/// differently. Data units here are whole device blocks, a multiple of
+/// 16 bytes, so no ciphertext stealing is needed.
+#[derive(Clone)]
+pub struct Xts {
+    data: Aes256,
+    tweak: Aes256,
+}
+
+impl Xts {
+    pub fn new(data_key: &[u8; KEY_SIZE], tweak_key: &[u8; KEY_SIZE]) -> Self {
+        Xts { data: Aes256::new(data_key), tweak: Aes256::new(tweak_key) }
+    }
+
+    pub fn encrypt(&self, unit: u64, data: &mut [u8]) {
+        self.apply(unit, data, |block| self.data.encrypt(block));
+    }
+
+    pub fn decrypt(&self, unit: u64, data: &mut [u8]) {
+        self.apply(unit, data, |block| self.data.decrypt(block));
+    }
+
+    fn apply(&self, unit: u64, data: &mut [u8], cipher: impl Fn(&mut [u8; BLOCK])) {
+        let mut tweak = [0u8; BLOCK];
+        tweak[..8].copy_from_slice(&unit.to_le_bytes());
+        self.tweak.encrypt(&mut tweak);
+        for chunk in data.chunks_exact_mut(BLOCK) {
+            let mut block = [0u8; BLOCK];
+            for i in 0..BLOCK {
+                block[i] = chunk[i] ^ tweak[i];
+            }
+            cipher(&mut block);
+            for i in 0..BLOCK {
+                chunk[i] = block[i] ^ tweak[i];
+            }
+            next_tweak(&mut tweak);
+        }
+    }
+}
+
+// Multiplies the tweak by x in GF(2^128), little-endian as in IEEE 1619.
+fn next_tweak(tweak: &mut [u8; BLOCK]) {
+    let mut carry = 0;
+    for b in tweak.iter_mut() {
+        let next = *b >> 7;
+        *b = (*b << 1) | carry;
+        carry = next;
+    }
+    if carry != 0 {
+        tweak[0] ^= 0x87;
+    }
+}
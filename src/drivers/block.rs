@@ -1,5 +1,7 @@
 use alloc::vec::Vec;
 use alloc::vec;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicIsize, Ordering};
 
 pub const BLOCK_SIZE: usize = 4096;
 
@@ -8,17 +10,66 @@ pub enum BlockError {
     ReadError,
     WriteError,
     OutOfBounds,
+    PoolExhausted, // Every buffer pool frame is pinned
+    DiskFull,      // No free page left on the device
+    DoubleFree,    // Page is already on the free list
+    LogFull,       // Uncommitted changes no longer fit in the write-ahead log
+    Corrupt { page_id: u32 }, // Page failed its checksum
+    BadKey,        // Passphrase does not match the encrypted device
 }
 
-pub trait BlockIO {
+pub trait BlockIO: Send {
     fn read_block(&self, block_id: u32, buf: &mut [u8]) -> Result<(), BlockError>;
     fn write_block(&mut self, block_id: u32, buf: &[u8]) -> Result<(), BlockError>;
     fn num_blocks(&self) -> u32;
 }
 
+/// Shared switch for simulating a crash on a `RamDisk`. Once armed, the
+/// disk accepts a given number of further writes and fails every write
+/// after that. The disk keeps a clone, so the switch still works after the
+/// disk has been boxed and handed to a `Pager`.
+#[derive(Clone)]
+pub struct FaultInjector {
+    writes_left: Arc<AtomicIsize>, // < 0 = disarmed
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        FaultInjector { writes_left: Arc::new(AtomicIsize::new(-1)) }
+    }
+
+    /// Let `writes` more block writes succeed, then fail the rest.
+    pub fn fail_after(&self, writes: usize) {
+        self.writes_left.store(writes as isize, Ordering::SeqCst);
+    }
+
+    pub fn disarm(&self) {
+        self.writes_left.store(-1, Ordering::SeqCst);
+    }
+
+    fn allow_write(&self) -> bool {
+        let left = self.writes_left.load(Ordering::SeqCst);
+        if left < 0 {
+            return true;
+        }
+        if left == 0 {
+            return false;
+        }
+        self.writes_left.store(left - 1, Ordering::SeqCst);
+        true
+    }
+}
+
+impl Default for FaultInjector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub struct RamDisk {
     data: Vec<u8>,
     size: usize,
+    faults: Option<FaultInjector>,
 }
 
 impl RamDisk {
@@ -28,8 +79,16 @@ impl RamDisk {
         RamDisk {
             data: vec![0; aligned_size],
             size: aligned_size,
+            faults: None,
         }
     }
+
+    /// RamDisk whose writes can be made to fail through `faults`.
+    pub fn with_faults(size: usize, faults: FaultInjector) -> Self {
+        let mut disk = Self::new(size);
+        disk.faults = Some(faults);
+        disk
+    }
 }
 
 impl BlockIO for RamDisk {
@@ -54,6 +113,11 @@ impl BlockIO for RamDisk {
         if buf.len() != BLOCK_SIZE {
             return Err(BlockError::WriteError);
         }
+        if let Some(faults) = &self.faults {
+            if !faults.allow_write() {
+                return Err(BlockError::WriteError);
+            }
+        }
 
         self.data[offset..offset + BLOCK_SIZE].copy_from_slice(buf);
         Ok(())
@@ -0,0 +1,132 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use core::convert::TryInto;
+use super::aes::{Xts, KEY_SIZE};
+use super::block::{BlockIO, BlockError, BLOCK_SIZE};
+use super::sha256::{self, DIGEST_SIZE};
+
+// Header block (block 0 of the inner device), stored in the clear:
+// 0..4   : Magic
+// 4..8   : Cipher (1 = AES-256-XTS, key from PBKDF2-HMAC-SHA256)
+// 8..12  : PBKDF2 iterations
+// 12..28 : Salt
+// 28..60 : Key check - HMAC of a fixed message under the derived key, so
+//          a wrong passphrase is caught without revealing the key
+//
+// Block n of the encrypted device is block n + 1 of the inner one,
+// encrypted as XTS data unit n + 1.
+const MAGIC: &[u8; 4] = b"SWCR";
+const CIPHER_AES_XTS: u32 = 1;
+const KEY_CHECK_MESSAGE: &[u8] = b"SawitDB key check";
+
+pub const SALT_SIZE: usize = 16;
+/// PBKDF2 iterations for new devices.
+pub const KDF_ITERATIONS: u32 = 10_000;
+
+/// Key of an encrypted device, derived from its passphrase.
+pub struct DiskKey {
+    xts: Xts,
+}
+
+impl DiskKey {
+    // The XTS data and tweak keys, and the key check value
+    fn derive(passphrase: &[u8], salt: &[u8], iterations: u32) -> (Self, [u8; DIGEST_SIZE]) {
+        let mut keys = [0u8; 2 * KEY_SIZE];
+        sha256::pbkdf2(passphrase, salt, iterations, &mut keys);
+        let data: [u8; KEY_SIZE] = keys[..KEY_SIZE].try_into().unwrap_or([0; KEY_SIZE]);
+        let tweak: [u8; KEY_SIZE] = keys[KEY_SIZE..].try_into().unwrap_or([0; KEY_SIZE]);
+        let check = sha256::hmac_sha256(&keys, KEY_CHECK_MESSAGE);
+        keys.fill(0);
+        (DiskKey { xts: Xts::new(&data, &tweak) }, check)
+    }
+}
+
+/// Transparent encryption around another block device. Everything
+/// written through it is encrypted with AES-256-XTS under a key derived
+/// from a passphrase; only the header block is readable without it.
+pub struct EncryptedDisk {
+    inner: Box<dyn BlockIO>,
+    key: DiskKey,
+}
+
+impl EncryptedDisk {
+    /// True if `disk` carries an encryption header.
+    pub fn is_formatted(disk: &dyn BlockIO) -> bool {
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        disk.read_block(0, &mut buf).is_ok() && &buf[0..4] == MAGIC
+    }
+
+    /// Writes a header for `passphrase` to `disk` and returns its key.
+    /// Whatever the disk held before can no longer be read through it.
+    pub fn format(disk: &mut dyn BlockIO, passphrase: &[u8], salt: [u8; SALT_SIZE], iterations: u32) -> Result<DiskKey, BlockError> {
+        if disk.num_blocks() < 2 {
+            return Err(BlockError::DiskFull);
+        }
+        let iterations = iterations.max(1);
+        let (key, check) = DiskKey::derive(passphrase, &salt, iterations);
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        buf[0..4].copy_from_slice(MAGIC);
+        buf[4..8].copy_from_slice(&CIPHER_AES_XTS.to_le_bytes());
+        buf[8..12].copy_from_slice(&iterations.to_le_bytes());
+        buf[12..28].copy_from_slice(&salt);
+        buf[28..60].copy_from_slice(&check);
+        disk.write_block(0, &buf)?;
+        // Leave the encrypted device's block 0 reading as zeros, so it
+        // looks blank whatever the disk held before
+        buf.fill(0);
+        key.xts.encrypt(1, &mut buf);
+        disk.write_block(1, &buf)?;
+        Ok(key)
+    }
+
+    /// Derives the key of a formatted `disk` from `passphrase`; `BadKey`
+    /// if the passphrase is not the one it was formatted with.
+    pub fn unlock(disk: &dyn BlockIO, passphrase: &[u8]) -> Result<DiskKey, BlockError> {
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        disk.read_block(0, &mut buf)?;
+        let header_u32 = |at: usize| u32::from_le_bytes(buf[at..at + 4].try_into().unwrap_or([0; 4]));
+        if &buf[0..4] != MAGIC || header_u32(4) != CIPHER_AES_XTS {
+            return Err(BlockError::Corrupt { page_id: 0 });
+        }
+        let (key, check) = DiskKey::derive(passphrase, &buf[12..28], header_u32(8));
+        // Compare without stopping at the first difference
+        let diff = check.iter().zip(buf[28..60].iter()).fold(0, |diff, (a, b)| diff | (a ^ b));
+        if diff != 0 {
+            return Err(BlockError::BadKey);
+        }
+        Ok(key)
+    }
+
+    /// Wraps `inner` with a key from `format` or `unlock`.
+    pub fn new(inner: Box<dyn BlockIO>, key: DiskKey) -> Self {
+        EncryptedDisk { inner, key }
+    }
+
+    pub fn into_inner(self) -> Box<dyn BlockIO> {
+        self.inner
+    }
+}
+
+impl BlockIO for EncryptedDisk {
+    fn read_block(&self, block_id: u32, buf: &mut [u8]) -> Result<(), BlockError> {
+        let inner_id = block_id.checked_add(1).ok_or(BlockError::OutOfBounds)?;
+        self.inner.read_block(inner_id, buf)?;
+        self.key.xts.decrypt(inner_id as u64, buf);
+        Ok(())
+    }
+
+    fn write_block(&mut self, block_id: u32, buf: &[u8]) -> Result<(), BlockError> {
+        let inner_id = block_id.checked_add(1).ok_or(BlockError::OutOfBounds)?;
+        if buf.len() != BLOCK_SIZE {
+            return Err(BlockError::WriteError);
+        }
+        let mut encrypted = buf.to_vec();
+        self.key.xts.encrypt(inner_id as u64, &mut encrypted);
+        self.inner.write_block(inner_id, &encrypted)
+    }
+
+    /// One block less than the inner device: the header.
+    fn num_blocks(&self) -> u32 {
+        self.inner.num_blocks().saturating_sub(1)
+    }
+}
@@ -1,5 +1,8 @@
 pub mod vga_buffer;
 pub mod block;
+pub mod sha256;
+pub mod aes;
+pub mod crypt;
 pub mod net;
 pub mod virtio_hal;
 pub mod virtio_transport;
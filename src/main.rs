@@ -46,68 +46,604 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
     use alloc::boxed::Box;
     use alloc::string::String;
     use alloc::vec;
-    use sawitcore_os::drivers::block::RamDisk;
+    use sawitcore_os::drivers::block::{RamDisk, FaultInjector, BlockError, BlockIO, BLOCK_SIZE};
     use sawitcore_os::sawitdb::pager::Pager;
     use sawitcore_os::sawitdb::btree::BTreeIndex;
-    use sawitcore_os::sawitdb::types::Value;
+    use sawitcore_os::sawitdb::types::{Value, ValueType};
+    use sawitcore_os::sawitdb::database::Database;
+    use sawitcore_os::sawitdb::error::DbError;
+    use sawitcore_os::sawitdb::catalog::Column;
+    use sawitcore_os::sawitdb::heap::HeapFile;
+    use sawitcore_os::sawitdb::codec;
+    use sawitcore_os::sawitdb::row::Row;
+    use sawitcore_os::sawitdb::query;
+    use sawitcore_os::sawitdb::sort;
+    use sawitcore_os::sawitdb::compress::Compression;
+    use sawitcore_os::drivers::crypt::{EncryptedDisk, SALT_SIZE};
+    use sawitcore_os::drivers::aes::{Aes256, Xts};
+    use sawitcore_os::drivers::sha256;
+    use sawitcore_os::sawitdb::backup::{Backup, Restore};
 
     println!("");
     println!("[SawitDB] Starting Verification Tests...");
 
-    // 1. Storage & Pager Test
-    // Use 64KB RamDisk to fit in heap
-    let ramdisk = Box::new(RamDisk::new(64 * 1024));
-    println!("[SawitDB] RamDisk Created (64KB)");
-
-    match Pager::new(ramdisk) {
-        Ok(mut pager) => {
-             println!("[SawitDB] Pager Initialized");
-             if let Ok(p0) = pager.read_page(0) {
-                 if &p0[0..4] == b"WOWO" {
-                     println!("[SawitDB] Pager Magic Verified: WOWO");
-                 } else {
-                     println!("[SawitDB] Error: Invalid Magic");
-                 }
-             }
-             match pager.alloc_page() {
-                 Ok(pid) => println!("[SawitDB] Page Allocated: ID {}", pid),
-                 Err(_) => println!("[SawitDB] Error Allocating Page"),
-             }
-        },
-        Err(_) => println!("[SawitDB] Failed to init Pager"),
+    // Runs a statement in autocommit, as the shell does; Ok(rows returned)
+    let step = |db: &mut Database, q: &str| {
+        let result = query::run(db, q).and_then(|output| match output {
+            query::Output::Rows(rows) => rows.collect_rows(db).map(|rows| rows.len()),
+            _ => Ok(0),
+        });
+        db.autocommit(result)
+    };
+
+    {
+        // 1. Storage & Pager Test
+        // Use 64KB RamDisk to fit in heap
+        let ramdisk = Box::new(RamDisk::new(64 * 1024));
+        println!("[SawitDB] RamDisk Created (64KB)");
+
+        let mut pager = match Pager::new(ramdisk) {
+            Ok(pager) => pager,
+            Err(_) => panic!("[SawitDB] Failed to init Pager"),
+        };
+        println!("[SawitDB] Pager Initialized");
+        if let Ok(p0) = pager.read_page(0) {
+            if &p0[0..4] == b"WOWO" {
+                println!("[SawitDB] Pager Magic Verified: WOWO");
+            } else {
+                println!("[SawitDB] Error: Invalid Magic");
+            }
+        }
+        match pager.alloc_page() {
+            Ok(pid) => {
+                println!("[SawitDB] Page Allocated: ID {}", pid);
+                // Freed pages go back on the free list and are handed out again
+                if pager.free_page(pid).is_ok() && pager.alloc_page().ok() == Some(pid) {
+                    println!("[SawitDB] Free List Verified: Page {} Reused", pid);
+                } else {
+                    println!("[SawitDB] Error: Freed Page Not Reused");
+                }
+            },
+            Err(_) => println!("[SawitDB] Error Allocating Page"),
+        }
+
+        // 2. BTree Index Test
+        println!("[SawitDB] Testing BTree Index...");
+        let btree = BTreeIndex::create(&mut pager, 4, true, String::from("users"), String::from("id"))
+            .expect("[SawitDB] Failed to create index");
+
+        println!("[SawitDB] Inserting Keys...");
+        let rows = [(10, "Alice"), (5, "Bob"), (20, "Charlie"), (15, "Dave"), (2, "Eve")];
+        for (k, name) in rows {
+            if btree.insert(&mut pager, Value::Int(k), Value::String(String::from(name))).is_err() {
+                println!("[SawitDB] Error Inserting Key {}", k);
+            }
+        }
+
+        // Reopen the index from the same disk to check it really lives in pages
+        pager.flush().expect("[SawitDB] Failed to flush Pager");
+        let mut pager = Pager::new(pager.into_disk()).expect("[SawitDB] Failed to reopen Pager");
+        let btree = BTreeIndex::open(&mut pager, btree.root, 4, true, String::from("users"), String::from("id"))
+            .expect("[SawitDB] Failed to reopen index");
+        println!("[SawitDB] Index Reopened: Root Page {}", btree.root);
+
+        println!("[SawitDB] Searching Keys...");
+        let search_keys = vec![5, 15, 99];
+        for k in search_keys {
+            let key_val = Value::Int(k);
+            match btree.search(&mut pager, &key_val) {
+                Ok(results) if !results.is_empty() => println!("[SawitDB] Found Key {}: {}", k, results[0]),
+                Ok(_) => println!("[SawitDB] Key {} Not Found", k),
+                Err(_) => println!("[SawitDB] Error Searching Key {}", k),
+            }
+        }
+        // The users index is unique; a non-unique index keeps every value per key
+        let duplicate_rejected = btree.insert(&mut pager, Value::Int(5), Value::String(String::from("Bobby"))).is_err();
+        let tags = BTreeIndex::create(&mut pager, 4, false, String::from("tags"), String::from("tag"))
+            .expect("[SawitDB] Failed to create index");
+        for (k, v) in [(1, 10), (2, 20), (1, 11), (1, 12), (3, 30), (1, 13)] {
+            let _ = tags.insert(&mut pager, Value::Int(k), Value::Int(v));
+        }
+        let _ = tags.delete_entry(&mut pager, &Value::Int(1), &Value::Int(12));
+        match tags.search(&mut pager, &Value::Int(1)) {
+            Ok(values) if duplicate_rejected && values == [Value::Int(10), Value::Int(11), Value::Int(13)] => {
+                println!("[SawitDB] Duplicate Keys Verified: unique rejects, non-unique holds {} values", values.len())
+            }
+            _ => println!("[SawitDB] Error: Duplicate Key Mismatch"),
+        }
+
+        // Deleting enough keys from an order-4 tree forces merges back into the root
+        let deleted = [10, 20, 15].iter().filter(|&&k| btree.delete(&mut pager, &Value::Int(k)).unwrap_or(0) == 1).count();
+        let remaining = [2, 5].iter().filter(|&&k| btree.search(&mut pager, &Value::Int(k)).map(|r| r.len()).unwrap_or(0) == 1).count();
+        if deleted == 3 && remaining == 2 && btree.search(&mut pager, &Value::Int(15)).map(|r| r.is_empty()).unwrap_or(false) {
+            println!("[SawitDB] Delete Verified: 3 keys removed, 2 kept");
+        } else {
+            println!("[SawitDB] Error: Delete Mismatch");
+        }
+        // Ordered scans walk the leaf chain in both directions
+        for k in [30, 40, 50, 60] {
+            let _ = btree.insert(&mut pager, Value::Int(k), Value::String(String::from("Plot")));
+        }
+        let forward: alloc::vec::Vec<Value> = btree.range(&mut pager, Value::Int(5)..Value::Int(50)).filter_map(|r| r.ok().map(|(k, _)| k)).collect();
+        let backward: alloc::vec::Vec<Value> = btree.iter(&mut pager).rev().take(2).filter_map(|r| r.ok().map(|(k, _)| k)).collect();
+        if forward == [Value::Int(5), Value::Int(30), Value::Int(40)] && backward == [Value::Int(60), Value::Int(50)] {
+            println!("[SawitDB] Range Scan Verified: [5, 50) has 3 keys, last two 60, 50");
+        } else {
+            println!("[SawitDB] Error: Range Scan Mismatch");
+        }
+        let stats = pager.pool_stats();
+        println!("[SawitDB] Buffer Pool: {} hits, {} misses", stats.hits, stats.misses);
     }
-    
-    // 2. BTree Index Test
-    println!("[SawitDB] Testing BTree Index...");
-    let mut btree = BTreeIndex::new(4, String::from("users"), String::from("id"));
-    
-    println!("[SawitDB] Inserting Keys...");
-    btree.insert(Value::Int(10), Value::String(String::from("Alice")));
-    btree.insert(Value::Int(5), Value::String(String::from("Bob")));
-    btree.insert(Value::Int(20), Value::String(String::from("Charlie")));
-    btree.insert(Value::Int(15), Value::String(String::from("Dave")));
-    btree.insert(Value::Int(2), Value::String(String::from("Eve")));
-    
-    println!("[SawitDB] Searching Keys...");
-    let search_keys = vec![5, 15, 99];
-    for k in search_keys {
-        let key_val = Value::Int(k);
-        let results = btree.search(&key_val);
-        if !results.is_empty() {
-             println!("[SawitDB] Found Key {}: {}", k, results[0]);
+
+    {
+        // 3. WAL Crash Recovery Test
+        println!("[SawitDB] Testing WAL Recovery...");
+        let faults = FaultInjector::new();
+        let mut pager = Pager::new(Box::new(RamDisk::with_faults(64 * 1024, faults.clone())))
+            .expect("[SawitDB] Failed to init Pager");
+        let btree = BTreeIndex::create(&mut pager, 4, false, String::from("wal"), String::from("id"))
+            .expect("[SawitDB] Failed to create index");
+        let _ = btree.insert(&mut pager, Value::Int(1), Value::String(String::from("committed")));
+        pager.flush().expect("[SawitDB] Failed to flush Pager");
+        let _ = btree.insert(&mut pager, Value::Int(2), Value::String(String::from("lost")));
+        // "Crash" after the page image reaches the log but before the commit record
+        faults.fail_after(1);
+        let crashed = pager.flush().is_err();
+        faults.disarm();
+
+        let mut pager = Pager::new(pager.into_disk()).expect("[SawitDB] Recovery failed");
+        let btree = BTreeIndex::open(&mut pager, btree.root, 4, false, String::from("wal"), String::from("id"))
+            .expect("[SawitDB] Failed to reopen index");
+        let kept = btree.search(&mut pager, &Value::Int(1)).map(|r| r.len()).unwrap_or(0);
+        let lost = btree.search(&mut pager, &Value::Int(2)).map(|r| r.len()).unwrap_or(0);
+        if crashed && kept == 1 && lost == 0 {
+            println!("[SawitDB] WAL Recovery Verified: committed kept, partial discarded");
         } else {
-             println!("[SawitDB] Key {} Not Found", k);
+            println!("[SawitDB] Error: WAL Recovery Mismatch");
+        }
+
+        // 4. Page Checksum Test
+        println!("[SawitDB] Testing Page Checksums...");
+        pager.flush().expect("[SawitDB] Failed to flush Pager");
+        let root = btree.root;
+        let mut disk = pager.into_disk();
+        let mut block = vec![0u8; sawitcore_os::drivers::block::BLOCK_SIZE];
+        // Flip one bit of the index root behind the pager's back
+        if disk.read_block(root, &mut block).is_ok() {
+            block[16] ^= 0x01;
+            let _ = disk.write_block(root, &block);
+        }
+        let mut pager = Pager::new(disk).expect("[SawitDB] Failed to reopen Pager");
+        match pager.read_page(root) {
+            Err(BlockError::Corrupt { page_id }) if pager.corruptions() == 1 => {
+                println!("[SawitDB] Checksum Verified: corruption detected on page {}", page_id)
+            }
+            _ => println!("[SawitDB] Error: Corrupted Page Not Detected"),
+        }
+    }
+
+    {
+        // 5. Table Catalog Test
+        println!("[SawitDB] Testing Table Catalog...");
+        let mut db = Database::open(Box::new(RamDisk::new(96 * 1024))).expect("[SawitDB] Failed to open Database");
+        let users_schema = vec![Column::new("id", ValueType::Int), Column::new("name", ValueType::String)];
+        let _ = db.create_table("users", "id", users_schema);
+        let _ = db.create_table("harvest", "block", vec![]);
+        let _ = db.drop_table("harvest");
+        db.flush().expect("[SawitDB] Failed to flush Database");
+        let db = Database::open(db.into_disk()).expect("[SawitDB] Failed to reopen Database");
+        for table in db.tables() {
+            println!("[SawitDB] Catalog Table: {} (key {}, {} columns)", table.name, table.key_field, table.schema.len());
+        }
+    }
+
+    {
+        // 6. Heap Page Test
+        println!("[SawitDB] Testing Heap Records...");
+        let mut pager = Pager::new(Box::new(RamDisk::new(64 * 1024))).expect("[SawitDB] Failed to init Pager");
+        let mut heap = HeapFile::create(&mut pager).expect("[SawitDB] Failed to create heap");
+        let a = heap.insert(&mut pager, b"Blok A-1").expect("[SawitDB] Heap insert failed");
+        let b = heap.insert(&mut pager, b"Blok B-7").expect("[SawitDB] Heap insert failed");
+        let _ = heap.update(&mut pager, a, b"Blok A-1 (replanted 2024)");
+        let _ = heap.delete(&mut pager, b);
+        let live = heap.scan(&mut pager).filter(|r| r.is_ok()).count();
+        match heap.get(&mut pager, a) {
+            Ok(Some(rec)) if live == 1 && rec == b"Blok A-1 (replanted 2024)" => {
+                println!("[SawitDB] Heap Verified: record {}:{} kept its id across update", a.page, a.slot)
+            }
+            _ => println!("[SawitDB] Error: Heap Record Mismatch"),
+        }
+
+        // 7. Mixed-Type Keys Test
+        println!("[SawitDB] Testing Value Ordering...");
+        let mixed = BTreeIndex::create(&mut pager, 4, false, String::from("mixed"), String::from("k"))
+            .expect("[SawitDB] Failed to create index");
+        let keys = [
+            Value::String(String::from("sawit")), Value::Float(f64::NAN), Value::Int(2), Value::Null,
+            Value::Bytes(vec![0xca, 0xfe]), Value::Float(1.5), Value::Bool(true), Value::Timestamp(1_700_000_000_000_000),
+        ];
+        for key in keys.iter() {
+            let _ = mixed.insert(&mut pager, key.clone(), Value::Null);
+        }
+        let ordered: alloc::vec::Vec<Value> = mixed.iter(&mut pager).filter_map(|r| r.ok().map(|(k, _)| k)).collect();
+        let expected = [
+            Value::Null, Value::Bool(true), Value::Float(1.5), Value::Int(2), Value::Float(f64::NAN),
+            Value::Timestamp(1_700_000_000_000_000), Value::String(String::from("sawit")), Value::Bytes(vec![0xca, 0xfe]),
+        ];
+        if ordered == expected {
+            println!("[SawitDB] Value Order Verified: {} mixed-type keys indexed", ordered.len());
+        } else {
+            println!("[SawitDB] Error: Value Order Mismatch");
+        }
+
+        // 8. Binary Codec Test
+        println!("[SawitDB] Testing Binary Codec...");
+        let record = codec::encode(&expected);
+        let decoded_ok = matches!(codec::decode(&record), Ok(ref values) if values == &expected);
+        let key_bytes: alloc::vec::Vec<alloc::vec::Vec<u8>> = expected.iter().map(|v| {
+            let mut out = vec![];
+            codec::encode_key(v, &mut out);
+            out
+        }).collect();
+        let keys_sorted = key_bytes.windows(2).all(|w| w[0] < w[1]);
+        if decoded_ok && keys_sorted {
+            println!("[SawitDB] Codec Verified: {} values in {} bytes, keys sort like values", expected.len(), record.len());
+        } else {
+            println!("[SawitDB] Error: Codec Round-Trip Mismatch");
+        }
+    }
+
+    {
+        // 9. Rows & Schema Test
+        println!("[SawitDB] Testing Rows...");
+        let mut db = Database::open(Box::new(RamDisk::new(64 * 1024))).expect("[SawitDB] Failed to open Database");
+        let schema = vec![Column::new("id", ValueType::Int), Column::new("name", ValueType::String), Column::new("yield", ValueType::Float)];
+        let _ = db.create_table("plots", "id", schema);
+        let _ = db.insert("plots", &Row::new().with("id", Value::Int(1)).with("name", Value::String(String::from("Blok A"))).with("yield", Value::Int(12)));
+        let _ = db.insert("plots", &Row::new().with("id", Value::Int(2)).with("name", Value::String(String::from("Blok B"))));
+        let rejected = [
+            db.insert("plots", &Row::new().with("id", Value::Int(3)).with("name", Value::Int(7))),
+            db.insert("plots", &Row::new().with("name", Value::String(String::from("no key")))),
+            db.insert("plots", &Row::new().with("id", Value::Int(1))),
+            db.insert("plots", &Row::new().with("id", Value::Int(4)).with("owner", Value::Null)),
+        ].iter().filter(|r| r.is_err()).count();
+        match db.get("plots", &Value::Int(1)) {
+            Ok(Some(row)) if rejected == 4 && row.get("yield") == Some(&Value::Float(12.0)) => {
+                println!("[SawitDB] Rows Verified: {} (4 bad rows rejected)", row)
+            }
+            _ => println!("[SawitDB] Error: Row Mismatch"),
+        }
+    }
+
+    {
+        // 10. Query Parser Test
+        println!("[SawitDB] Testing Query Parser...");
+        let aql = query::parse("PANEN bibit, umur DARI sawit DIMANA umur >= 5 AND lokasi IN ('Riau', 'Jambi')");
+        let sql = query::parse("SELECT bibit, umur FROM sawit WHERE umur >= 5 AND lokasi IN ('Riau', 'Jambi');");
+        let bad_query = "TANAM KE sawit (bibit) BIBIT ('Dura',)";
+        match (aql, sql, query::parse(bad_query)) {
+            (Ok(a), Ok(b), Err(e)) if a == b => {
+                println!("[SawitDB] Parser Verified: AQL and SQL agree, error at {} ({})", e.pos, e.message)
+            }
+            _ => println!("[SawitDB] Error: Parser Mismatch"),
+        }
+    }
+
+    {
+        // 11. Query Executor Test
+        println!("[SawitDB] Testing Query Executor...");
+        let mut db = Database::open(Box::new(RamDisk::new(128 * 1024))).expect("[SawitDB] Failed to open Database");
+        let setup = [
+            "LAHAN sawit (blok STRING, umur INT, ton FLOAT)",
+            "INDEKS sawit PADA blok",
+            "TANAM KE sawit (blok, umur, ton) BIBIT ('A', 3, 1.5), ('B', 7, 4.0), ('A', 9, 5.5), ('C', 12, NULL)",
+            "PUPUK sawit DENGAN ton = ton + 1 DIMANA blok = 'A' AND umur > 5",
+            "GUSUR DARI sawit DIMANA umur >= 12",
+        ];
+        let setup_ok = setup.iter().all(|q| query::run(&mut db, q).is_ok());
+        let found = match query::run(&mut db, "SELECT _id, ton FROM sawit WHERE blok IN ('A', 'C') AND NOT ton < 2") {
+            Ok(query::Output::Rows(rows)) => rows.collect_rows(&mut db).ok(),
+            _ => None,
+        };
+        match found {
+            Some(rows) if setup_ok && rows.len() == 1 && rows[0].get("ton") == Some(&Value::Float(6.5)) => {
+                println!("[SawitDB] Executor Verified: {}", rows[0])
+            }
+            _ => println!("[SawitDB] Error: Executor Mismatch"),
+        }
+
+        // 12. Aggregation Test
+        println!("[SawitDB] Testing Aggregation...");
+        let grouped = match query::run(&mut db, "PANEN blok, COUNT(*) AS n, SUM(ton), AVG(umur) DARI sawit GROUP BY blok HAVING COUNT(*) > 1") {
+            Ok(query::Output::Rows(rows)) => rows.collect_rows(&mut db).ok(),
+            _ => None,
+        };
+        match grouped {
+            Some(rows) if rows.len() == 1 && rows[0].get("SUM(ton)") == Some(&Value::Float(8.0)) && rows[0].get("AVG(umur)") == Some(&Value::Float(6.0)) => {
+                println!("[SawitDB] Aggregation Verified: {}", rows[0])
+            }
+            _ => println!("[SawitDB] Error: Aggregation Mismatch"),
+        }
+
+        // 13. ORDER BY / LIMIT Test (small sort memory forces spilled runs)
+        println!("[SawitDB] Testing External Sort...");
+        {
+            let mut pager = Pager::new(Box::new(RamDisk::new(96 * 1024))).expect("[SawitDB] Failed to init Pager");
+            let mut sorter = sort::ExternalSorter::with_memory(vec![true], 512);
+            for i in 0..300 {
+                let _ = sorter.push(&mut pager, vec![Value::Int((i * 37) % 300)]);
+            }
+            let spilled = sorter.runs();
+            let mut sorted_ok = true;
+            let mut expect = 299;
+            if let Ok(mut records) = sorter.finish(&mut pager) {
+                while let Some(record) = records.next_record(&mut pager) {
+                    sorted_ok &= matches!(record.as_deref(), Ok([Value::Int(v)]) if *v == expect);
+                    expect -= 1;
+                }
+            }
+            let top = match query::run(&mut db, "PANEN _id, umur DARI sawit ORDER BY umur DESC LIMIT 1") {
+                Ok(query::Output::Rows(rows)) => rows.collect_rows(&mut db).ok(),
+                _ => None,
+            };
+            match top {
+                Some(rows) if sorted_ok && expect == -1 && spilled > 1 && pager.temp_pages() == 0 && rows.len() == 1 => {
+                    println!("[SawitDB] Sort Verified: {} runs merged, top row {}", spilled, rows[0])
+                }
+                _ => println!("[SawitDB] Error: Sort Mismatch"),
+            }
+        }
+
+        // 14. Join Test (index nested loop on the key, hash join otherwise)
+        println!("[SawitDB] Testing Joins...");
+        let setup = [
+            "LAHAN kebun (id INT PRIMARY KEY, nama STRING)",
+            "TANAM KE kebun (id, nama) BIBIT (1, 'Riau'), (2, 'Jambi'), (3, 'Aceh')",
+        ];
+        let setup_ok = db.flush().is_ok() && setup.iter().all(|q| query::run(&mut db, q).is_ok()) && db.flush().is_ok();
+        let mut joined = alloc::vec::Vec::new();
+        for q in [
+            "SELECT k.nama, s.blok FROM sawit s JOIN kebun k ON k.id = s.umur / 4",
+            "SELECT k.nama, s.blok FROM kebun k JOIN sawit s ON s.umur / 4 = k.id",
+            "SELECT k.nama, s.blok FROM kebun k LEFT JOIN sawit s ON s.umur / 4 = k.id",
+        ] {
+            if let Ok(query::Output::Rows(rows)) = query::run(&mut db, q) {
+                joined.push(rows.collect_rows(&mut db).map(|r| r.len()).unwrap_or(0));
+            }
+        }
+        match joined.as_slice() {
+            [2, 2, 3] if setup_ok => println!("[SawitDB] Joins Verified: inner 2 rows both ways, left 3 rows"),
+            _ => println!("[SawitDB] Error: Join Mismatch"),
+        }
+        // 15. Transaction Test (ROLLBACK undoes, COMMIT keeps)
+        println!("[SawitDB] Testing Transactions...");
+        let script = [
+            "BEGIN",
+            "TANAM KE kebun (id, nama) BIBIT (4, 'Sumut')",
+            "GUSUR DARI kebun DIMANA id = 1",
+            "ROLLBACK",
+            "MULAI",
+            "PUPUK kebun DENGAN nama = 'Bengkulu' DIMANA id = 3",
+            "SIMPAN",
+        ];
+        let script_ok = script.iter().all(|q| step(&mut db, q).is_ok());
+        let failed = step(&mut db, "BEGIN").is_ok() && step(&mut db, "TANAM KE kebun (id, nama) BIBIT (2, 'Jambi')").is_err();
+        match (step(&mut db, "PANEN * DARI kebun"), step(&mut db, "PANEN * DARI kebun DIMANA nama = 'Bengkulu'")) {
+            (Ok(3), Ok(1)) if script_ok && failed && db.transaction().is_none() => {
+                println!("[SawitDB] Transactions Verified: rollback restored 3 rows, commit kept the update")
+            }
+            _ => println!("[SawitDB] Error: Transaction Mismatch"),
+        }
+        // 16. MVCC Test (an open scan keeps its snapshot while rows change)
+        println!("[SawitDB] Testing MVCC...");
+        let mut seen = 0;
+        let mut changed = false;
+        if let Ok(query::Output::Rows(mut rows)) = query::run(&mut db, "PANEN nama DARI kebun") {
+            if let Ok(Some(_)) = rows.next_row(&mut db) {
+                seen += 1;
+                changed = ["TANAM KE kebun (id, nama) BIBIT (5, 'Banten')", "PUPUK kebun DENGAN nama = 'Lampung'", "GUSUR DARI kebun DIMANA id = 2"]
+                    .iter()
+                    .all(|q| step(&mut db, q).is_ok());
+            }
+            while let Ok(Some(row)) = rows.next_row(&mut db) {
+                if row.get("nama") != Some(&Value::String(String::from("Lampung"))) {
+                    seen += 1;
+                }
+            }
+        }
+        let vacuumed = db.vacuum();
+        match (step(&mut db, "PANEN * DARI kebun DIMANA nama = 'Lampung'"), vacuumed) {
+            (Ok(3), Ok(stats)) if changed && seen == 3 && stats.versions >= 4 => {
+                println!("[SawitDB] MVCC Verified: scan kept 3 old rows, vacuum reclaimed {} versions", stats.versions)
+            }
+            _ => println!("[SawitDB] Error: MVCC Mismatch"),
+        }
+        // 17. Lock Manager Test (sessions wait for each other; a deadlock is broken)
+        println!("[SawitDB] Testing Lock Manager...");
+        let mut waited = false;
+        let mut broken = false;
+        if let Ok(remote) = db.open_session() {
+            let run_as = |db: &mut Database, session, q: &str| {
+                db.enter(session);
+                step(db, q)
+            };
+            waited = run_as(&mut db, 0, "BEGIN").is_ok()
+                && run_as(&mut db, 0, "PUPUK kebun DENGAN nama = 'Aceh' DIMANA id = 1").is_ok()
+                && matches!(run_as(&mut db, remote, "PUPUK kebun DENGAN nama = 'Riau' DIMANA id = 1"), Err(DbError::LockWait))
+                && matches!(db.lock_granted(), Ok(false))
+                && run_as(&mut db, 0, "SIMPAN").is_ok()
+                && { db.enter(remote); matches!(db.lock_granted(), Ok(true)) }
+                && run_as(&mut db, remote, "PUPUK kebun DENGAN nama = 'Riau' DIMANA id = 1").is_ok();
+            broken = run_as(&mut db, 0, "BEGIN").is_ok()
+                && run_as(&mut db, remote, "BEGIN").is_ok()
+                && run_as(&mut db, 0, "PUPUK kebun DENGAN nama = 'Aceh' DIMANA id = 3").is_ok()
+                && run_as(&mut db, remote, "PUPUK kebun DENGAN nama = 'Riau' DIMANA id = 5").is_ok()
+                && matches!(run_as(&mut db, 0, "PUPUK kebun DENGAN nama = 'Aceh' DIMANA id = 5"), Err(DbError::LockWait))
+                && matches!(run_as(&mut db, remote, "PUPUK kebun DENGAN nama = 'Riau' DIMANA id = 3"), Err(DbError::Deadlock))
+                && db.transaction().is_none()
+                && { db.enter(0); matches!(db.lock_granted(), Ok(true)) }
+                && run_as(&mut db, 0, "PUPUK kebun DENGAN nama = 'Aceh' DIMANA id = 5").is_ok()
+                && run_as(&mut db, 0, "SIMPAN").is_ok();
+            let _ = db.close_session(remote);
+        }
+        match (step(&mut db, "PANEN * DARI kebun DIMANA nama = 'Riau'"), step(&mut db, "PANEN * DARI kebun DIMANA nama = 'Aceh'")) {
+            (Ok(1), Ok(2)) if waited && broken => {
+                println!("[SawitDB] Locks Verified: second session waited for the commit, deadlock rolled back one")
+            }
+            _ => println!("[SawitDB] Error: Lock Mismatch"),
+        }
+        // 18. Overflow Test (values longer than a page span overflow pages)
+        println!("[SawitDB] Testing Overflow Pages...");
+        let long = "sawit ".repeat(1000);
+        let longer = "kelapa ".repeat(1100);
+        let read_back = |db: &mut Database| match db.get("arsip", &Value::Int(1)) {
+            Ok(Some(row)) => match row.get("isi") {
+                Some(Value::String(s)) => s.len(),
+                _ => 0,
+            },
+            _ => 0,
+        };
+        let stored = step(&mut db, "LAHAN arsip (id INT PRIMARY KEY, isi STRING)").is_ok()
+            && step(&mut db, &alloc::format!("TANAM KE arsip (id, isi) BIBIT (1, '{}')", long)).is_ok()
+            && read_back(&mut db) == long.len()
+            && step(&mut db, &alloc::format!("PUPUK arsip DENGAN isi = '{}' DIMANA id = 1", longer)).is_ok()
+            && read_back(&mut db) == longer.len();
+        let free_before = db.pager().free_pages().unwrap_or(0);
+        let deleted = step(&mut db, "GUSUR DARI arsip DIMANA id = 1").is_ok() && db.vacuum().is_ok();
+        let reclaimed = db.pager().free_pages().unwrap_or(0).saturating_sub(free_before);
+        match step(&mut db, "PANEN * DARI arsip") {
+            Ok(0) if stored && deleted && reclaimed >= 4 => {
+                println!("[SawitDB] Overflow Verified: {} and {} byte values read back, {} pages reclaimed", long.len(), longer.len(), reclaimed)
+            }
+            _ => println!("[SawitDB] Error: Overflow Mismatch"),
+        }
+    }
+
+    {
+        // 19. Compression Test (pages packed into fewer blocks, survive reopen)
+        println!("[SawitDB] Testing Page Compression...");
+        let mut packed = Database::with_compression(Box::new(RamDisk::new(64 * 1024)), Compression::Lz)
+            .expect("[SawitDB] Failed to open compressed Database");
+        let mut planted = 0;
+        if step(&mut packed, "LAHAN panen (id INT PRIMARY KEY, blok STRING)").is_ok() {
+            while planted < 400 && step(&mut packed, &alloc::format!("TANAM KE panen (id, blok) BIBIT ({}, 'Blok {}')", planted, planted % 8)).is_ok() {
+                planted += 1;
+            }
+        }
+        let (pages, blocks) = packed.pager().packing_stats().unwrap_or((0, 0));
+        let reopened = Database::open(packed.into_disk()).map(|mut db| step(&mut db, "PANEN * DARI panen"));
+        match reopened {
+            Ok(Ok(400)) if planted == 400 && pages > blocks * 2 => {
+                println!("[SawitDB] Compression Verified: 400 rows in {} pages packed into {} blocks", pages, blocks)
+            }
+            _ => println!("[SawitDB] Error: Compression Mismatch"),
+        }
+    }
+
+    {
+        // 20. Encryption Test (data unreadable on the raw device, wrong passphrase rejected)
+        println!("[SawitDB] Testing Encryption...");
+        let hex = |s: &str| (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap_or(0)).collect::<alloc::vec::Vec<u8>>();
+        // Known answers: FIPS-197 C.3, IEEE 1619 XTS-AES-256 vector 10
+        // (first and last block of the 512-byte unit), RFC 7914 PBKDF2
+        let mut aes_key = [0u8; 32];
+        aes_key.iter_mut().enumerate().for_each(|(i, b)| *b = i as u8);
+        let mut block = [0u8; 16];
+        block.copy_from_slice(&hex("00112233445566778899aabbccddeeff"));
+        Aes256::new(&aes_key).encrypt(&mut block);
+        let aes_ok = block[..] == hex("8ea2b7ca516745bfeafc49904b496089")[..];
+        let mut data_key = [0u8; 32];
+        let mut tweak_key = [0u8; 32];
+        data_key.copy_from_slice(&hex("2718281828459045235360287471352662497757247093699959574966967627"));
+        tweak_key.copy_from_slice(&hex("3141592653589793238462643383279502884197169399375105820974944592"));
+        let xts = Xts::new(&data_key, &tweak_key);
+        let plain: alloc::vec::Vec<u8> = (0..512).map(|i| i as u8).collect();
+        let mut unit = plain.clone();
+        xts.encrypt(0xff, &mut unit);
+        let xts_ok = unit[..16] == hex("1c3b3a102f770386e4836c99e370cf9b")[..]
+            && unit[496..] == hex("c4f36ffda9fcea70b9c6e693e148c151")[..];
+        xts.decrypt(0xff, &mut unit);
+        let mut derived = [0u8; 64];
+        sha256::pbkdf2(b"passwd", b"salt", 1, &mut derived);
+        let kdf_ok = derived[..] == hex("55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+            49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783")[..];
+        let known = aes_ok && xts_ok && unit == plain && kdf_ok;
+
+        let mut raw = RamDisk::new(128 * 1024);
+        let key = EncryptedDisk::format(&mut raw, b"kelapa sawit", [0x5a; SALT_SIZE], 1000)
+            .expect("[SawitDB] Failed to format encrypted disk");
+        let mut secret = EncryptedDisk::new(Box::new(raw), key);
+        let mut page = vec![0u8; BLOCK_SIZE];
+        page[..17].copy_from_slice(b"Panen Riau 12 ton");
+        let mut back = vec![0u8; BLOCK_SIZE];
+        let written = secret.write_block(3, &page).is_ok() && secret.read_block(3, &mut back).is_ok() && back == page;
+        let raw = secret.into_inner();
+        let mut image = vec![0u8; BLOCK_SIZE];
+        let hidden = raw.read_block(4, &mut image).is_ok() && !image.windows(4).any(|w| w == b"Riau");
+        let rejected = matches!(EncryptedDisk::unlock(raw.as_ref(), b"kelapa"), Err(BlockError::BadKey));
+        match EncryptedDisk::unlock(raw.as_ref(), b"kelapa sawit") {
+            Ok(key) if known && written && hidden && rejected => {
+                let stored = Database::with_compression(Box::new(EncryptedDisk::new(raw, key)), Compression::Lz)
+                    .map(|mut db| step(&mut db, "LAHAN gaji (id INT PRIMARY KEY, nama STRING)").is_ok()
+                        && step(&mut db, "TANAM KE gaji (id, nama) BIBIT (1, 'Siti'), (2, 'Budi')").is_ok()
+                        && matches!(step(&mut db, "PANEN * DARI gaji"), Ok(2)));
+                match stored {
+                    Ok(true) => println!("[SawitDB] Encryption Verified: known answers match, ciphertext on disk, wrong passphrase rejected"),
+                    _ => println!("[SawitDB] Error: Encrypted Database Mismatch"),
+                }
+            }
+            _ => println!("[SawitDB] Error: Encryption Mismatch"),
+        }
+    }
+
+    {
+        // 21. Backup Test (stream a snapshot, restore it onto a fresh device)
+        println!("[SawitDB] Testing Backup & Restore...");
+        let mut source = Database::open(Box::new(RamDisk::new(96 * 1024))).expect("[SawitDB] Failed to open Database");
+        let mut planted = 0;
+        if step(&mut source, "LAHAN kebun (id INT PRIMARY KEY, lokasi STRING)").is_ok() {
+            while planted < 100 && step(&mut source, &alloc::format!("TANAM KE kebun (id, lokasi) BIBIT ({}, 'Kebun {}')", planted, planted)).is_ok() {
+                planted += 1;
+            }
+        }
+        let mut stream = vec![];
+        let backed_up = Backup::start(&mut source).and_then(|mut backup| {
+            while let Some(chunk) = backup.next_chunk(&mut source)? {
+                stream.extend_from_slice(&chunk);
+            }
+            Ok(backup.manifest())
+        });
+        // Fed in uneven pieces, as they come off the network; a different
+        // device size moves the log
+        let restore = |stream: &[u8]| {
+            let mut restore = Restore::new(Box::new(RamDisk::new(128 * 1024)));
+            stream.chunks(1000).try_for_each(|piece| restore.feed(piece)).and_then(|_| restore.finish())
+        };
+        let restored = restore(&stream).and_then(Database::open).map(|mut db| step(&mut db, "PANEN * DARI kebun DIMANA id >= 50"));
+        // The same stream with a bit flipped in its first page
+        stream[4096 + 100] ^= 1;
+        match (backed_up, restored) {
+            (Ok(manifest), Ok(Ok(50))) if planted == 100 && restore(&stream).is_err() => {
+                println!("[SawitDB] Backup Verified: {} pages restored, tampered stream rejected", manifest.pages)
+            }
+            _ => println!("[SawitDB] Error: Backup Mismatch"),
         }
     }
     println!("[SawitDB] Tests Completed.");
     println!(""); 
     // --- END TESTS ---
 
+    // The shell unlocks this 256KB RamDisk with a passphrase and opens
+    // the shared database on it; until then remote sessions are refused.
+    // A restore builds a second one of the same size beside it.
+    let disk = Box::new(RamDisk::new(256 * 1024));
+
     let mut executor = sawitcore_os::task::simple_executor::SimpleExecutor::new();
 
-    executor.spawn(Task::new(sawitcore_os::task::shell::shell_task()));
+    executor.spawn(Task::new(sawitcore_os::task::shell::shell_task(disk)));
     executor.spawn(Task::new(sawitcore_os::task::net::poll_task()));
     executor.spawn(Task::new(sawitcore_os::task::net::server_task()));
+    executor.spawn(Task::new(sawitcore_os::task::net::backup_task()));
     executor.run();
 
     #[allow(clippy::empty_loop)]
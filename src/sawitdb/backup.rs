@@ -0,0 +1,220 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use crate::drivers::block::{BlockIO, BlockError, BLOCK_SIZE};
+use crate::drivers::sha256::{Sha256, DIGEST_SIZE};
+use super::compress::Compression;
+use super::database::Database;
+use super::error::DbError;
+use super::pager::Pager;
+
+// Backup stream:
+// Manifest (MANIFEST_SIZE bytes):
+//   0..4   : Magic
+//   4..8   : Format version
+//   8..12  : Page size
+//   12..16 : Page count
+//   16..20 : Compression of the source database (see `Compression::code`)
+//   20..24 : Tables in the catalog
+//   24..32 : Reserved (0)
+// Pages 0 .. count, whole, as the source's pager holds them
+// SHA-256 of the manifest and pages (DIGEST_SIZE bytes)
+//
+// All integers are little-endian. Page trailers are not trusted on
+// restore: the pages are logged and stamped anew on the target device.
+const MAGIC: &[u8; 4] = b"SWBK";
+const VERSION: u32 = 1;
+pub const MANIFEST_SIZE: usize = 32;
+
+/// What a backup stream holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Manifest {
+    pub pages: u32,
+    pub compression: Compression,
+    pub tables: u32,
+}
+
+impl Manifest {
+    pub fn encode(&self) -> [u8; MANIFEST_SIZE] {
+        let mut buf = [0u8; MANIFEST_SIZE];
+        buf[0..4].copy_from_slice(MAGIC);
+        buf[4..8].copy_from_slice(&VERSION.to_le_bytes());
+        buf[8..12].copy_from_slice(&(BLOCK_SIZE as u32).to_le_bytes());
+        buf[12..16].copy_from_slice(&self.pages.to_le_bytes());
+        buf[16..20].copy_from_slice(&self.compression.code().to_le_bytes());
+        buf[20..24].copy_from_slice(&self.tables.to_le_bytes());
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self, DbError> {
+        let field = |at: usize| u32::from_le_bytes(buf[at..at + 4].try_into().unwrap_or([0; 4]));
+        if buf.len() < MANIFEST_SIZE || &buf[0..4] != MAGIC {
+            return Err(DbError::Decode("not a backup stream"));
+        }
+        if field(4) != VERSION || field(8) != BLOCK_SIZE as u32 {
+            return Err(DbError::Decode("unsupported backup version"));
+        }
+        let compression = Compression::from_code(field(16)).ok_or(DbError::Decode("unknown backup compression"))?;
+        if field(12) == 0 {
+            return Err(DbError::Decode("backup without a header page"));
+        }
+        Ok(Manifest { pages: field(12), compression, tables: field(20) })
+    }
+}
+
+/// The sending side of a backup: the stream in pieces, so a slow
+/// receiver does not hold up the database between them.
+pub struct Backup {
+    manifest: Manifest,
+    // Pieces handed out: the manifest, then one per page
+    sent: u32,
+    hash: Sha256,
+    done: bool,
+}
+
+impl Backup {
+    /// Starts a backup of the current session's database (see
+    /// `Database::begin_backup`; `LockWait` until writers are done).
+    pub fn start(db: &mut Database) -> Result<Self, DbError> {
+        let pages = db.begin_backup()?;
+        let pager = db.pager();
+        let manifest = Manifest { pages, compression: pager.compression(), tables: pager.num_tables()? };
+        Ok(Backup { manifest, sent: 0, hash: Sha256::new(), done: false })
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        self.manifest
+    }
+
+    /// The next piece of the stream: the manifest, each page in turn, then
+    /// the checksum, which also ends the backup; `None` after that.
+    pub fn next_chunk(&mut self, db: &mut Database) -> Result<Option<Vec<u8>>, DbError> {
+        if self.done {
+            return Ok(None);
+        }
+        let chunk = match self.sent {
+            0 => self.manifest.encode().to_vec(),
+            n if n <= self.manifest.pages => db.backup_page(n - 1)?,
+            _ => {
+                self.done = true;
+                db.end_backup();
+                return Ok(Some(self.hash.clone().finish().to_vec()));
+            }
+        };
+        self.sent += 1;
+        self.hash.update(&chunk);
+        Ok(Some(chunk))
+    }
+}
+
+/// The receiving side: loads a backup stream, fed in pieces of any size,
+/// into a blank device formatted like the source. Page 0 is held back
+/// until the checksum is verified, so a stream that breaks off or does
+/// not match leaves the device an empty database.
+pub struct Restore {
+    disk: Option<Box<dyn BlockIO>>,
+    pager: Option<Pager>,
+    manifest: Option<Manifest>,
+    pending: Vec<u8>,
+    // Pages loaded so far
+    received: u32,
+    header: Vec<u8>,
+    hash: Sha256,
+    complete: bool,
+}
+
+impl Restore {
+    pub fn new(disk: Box<dyn BlockIO>) -> Self {
+        Restore {
+            disk: Some(disk),
+            pager: None,
+            manifest: None,
+            pending: Vec::new(),
+            received: 0,
+            header: Vec::new(),
+            hash: Sha256::new(),
+            complete: false,
+        }
+    }
+
+    /// The manifest, once it has been received.
+    pub fn manifest(&self) -> Option<Manifest> {
+        self.manifest
+    }
+
+    /// True once the whole stream arrived and its checksum matched.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Takes the next bytes of the stream.
+    pub fn feed(&mut self, mut data: &[u8]) -> Result<(), DbError> {
+        while !data.is_empty() {
+            if self.complete {
+                return Err(DbError::Decode("data after backup checksum"));
+            }
+            let want = self.next_size() - self.pending.len();
+            let take = want.min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if take == want {
+                let piece = core::mem::take(&mut self.pending);
+                self.take_piece(piece)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn next_size(&self) -> usize {
+        match self.manifest {
+            None => MANIFEST_SIZE,
+            Some(manifest) if self.received < manifest.pages => BLOCK_SIZE,
+            Some(_) => DIGEST_SIZE,
+        }
+    }
+
+    fn take_piece(&mut self, piece: Vec<u8>) -> Result<(), DbError> {
+        let Some(manifest) = self.manifest else {
+            let manifest = Manifest::decode(&piece)?;
+            let disk = self.disk.take().ok_or(DbError::Decode("backup restored twice"))?;
+            let pager = Pager::with_compression(disk, manifest.compression)?;
+            if pager.page_capacity() < manifest.pages {
+                return Err(DbError::Block(BlockError::DiskFull));
+            }
+            self.hash.update(&piece);
+            self.pager = Some(pager);
+            self.manifest = Some(manifest);
+            return Ok(());
+        };
+        let pager = self.pager.as_mut().ok_or(DbError::Decode("backup without a manifest"))?;
+        if self.received < manifest.pages {
+            self.hash.update(&piece);
+            if self.received == 0 {
+                self.header = piece;
+            } else {
+                pager.write_page(self.received, &piece)?;
+                // Commit before the pool holds more than the log can take
+                if (self.received as usize).is_multiple_of(pager.pool_capacity()) {
+                    pager.flush()?;
+                }
+            }
+            self.received += 1;
+            return Ok(());
+        }
+        if piece[..] != self.hash.clone().finish()[..] {
+            return Err(DbError::Decode("backup checksum mismatch"));
+        }
+        pager.restore_header(&self.header)?;
+        pager.flush()?;
+        self.complete = true;
+        Ok(())
+    }
+
+    /// The restored device, ready to `Database::open`.
+    pub fn finish(self) -> Result<Box<dyn BlockIO>, DbError> {
+        match self.pager {
+            Some(pager) if self.complete => Ok(pager.into_disk()),
+            _ => Err(DbError::Decode("backup stream incomplete")),
+        }
+    }
+}
@@ -1,14 +1,35 @@
 use alloc::vec::Vec;
 use alloc::vec;
-use alloc::string::String;
+use alloc::string::{String, ToString};
 use super::types::Value;
+use super::error::DbError;
+use super::pager::{Pager, PAGE_DATA_SIZE};
+use crate::drivers::block::{BlockError, BLOCK_SIZE};
+use core::convert::TryInto;
+use core::ops::{Bound, RangeBounds};
+
+// Node page layout:
+// 0    : Node type (1 = Leaf, 2 = Internal)
+// 1..3 : Key count (u16)
+// Leaf     : prev leaf (u32), next leaf (u32), then count x (key, value)
+// Internal : first child (u32), then count x (key, child u32)
+const NODE_LEAF: u8 = 1;
+const NODE_INTERNAL: u8 = 2;
+const NODE_HEADER_SIZE: usize = 3;
+const NODE_CAPACITY: usize = PAGE_DATA_SIZE;
+
+/// Largest key/value pair accepted. Keeping entries to a quarter page
+/// guarantees both halves of a split fit in a page again.
+pub const MAX_ENTRY_SIZE: usize = (NODE_CAPACITY - NODE_HEADER_SIZE) / 4;
 
 #[derive(Clone, Debug)]
 pub struct BTreeNode {
     pub is_leaf: bool,
     pub keys: Vec<Value>,
-    pub values: Vec<Value>,     // For leaf nodes
-    pub children: Vec<BTreeNode>, // For internal nodes
+    pub values: Vec<Value>, // For leaf nodes
+    pub children: Vec<u32>, // For internal nodes (page ids)
+    pub prev: u32, // Leaf siblings (0 = none)
+    pub next: u32,
 }
 
 impl BTreeNode {
@@ -18,130 +39,572 @@ impl BTreeNode {
             keys: Vec::new(),
             values: Vec::new(),
             children: Vec::new(),
+            prev: 0,
+            next: 0,
+        }
+    }
+
+    pub fn load(pager: &mut Pager, page_id: u32) -> Result<Self, BlockError> {
+        let page = pager.read_page(page_id)?;
+        Self::decode(&page).ok_or(BlockError::ReadError)
+    }
+
+    pub fn store(&self, pager: &mut Pager, page_id: u32) -> Result<(), BlockError> {
+        let mut page = self.encode();
+        page.resize(BLOCK_SIZE, 0);
+        pager.write_page(page_id, &page)
+    }
+
+    fn decode(page: &[u8]) -> Option<Self> {
+        let is_leaf = match page[0] {
+            NODE_LEAF => true,
+            NODE_INTERNAL => false,
+            _ => return None,
+        };
+        let count = u16::from_le_bytes(page[1..3].try_into().ok()?) as usize;
+        let mut node = BTreeNode::new(is_leaf);
+        let mut pos = NODE_HEADER_SIZE;
+
+        if is_leaf {
+            node.prev = read_u32(page, pos)?;
+            node.next = read_u32(page, pos + 4)?;
+            pos += 8;
+        } else {
+            node.children.push(read_u32(page, pos)?);
+            pos += 4;
+        }
+        for _ in 0..count {
+            let (key, used) = Value::decode_from(&page[pos..])?;
+            node.keys.push(key);
+            pos += used;
+            if is_leaf {
+                let (value, used) = Value::decode_from(&page[pos..])?;
+                node.values.push(value);
+                pos += used;
+            } else {
+                node.children.push(read_u32(page, pos)?);
+                pos += 4;
+            }
         }
+        Some(node)
     }
 
-    fn insert_non_full(&mut self, order: usize, key: Value, value: Value) {
-        let mut i = self.keys.len() as isize - 1;
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.encoded_len());
+        out.push(if self.is_leaf { NODE_LEAF } else { NODE_INTERNAL });
+        out.extend_from_slice(&(self.keys.len() as u16).to_le_bytes());
 
         if self.is_leaf {
-            self.keys.push(key.clone()); 
-            self.values.push(value); 
-
-            while i >= 0 {
-                let current_key = &self.keys[i as usize];
-                if key < *current_key {
-                    self.keys.swap(i as usize + 1, i as usize);
-                    self.values.swap(i as usize + 1, i as usize);
-                    i -= 1;
-                } else {
-                    break;
-                }
+            out.extend_from_slice(&self.prev.to_le_bytes());
+            out.extend_from_slice(&self.next.to_le_bytes());
+            for (key, value) in self.keys.iter().zip(self.values.iter()) {
+                key.encode_into(&mut out);
+                value.encode_into(&mut out);
             }
         } else {
-            while i >= 0 {
-                 let current_key = &self.keys[i as usize];
-                 if key < *current_key {
-                     i -= 1;
-                 } else {
-                     break;
-                 }
-            }
-            i += 1;
-            let idx = i as usize;
-
-            if self.children[idx].keys.len() >= order {
-                self.split_child(idx, order);
-                if key > self.keys[idx] {
-                     self.children[idx + 1].insert_non_full(order, key, value);
-                } else {
-                     self.children[idx].insert_non_full(order, key, value);
-                }
-            } else {
-                 self.children[idx].insert_non_full(order, key, value);
+            out.extend_from_slice(&self.children[0].to_le_bytes());
+            for (key, child) in self.keys.iter().zip(self.children[1..].iter()) {
+                key.encode_into(&mut out);
+                out.extend_from_slice(&child.to_le_bytes());
             }
         }
+        out
+    }
+
+    fn encoded_len(&self) -> usize {
+        NODE_HEADER_SIZE + (0..self.keys.len()).map(|i| self.entry_len(i)).sum::<usize>()
+            + if self.is_leaf { 8 } else { 4 }
+    }
+
+    fn entry_len(&self, i: usize) -> usize {
+        let tail = if self.is_leaf { self.values[i].encoded_len() } else { 4 };
+        self.keys[i].encoded_len() + tail
+    }
+
+    fn is_overfull(&self, order: usize) -> bool {
+        self.keys.len() > order || self.encoded_len() > NODE_CAPACITY
+    }
+
+    /// Index of the first key that is not less than `key`.
+    fn lower_bound(&self, key: &Value) -> usize {
+        self.keys.iter().take_while(|k| *k < key).count()
     }
 
-    fn split_child(&mut self, index: usize, order: usize) {
-        let mid = order / 2;
-        let full_node = &mut self.children[index];
-        let mut new_node = BTreeNode::new(full_node.is_leaf);
+    /// Index of the first key that is greater than `key`.
+    fn upper_bound(&self, key: &Value) -> usize {
+        self.keys.iter().take_while(|k| *k <= key).count()
+    }
+
+    /// A node is underfull when it is light both in keys and in bytes; large
+    /// entries legitimately leave few keys in a full page.
+    fn is_underfull(&self, order: usize) -> bool {
+        self.keys.len() < order / 2 && self.encoded_len() < NODE_CAPACITY / 4
+    }
 
-        let mut right_keys_vec = full_node.keys.split_off(mid);
-        let middle_key = right_keys_vec.remove(0); 
-        new_node.keys = right_keys_vec;
+    /// Appends `right` to this node. For internal nodes the parent's
+    /// separator comes down between the two halves; leaves take over the
+    /// right node's successor link.
+    fn absorb(&mut self, separator: Value, mut right: BTreeNode) {
+        if self.is_leaf {
+            self.next = right.next;
+        } else {
+            self.keys.push(separator);
+        }
+        self.keys.append(&mut right.keys);
+        self.values.append(&mut right.values);
+        self.children.append(&mut right.children);
+    }
+
+    /// Splits an overfull node roughly in half by encoded size. Returns the
+    /// separator to push into the parent and the new right sibling.
+    fn split(&mut self) -> (Value, BTreeNode) {
+        let total: usize = (0..self.keys.len()).map(|i| self.entry_len(i)).sum();
+        // Leave at least one key on each side (plus the separator for internals)
+        let last = if self.is_leaf { self.keys.len() - 1 } else { self.keys.len() - 2 };
+        let mut mid = 0;
+        let mut left_bytes = 0;
+        while mid < last && left_bytes < total / 2 {
+            left_bytes += self.entry_len(mid);
+            mid += 1;
+        }
+        let mid = mid.max(1);
 
-        if full_node.is_leaf {
-            let right_values = full_node.values.split_off(mid);
-             new_node.values = right_values;
-             // Remove value corresponding to pivot if necessary (Go logic implied it)
-             if new_node.is_leaf && !new_node.values.is_empty(){
-                  new_node.values.remove(0);
-             }
+        let mut right = BTreeNode::new(self.is_leaf);
+        if self.is_leaf {
+            // B+tree leaf: separator is copied up, entries stay in the leaves
+            right.keys = self.keys.split_off(mid);
+            right.values = self.values.split_off(mid);
+            (right.keys[0].clone(), right)
         } else {
-             let right_children = full_node.children.split_off(mid);
-             new_node.children = right_children;
+            // Internal: separator moves up, its right child starts the sibling
+            right.keys = self.keys.split_off(mid + 1);
+            right.children = self.children.split_off(mid + 1);
+            let separator = self.keys.pop().unwrap();
+            (separator, right)
         }
+    }
+}
+
+fn read_u32(page: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(page.get(pos..pos + 4)?.try_into().ok()?))
+}
 
-        self.keys.insert(index, middle_key);
-        self.children.insert(index + 1, new_node);
+/// Links the split-off leaf `right` in after `left` and repoints the old
+/// successor back at it. Neither node is written back by this call.
+fn link_leaf(pager: &mut Pager, left: &mut BTreeNode, left_id: u32, right: &mut BTreeNode, right_id: u32)
+    -> Result<(), BlockError> {
+    right.prev = left_id;
+    right.next = left.next;
+    left.next = right_id;
+    if right.next != 0 {
+        set_leaf_prev(pager, right.next, right_id)?;
     }
+    Ok(())
 }
 
+fn set_leaf_prev(pager: &mut Pager, page_id: u32, prev: u32) -> Result<(), BlockError> {
+    let mut node = BTreeNode::load(pager, page_id)?;
+    node.prev = prev;
+    node.store(pager, page_id)
+}
+
+/// Page-backed B+tree. Every node lives in its own `Pager` page and children
+/// are referenced by page id. The root page never moves (a root split pushes
+/// both halves into fresh pages), so its id is all that is needed to reopen
+/// the index. Leaves are doubly linked for ordered scans.
+///
+/// A key may hold any number of values (kept in insertion order) unless the
+/// index is `unique`, in which case inserting an existing key fails.
 pub struct BTreeIndex {
     pub order: usize,
-    pub root: BTreeNode,
+    pub unique: bool,
+    pub root: u32,
     pub name: String,
     pub key_field: String,
 }
 
 impl BTreeIndex {
-    pub fn new(order: usize, name: String, key_field: String) -> Self {
-        let actual_order = if order == 0 { 32 } else { order };
+    /// Allocates an empty root leaf. Keep `root` somewhere (e.g. the table
+    /// catalog) to reopen the index later.
+    pub fn create(pager: &mut Pager, order: usize, unique: bool, name: String, key_field: String) -> Result<Self, BlockError> {
+        let root = pager.alloc_page()?;
+        BTreeNode::new(true).store(pager, root)?;
+        Ok(Self::with_root(order, unique, root, name, key_field))
+    }
+
+    /// Reopens the index rooted at page `root`.
+    pub fn open(pager: &mut Pager, root: u32, order: usize, unique: bool, name: String, key_field: String) -> Result<Self, BlockError> {
+        BTreeNode::load(pager, root)?;
+        Ok(Self::with_root(order, unique, root, name, key_field))
+    }
+
+    /// Returns every page of the index, root included, to the free list.
+    pub fn destroy(self, pager: &mut Pager) -> Result<(), BlockError> {
+        let mut pending = vec![self.root];
+        while let Some(page_id) = pending.pop() {
+            let node = BTreeNode::load(pager, page_id)?;
+            pending.extend_from_slice(&node.children);
+            pager.free_page(page_id)?;
+        }
+        Ok(())
+    }
+
+    fn with_root(order: usize, unique: bool, root: u32, name: String, key_field: String) -> Self {
+        // Internal splits need a key for each side plus the separator
+        let actual_order = if order == 0 { 32 } else { order.max(3) };
         BTreeIndex {
             order: actual_order,
-            root: BTreeNode::new(true),
+            unique,
+            root,
             name,
             key_field,
         }
     }
 
-    pub fn insert(&mut self, key: Value, value: Value) {
-        if self.root.keys.len() >= self.order {
-            let new_root = BTreeNode::new(false);
-            let old_root = core::mem::replace(&mut self.root, new_root);
-            self.root.children.push(old_root);
-            
-            self.root.split_child(0, self.order);
-            self.root.insert_non_full(self.order, key, value);
+    /// Adds `value` under `key`, after any values already stored for it.
+    pub fn insert(&self, pager: &mut Pager, key: Value, value: Value) -> Result<(), DbError> {
+        let len = key.encoded_len() + value.encoded_len();
+        if len > MAX_ENTRY_SIZE {
+            return Err(DbError::RecordTooLarge(len));
+        }
+        if self.unique && self.range(pager, key.clone()..=key.clone()).next().transpose()?.is_some() {
+            return Err(DbError::DuplicateKey(key.to_string()));
+        }
+        let mut root = BTreeNode::load(pager, self.root)?;
+        if let Some((separator, mut right)) = self.insert_into(pager, &mut root, key, value)? {
+            // Root split: move the left half out as well so the root id stays put
+            let left_id = pager.alloc_page()?;
+            let right_id = pager.alloc_page()?;
+            if right.is_leaf {
+                link_leaf(pager, &mut root, left_id, &mut right, right_id)?;
+            }
+            root.store(pager, left_id)?;
+            right.store(pager, right_id)?;
+
+            let mut new_root = BTreeNode::new(false);
+            new_root.keys.push(separator);
+            new_root.children.push(left_id);
+            new_root.children.push(right_id);
+            root = new_root;
+        }
+        Ok(root.store(pager, self.root)?)
+    }
+
+    /// Inserts below `node` (not yet written back by this call). Returns the
+    /// split-off sibling if `node` overflowed.
+    fn insert_into(&self, pager: &mut Pager, node: &mut BTreeNode, key: Value, value: Value)
+        -> Result<Option<(Value, BTreeNode)>, BlockError> {
+        let idx = node.upper_bound(&key);
+        if node.is_leaf {
+            node.keys.insert(idx, key);
+            node.values.insert(idx, value);
+        } else {
+            let child_id = node.children[idx];
+            let mut child = BTreeNode::load(pager, child_id)?;
+            if let Some((separator, mut right)) = self.insert_into(pager, &mut child, key, value)? {
+                let right_id = pager.alloc_page()?;
+                if right.is_leaf {
+                    link_leaf(pager, &mut child, child_id, &mut right, right_id)?;
+                }
+                right.store(pager, right_id)?;
+                node.keys.insert(idx, separator);
+                node.children.insert(idx + 1, right_id);
+            }
+            child.store(pager, child_id)?;
+        }
+
+        if node.is_overfull(self.order) {
+            Ok(Some(node.split()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// All values stored under `key`, in insertion order.
+    pub fn search(&self, pager: &mut Pager, key: &Value) -> Result<Vec<Value>, BlockError> {
+        self.range(pager, key.clone()..=key.clone()).map(|entry| entry.map(|(_, v)| v)).collect()
+    }
+
+    /// Ordered cursor over the entries whose key lies in `range`. It walks
+    /// the leaf chain from both ends, so `.rev()` scans in descending order.
+    pub fn range<'a, R: RangeBounds<Value>>(&self, pager: &'a mut Pager, range: R) -> BTreeCursor<'a> {
+        BTreeCursor { pager, cursor: RangeCursor::new(self.root, range) }
+    }
+
+    /// Ordered cursor over every entry.
+    pub fn iter<'a>(&self, pager: &'a mut Pager) -> BTreeCursor<'a> {
+        self.range(pager, ..)
+    }
+
+    /// Removes every entry with `key`. Returns how many were removed.
+    pub fn delete(&self, pager: &mut Pager, key: &Value) -> Result<usize, BlockError> {
+        let mut removed = 0;
+        while self.remove(pager, key, None)? {
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Removes a single `key`/`value` pair, leaving other values stored
+    /// under the same key alone. Returns false if the pair was not found.
+    pub fn delete_entry(&self, pager: &mut Pager, key: &Value, value: &Value) -> Result<bool, BlockError> {
+        self.remove(pager, key, Some(value))
+    }
+
+    fn remove(&self, pager: &mut Pager, key: &Value, value: Option<&Value>) -> Result<bool, BlockError> {
+        let mut root = BTreeNode::load(pager, self.root)?;
+        if !self.remove_from(pager, &mut root, key, value)? {
+            return Ok(false);
+        }
+        // Collapse a root left with a single child into the fixed root page
+        while !root.is_leaf && root.keys.is_empty() {
+            let child_id = root.children[0];
+            root = BTreeNode::load(pager, child_id)?;
+            pager.free_page(child_id)?;
+        }
+        root.store(pager, self.root)?;
+        Ok(true)
+    }
+
+    /// Removes one matching entry below `node` (written back by the caller)
+    /// and rebalances the child it descended into if that child underflowed.
+    fn remove_from(&self, pager: &mut Pager, node: &mut BTreeNode, key: &Value, value: Option<&Value>)
+        -> Result<bool, BlockError> {
+        if node.is_leaf {
+            let found = (node.lower_bound(key)..node.upper_bound(key))
+                .find(|&i| value.is_none_or(|v| &node.values[i] == v));
+            if let Some(i) = found {
+                node.keys.remove(i);
+                node.values.remove(i);
+                return Ok(true);
+            }
+            return Ok(false);
+        }
+
+        for i in node.lower_bound(key)..=node.upper_bound(key) {
+            let child_id = node.children[i];
+            let mut child = BTreeNode::load(pager, child_id)?;
+            if self.remove_from(pager, &mut child, key, value)? {
+                if child.is_underfull(self.order) && node.children.len() > 1 {
+                    self.rebalance(pager, node, i, child)?;
+                } else {
+                    child.store(pager, child_id)?;
+                }
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Fixes an underfull child of `parent` at `idx` by merging it with a
+    /// sibling, or if the two do not fit in one page, by redistributing
+    /// their entries evenly.
+    fn rebalance(&self, pager: &mut Pager, parent: &mut BTreeNode, idx: usize, child: BTreeNode)
+        -> Result<(), BlockError> {
+        let (left_idx, mut left, right) = if idx > 0 {
+            let left = BTreeNode::load(pager, parent.children[idx - 1])?;
+            (idx - 1, left, child)
         } else {
-            self.root.insert_non_full(self.order, key, value);
+            let right = BTreeNode::load(pager, parent.children[idx + 1])?;
+            (idx, child, right)
+        };
+        let left_id = parent.children[left_idx];
+        let right_id = parent.children[left_idx + 1];
+
+        let separator = parent.keys.remove(left_idx);
+        left.absorb(separator, right);
+
+        if left.is_overfull(self.order) {
+            // Redistribute: split the combined entries again in the middle
+            let (separator, mut right) = left.split();
+            if right.is_leaf {
+                link_leaf(pager, &mut left, left_id, &mut right, right_id)?;
+            }
+            left.store(pager, left_id)?;
+            right.store(pager, right_id)?;
+            parent.keys.insert(left_idx, separator);
+        } else {
+            // Merge: everything lives in the left page now
+            if left.is_leaf && left.next != 0 {
+                set_leaf_prev(pager, left.next, left_id)?;
+            }
+            left.store(pager, left_id)?;
+            parent.children.remove(left_idx + 1);
+            pager.free_page(right_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Leaf a cursor end is parked on. `idx` is the next entry to return going
+/// forward, or one past it going backward.
+struct LeafPos {
+    page_id: u32,
+    node: BTreeNode,
+    idx: usize,
+}
+
+/// Position of a range scan that does not hold on to the pager (see
+/// `BTreeCursor` for the iterator form). Each end descends from the root
+/// once, on first use, and then follows the leaf links. Leaves are cached,
+/// so the tree must not change while a cursor is open.
+pub struct RangeCursor {
+    root: u32,
+    start: Bound<Value>,
+    end: Bound<Value>,
+    front: Option<LeafPos>,
+    back: Option<LeafPos>,
+    done: bool,
+}
+
+impl RangeCursor {
+    pub fn new<R: RangeBounds<Value>>(root: u32, range: R) -> Self {
+        RangeCursor {
+            root,
+            start: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            front: None,
+            back: None,
+            done: false,
         }
     }
-    
-    pub fn search(&self, key: &Value) -> Vec<Value> {
-        self.search_node(&self.root, key)
+
+    pub fn next_entry(&mut self, pager: &mut Pager) -> Option<Result<(Value, Value), BlockError>> {
+        if self.done {
+            return None;
+        }
+        let step = self.step_front(pager);
+        self.finish(step)
     }
-    
-    fn search_node(&self, node: &BTreeNode, key: &Value) -> Vec<Value> {
-        let mut i = 0;
-        while i < node.keys.len() && key > &node.keys[i] {
-            i += 1;
+
+    pub fn next_back_entry(&mut self, pager: &mut Pager) -> Option<Result<(Value, Value), BlockError>> {
+        if self.done {
+            return None;
         }
-        
-        if i < node.keys.len() && key == &node.keys[i] {
+        let step = self.step_back(pager);
+        self.finish(step)
+    }
+
+    /// Descends to the leaf position of the start (`front`) or end bound.
+    fn seek(&mut self, pager: &mut Pager, front: bool) -> Result<LeafPos, BlockError> {
+        let bound = if front { &self.start } else { &self.end };
+        let mut page_id = self.root;
+        loop {
+            let node = BTreeNode::load(pager, page_id)?;
+            let idx = match (bound, front) {
+                (Bound::Unbounded, true) => 0,
+                (Bound::Unbounded, false) => node.keys.len(),
+                (Bound::Included(k), true) | (Bound::Excluded(k), false) => node.lower_bound(k),
+                (Bound::Excluded(k), true) | (Bound::Included(k), false) => node.upper_bound(k),
+            };
             if node.is_leaf {
-                return vec![node.values[i].clone()];
-            } else {
-                return self.search_node(&node.children[i + 1], key);
+                return Ok(LeafPos { page_id, node, idx });
             }
+            page_id = node.children[idx];
         }
-        
-        if node.is_leaf {
-            return Vec::new(); // Empty
+    }
+
+    /// True once the two ends have met on the same leaf.
+    fn exhausted(&self) -> bool {
+        match (&self.front, &self.back) {
+            (Some(f), Some(b)) => f.page_id == b.page_id && f.idx >= b.idx,
+            _ => false,
+        }
+    }
+
+    fn step_front(&mut self, pager: &mut Pager) -> Result<Option<(Value, Value)>, BlockError> {
+        if self.front.is_none() {
+            self.front = Some(self.seek(pager, true)?);
+        }
+        loop {
+            if self.exhausted() {
+                return Ok(None);
+            }
+            let pos = self.front.as_mut().unwrap();
+            if pos.idx < pos.node.keys.len() {
+                let key = &pos.node.keys[pos.idx];
+                let in_range = match &self.end {
+                    Bound::Included(hi) => key <= hi,
+                    Bound::Excluded(hi) => key < hi,
+                    Bound::Unbounded => true,
+                };
+                if !in_range {
+                    return Ok(None);
+                }
+                pos.idx += 1;
+                return Ok(Some((key.clone(), pos.node.values[pos.idx - 1].clone())));
+            }
+            let next = pos.node.next;
+            if next == 0 {
+                return Ok(None);
+            }
+            *pos = LeafPos { page_id: next, node: BTreeNode::load(pager, next)?, idx: 0 };
+        }
+    }
+
+    fn step_back(&mut self, pager: &mut Pager) -> Result<Option<(Value, Value)>, BlockError> {
+        if self.back.is_none() {
+            self.back = Some(self.seek(pager, false)?);
+        }
+        loop {
+            if self.exhausted() {
+                return Ok(None);
+            }
+            let pos = self.back.as_mut().unwrap();
+            if pos.idx > 0 {
+                let key = &pos.node.keys[pos.idx - 1];
+                let in_range = match &self.start {
+                    Bound::Included(lo) => key >= lo,
+                    Bound::Excluded(lo) => key > lo,
+                    Bound::Unbounded => true,
+                };
+                if !in_range {
+                    return Ok(None);
+                }
+                pos.idx -= 1;
+                return Ok(Some((key.clone(), pos.node.values[pos.idx].clone())));
+            }
+            let prev = pos.node.prev;
+            if prev == 0 {
+                return Ok(None);
+            }
+            let node = BTreeNode::load(pager, prev)?;
+            *pos = LeafPos { page_id: prev, idx: node.keys.len(), node };
+        }
+    }
+
+    /// Stops the cursor for good after the end of the range or an error.
+    fn finish(&mut self, step: Result<Option<(Value, Value)>, BlockError>) -> Option<Result<(Value, Value), BlockError>> {
+        match step {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
         }
-        
-        self.search_node(&node.children[i], key)
+    }
+}
+
+/// Double-ended iterator returned by `BTreeIndex::range`.
+pub struct BTreeCursor<'a> {
+    pager: &'a mut Pager,
+    cursor: RangeCursor,
+}
+
+impl<'a> Iterator for BTreeCursor<'a> {
+    type Item = Result<(Value, Value), BlockError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cursor.next_entry(self.pager)
+    }
+}
+
+impl<'a> DoubleEndedIterator for BTreeCursor<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.cursor.next_back_entry(self.pager)
     }
 }
@@ -0,0 +1,183 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use alloc::vec;
+use crate::drivers::block::{BlockIO, BlockError, BLOCK_SIZE};
+
+pub const DEFAULT_POOL_FRAMES: usize = 32;
+
+// Page id marking a frame that holds nothing
+const NO_PAGE: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PoolStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub writebacks: u64,
+}
+
+impl PoolStats {
+    /// Hit ratio in percent (0 when nothing was requested yet).
+    pub fn hit_ratio(&self) -> u64 {
+        (self.hits * 100).checked_div(self.hits + self.misses).unwrap_or(0)
+    }
+}
+
+struct Frame {
+    page_id: u32,
+    data: Vec<u8>,
+    dirty: bool,
+    pins: u32,
+    last_used: u64,
+}
+
+/// A pinned page. While held, the frame it refers to cannot be evicted.
+/// Give it back with `Pager::unpin_page`.
+#[derive(Debug)]
+pub struct PageHandle {
+    pub(crate) page_id: u32,
+    pub(crate) frame: usize,
+}
+
+impl PageHandle {
+    pub fn page_id(&self) -> u32 {
+        self.page_id
+    }
+}
+
+/// Fixed-size page cache sitting between the `Pager` and its `BlockIO`.
+/// Eviction picks the least recently used unpinned frame; dirty frames are
+/// written back when evicted or on `flush`.
+pub struct BufferPool {
+    frames: Vec<Frame>,
+    capacity: usize,
+    page_table: BTreeMap<u32, usize>,
+    tick: u64,
+    stats: PoolStats,
+}
+
+impl BufferPool {
+    pub fn new(capacity: usize) -> Self {
+        BufferPool {
+            frames: Vec::new(),
+            capacity: capacity.max(1),
+            page_table: BTreeMap::new(),
+            tick: 0,
+            stats: PoolStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Pins `page_id`, reading it from `disk` on a miss. With `load == false`
+    /// the caller is about to overwrite the whole page, so a miss skips the read.
+    pub fn pin(&mut self, disk: &mut dyn BlockIO, page_id: u32, load: bool) -> Result<PageHandle, BlockError> {
+        self.tick += 1;
+        if let Some(&frame) = self.page_table.get(&page_id) {
+            self.stats.hits += 1;
+            let f = &mut self.frames[frame];
+            f.pins += 1;
+            f.last_used = self.tick;
+            return Ok(PageHandle { page_id, frame });
+        }
+
+        self.stats.misses += 1;
+        let frame = self.free_frame(disk)?;
+        let f = &mut self.frames[frame];
+        if load {
+            if let Err(e) = disk.read_block(page_id, &mut f.data) {
+                // Leave the frame unused rather than caching garbage
+                f.page_id = NO_PAGE;
+                return Err(e);
+            }
+        } else {
+            f.data.fill(0);
+        }
+        f.page_id = page_id;
+        f.dirty = false;
+        f.pins = 1;
+        f.last_used = self.tick;
+        self.page_table.insert(page_id, frame);
+        Ok(PageHandle { page_id, frame })
+    }
+
+    pub fn unpin(&mut self, handle: PageHandle) {
+        let f = &mut self.frames[handle.frame];
+        f.pins = f.pins.saturating_sub(1);
+    }
+
+    pub fn page(&self, handle: &PageHandle) -> &[u8] {
+        &self.frames[handle.frame].data
+    }
+
+    pub fn page_mut(&mut self, handle: &PageHandle) -> &mut [u8] {
+        let f = &mut self.frames[handle.frame];
+        f.dirty = true;
+        &mut f.data
+    }
+
+    /// Writes every dirty frame back to `disk`.
+    pub fn flush(&mut self, disk: &mut dyn BlockIO) -> Result<(), BlockError> {
+        for f in self.frames.iter_mut() {
+            if f.dirty {
+                disk.write_block(f.page_id, &f.data)?;
+                f.dirty = false;
+                self.stats.writebacks += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets every cached page, dirty or not, without writing anything
+    /// back. No page may be pinned.
+    pub fn discard(&mut self) {
+        for f in self.frames.iter_mut() {
+            f.page_id = NO_PAGE;
+            f.dirty = false;
+            f.pins = 0;
+        }
+        self.page_table.clear();
+    }
+
+    /// Finds a frame to load a new page into: a never-used one while the pool
+    /// is still growing, otherwise the LRU unpinned frame.
+    fn free_frame(&mut self, disk: &mut dyn BlockIO) -> Result<usize, BlockError> {
+        if let Some(i) = self.frames.iter().position(|f| f.page_id == NO_PAGE) {
+            return Ok(i);
+        }
+        if self.frames.len() < self.capacity {
+            self.frames.push(Frame {
+                page_id: NO_PAGE,
+                data: vec![0u8; BLOCK_SIZE],
+                dirty: false,
+                pins: 0,
+                last_used: 0,
+            });
+            return Ok(self.frames.len() - 1);
+        }
+
+        let victim = self.frames.iter()
+            .enumerate()
+            .filter(|(_, f)| f.pins == 0)
+            .min_by_key(|(_, f)| f.last_used)
+            .map(|(i, _)| i)
+            .ok_or(BlockError::PoolExhausted)?;
+
+        let f = &mut self.frames[victim];
+        if f.dirty {
+            disk.write_block(f.page_id, &f.data)?;
+            f.dirty = false;
+            self.stats.writebacks += 1;
+        }
+        self.page_table.remove(&f.page_id);
+        f.page_id = NO_PAGE;
+        self.stats.evictions += 1;
+        Ok(victim)
+    }
+}
@@ -0,0 +1,223 @@
+use alloc::vec::Vec;
+use alloc::vec;
+use alloc::string::String;
+use super::pager::{Pager, PAGE_DATA_SIZE};
+use super::types::ValueType;
+use super::error::DbError;
+use crate::drivers::block::{BlockError, BLOCK_SIZE};
+use core::convert::TryInto;
+
+// Catalog page layout (chained from the page-0 "Catalog Page" field):
+// 0..4 : Next catalog page (0 = last)
+// 4..6 : Entry count (u16)
+// 6..  : Entries
+//
+// Entry: name, key field, index root page (u32), first and last heap page
+// (u32 each), column count (u16), then per column its name and type tag,
+// then secondary index count (u16) and per index its column and root page.
+// Names are u8 length-prefixed UTF-8. An entry never spans pages.
+const CATALOG_HEADER_SIZE: usize = 6;
+
+pub const MAX_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub ty: ValueType,
+}
+
+impl Column {
+    pub fn new(name: &str, ty: ValueType) -> Self {
+        Column { name: String::from(name), ty }
+    }
+}
+
+/// Secondary (non-unique) index on one column, mapping its values to
+/// record ids.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexInfo {
+    pub column: String,
+    pub root_page: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct TableInfo {
+    pub name: String,
+    pub key_field: String,
+    pub root_page: u32,
+    pub heap_first: u32,
+    pub heap_last: u32,
+    pub schema: Vec<Column>,
+    pub indexes: Vec<IndexInfo>,
+}
+
+impl TableInfo {
+    fn encoded_len(&self) -> usize {
+        let cols: usize = self.schema.iter().map(|c| 1 + c.name.len() + 1).sum();
+        let indexes: usize = self.indexes.iter().map(|i| 1 + i.column.len() + 4).sum();
+        1 + self.name.len() + 1 + self.key_field.len() + 4 + 8 + 2 + cols + 2 + indexes
+    }
+
+    pub fn index_on(&self, column: &str) -> Option<&IndexInfo> {
+        self.indexes.iter().find(|i| i.column == column)
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        write_name(out, &self.name);
+        write_name(out, &self.key_field);
+        out.extend_from_slice(&self.root_page.to_le_bytes());
+        out.extend_from_slice(&self.heap_first.to_le_bytes());
+        out.extend_from_slice(&self.heap_last.to_le_bytes());
+        out.extend_from_slice(&(self.schema.len() as u16).to_le_bytes());
+        for col in &self.schema {
+            write_name(out, &col.name);
+            out.push(col.ty.tag());
+        }
+        out.extend_from_slice(&(self.indexes.len() as u16).to_le_bytes());
+        for index in &self.indexes {
+            write_name(out, &index.column);
+            out.extend_from_slice(&index.root_page.to_le_bytes());
+        }
+    }
+
+    fn decode(buf: &[u8], pos: &mut usize) -> Option<Self> {
+        let name = read_name(buf, pos)?;
+        let key_field = read_name(buf, pos)?;
+        let root_page = u32::from_le_bytes(buf.get(*pos..*pos + 4)?.try_into().ok()?);
+        let heap_first = u32::from_le_bytes(buf.get(*pos + 4..*pos + 8)?.try_into().ok()?);
+        let heap_last = u32::from_le_bytes(buf.get(*pos + 8..*pos + 12)?.try_into().ok()?);
+        let cols = u16::from_le_bytes(buf.get(*pos + 12..*pos + 14)?.try_into().ok()?);
+        *pos += 14;
+        let mut schema = Vec::with_capacity(cols as usize);
+        for _ in 0..cols {
+            let name = read_name(buf, pos)?;
+            let ty = ValueType::from_tag(*buf.get(*pos)?)?;
+            *pos += 1;
+            schema.push(Column { name, ty });
+        }
+        let count = u16::from_le_bytes(buf.get(*pos..*pos + 2)?.try_into().ok()?);
+        *pos += 2;
+        let mut indexes = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let column = read_name(buf, pos)?;
+            let root_page = u32::from_le_bytes(buf.get(*pos..*pos + 4)?.try_into().ok()?);
+            *pos += 4;
+            indexes.push(IndexInfo { column, root_page });
+        }
+        Some(TableInfo { name, key_field, root_page, heap_first, heap_last, schema, indexes })
+    }
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    out.push(name.len() as u8);
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn read_name(buf: &[u8], pos: &mut usize) -> Option<String> {
+    let len = *buf.get(*pos)? as usize;
+    let s = core::str::from_utf8(buf.get(*pos + 1..*pos + 1 + len)?).ok()?;
+    *pos += 1 + len;
+    Some(String::from(s))
+}
+
+/// Checks a table or column name fits the catalog encoding.
+pub fn check_name(name: &str) -> Result<(), DbError> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(DbError::NameTooLong(String::from(name)));
+    }
+    Ok(())
+}
+
+/// Checks a table's catalog entry fits in one catalog page.
+pub fn check_entry(info: &TableInfo) -> Result<(), DbError> {
+    if CATALOG_HEADER_SIZE + info.encoded_len() > PAGE_DATA_SIZE {
+        return Err(DbError::SchemaTooLarge(info.name.clone()));
+    }
+    Ok(())
+}
+
+/// In-memory copy of the table catalog. Changes are written back to the
+/// catalog pages with `save`.
+pub struct Catalog {
+    tables: Vec<TableInfo>,
+    pages: Vec<u32>, // Catalog page chain currently on disk
+}
+
+impl Catalog {
+    pub fn load(pager: &mut Pager) -> Result<Self, BlockError> {
+        let mut catalog = Catalog { tables: Vec::new(), pages: Vec::new() };
+        let mut page_id = pager.catalog_page()?;
+        while page_id != 0 {
+            let page = pager.read_page(page_id)?;
+            let count = u16::from_le_bytes(page[4..6].try_into().unwrap_or([0; 2]));
+            let mut pos = CATALOG_HEADER_SIZE;
+            for _ in 0..count {
+                let info = TableInfo::decode(&page[..PAGE_DATA_SIZE], &mut pos)
+                    .ok_or(BlockError::Corrupt { page_id })?;
+                catalog.tables.push(info);
+            }
+            catalog.pages.push(page_id);
+            page_id = u32::from_le_bytes(page[0..4].try_into().unwrap_or([0; 4]));
+        }
+        Ok(catalog)
+    }
+
+    /// Rewrites the catalog pages from the in-memory table list, growing or
+    /// shrinking the page chain as needed.
+    pub fn save(&mut self, pager: &mut Pager) -> Result<(), DbError> {
+        // Pack entries into page-sized chunks
+        let mut chunks: Vec<(u16, Vec<u8>)> = vec![(0, Vec::new())];
+        for info in &self.tables {
+            check_entry(info)?;
+            let last = chunks.last_mut().unwrap();
+            if CATALOG_HEADER_SIZE + last.1.len() + info.encoded_len() > PAGE_DATA_SIZE {
+                chunks.push((0, Vec::new()));
+            }
+            let last = chunks.last_mut().unwrap();
+            info.encode_into(&mut last.1);
+            last.0 += 1;
+        }
+        if self.tables.is_empty() {
+            chunks.clear();
+        }
+
+        while self.pages.len() < chunks.len() {
+            self.pages.push(pager.alloc_page()?);
+        }
+        while self.pages.len() > chunks.len() {
+            pager.free_page(self.pages.pop().unwrap())?;
+        }
+
+        for (i, (count, entries)) in chunks.iter().enumerate() {
+            let next = self.pages.get(i + 1).copied().unwrap_or(0);
+            let mut page = vec![0u8; BLOCK_SIZE];
+            page[0..4].copy_from_slice(&next.to_le_bytes());
+            page[4..6].copy_from_slice(&count.to_le_bytes());
+            page[CATALOG_HEADER_SIZE..CATALOG_HEADER_SIZE + entries.len()].copy_from_slice(entries);
+            pager.write_page(self.pages[i], &page)?;
+        }
+        pager.set_catalog_page(self.pages.first().copied().unwrap_or(0))?;
+        Ok(pager.set_num_tables(self.tables.len() as u32)?)
+    }
+
+    pub fn tables(&self) -> &[TableInfo] {
+        &self.tables
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TableInfo> {
+        self.tables.iter().find(|t| t.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut TableInfo> {
+        self.tables.iter_mut().find(|t| t.name == name)
+    }
+
+    pub fn add(&mut self, info: TableInfo) {
+        self.tables.push(info);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<TableInfo> {
+        let pos = self.tables.iter().position(|t| t.name == name)?;
+        Some(self.tables.remove(pos))
+    }
+}
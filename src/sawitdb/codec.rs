@@ -0,0 +1,304 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use super::types::{Value, TAG_NULL, TAG_BOOL, TAG_INT, TAG_FLOAT, TAG_STRING, TAG_BYTES, TAG_TIMESTAMP};
+use super::error::DbError;
+
+// Value encoding (used on pages and inside records):
+//   tag (u8), then
+//   Null      : -
+//   Bool      : 0 / 1
+//   Int       : zigzag varint
+//   Float     : f64 bits, little-endian
+//   String    : varint length, UTF-8 bytes
+//   Bytes     : varint length, bytes
+//   Timestamp : zigzag varint (microseconds)
+//
+// Record encoding: version (u8), varint value count, then the values.
+
+/// Version byte written in front of every encoded record.
+pub const CODEC_VERSION: u8 = 1;
+
+pub fn write_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+pub fn read_varint(buf: &[u8], pos: &mut usize) -> Result<u64, DbError> {
+    let mut v = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buf.get(*pos).ok_or(DbError::Decode("truncated varint"))?;
+        *pos += 1;
+        v |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(v);
+        }
+    }
+    Err(DbError::Decode("varint too long"))
+}
+
+pub fn varint_len(mut v: u64) -> usize {
+    let mut len = 1;
+    while v >= 0x80 {
+        v >>= 7;
+        len += 1;
+    }
+    len
+}
+
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn unzigzag(v: u64) -> i64 {
+    (v >> 1) as i64 ^ -((v & 1) as i64)
+}
+
+/// Number of bytes `encode_value` appends for `value`.
+pub fn value_len(value: &Value) -> usize {
+    1 + match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Int(v) | Value::Timestamp(v) => varint_len(zigzag(*v)),
+        Value::Float(_) => 8,
+        Value::String(s) => varint_len(s.len() as u64) + s.len(),
+        Value::Bytes(b) => varint_len(b.len() as u64) + b.len(),
+    }
+}
+
+pub fn encode_value(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(TAG_NULL),
+        Value::Bool(v) => {
+            out.push(TAG_BOOL);
+            out.push(*v as u8);
+        }
+        Value::Int(v) => {
+            out.push(TAG_INT);
+            write_varint(out, zigzag(*v));
+        }
+        Value::Float(v) => {
+            out.push(TAG_FLOAT);
+            out.extend_from_slice(&v.to_bits().to_le_bytes());
+        }
+        Value::String(s) => {
+            out.push(TAG_STRING);
+            write_varint(out, s.len() as u64);
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Bytes(b) => {
+            out.push(TAG_BYTES);
+            write_varint(out, b.len() as u64);
+            out.extend_from_slice(b);
+        }
+        Value::Timestamp(v) => {
+            out.push(TAG_TIMESTAMP);
+            write_varint(out, zigzag(*v));
+        }
+    }
+}
+
+/// Decodes one value at `*pos` and advances past it.
+pub fn decode_value(buf: &[u8], pos: &mut usize) -> Result<Value, DbError> {
+    let tag = *buf.get(*pos).ok_or(DbError::Decode("truncated value"))?;
+    *pos += 1;
+    match tag {
+        TAG_NULL => Ok(Value::Null),
+        TAG_BOOL => {
+            let b = match buf.get(*pos) {
+                Some(0) => false,
+                Some(1) => true,
+                _ => return Err(DbError::Decode("bad bool")),
+            };
+            *pos += 1;
+            Ok(Value::Bool(b))
+        }
+        TAG_INT => Ok(Value::Int(unzigzag(read_varint(buf, pos)?))),
+        TAG_FLOAT => {
+            let bytes: [u8; 8] = take(buf, pos, 8)?.try_into().unwrap();
+            Ok(Value::Float(f64::from_bits(u64::from_le_bytes(bytes))))
+        }
+        TAG_STRING => {
+            let len = read_varint(buf, pos)? as usize;
+            let s = core::str::from_utf8(take(buf, pos, len)?).map_err(|_| DbError::Decode("bad UTF-8"))?;
+            Ok(Value::String(String::from(s)))
+        }
+        TAG_BYTES => {
+            let len = read_varint(buf, pos)? as usize;
+            Ok(Value::Bytes(take(buf, pos, len)?.to_vec()))
+        }
+        TAG_TIMESTAMP => Ok(Value::Timestamp(unzigzag(read_varint(buf, pos)?))),
+        _ => Err(DbError::Decode("unknown value tag")),
+    }
+}
+
+fn take<'a>(buf: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], DbError> {
+    let bytes = pos.checked_add(len).and_then(|end| buf.get(*pos..end))
+        .ok_or(DbError::Decode("truncated value"))?;
+    *pos += len;
+    Ok(bytes)
+}
+
+/// Encodes a list of values (e.g. a row) as a self-describing record.
+pub fn encode(values: &[Value]) -> Vec<u8> {
+    let len = 1 + varint_len(values.len() as u64) + values.iter().map(value_len).sum::<usize>();
+    let mut out = Vec::with_capacity(len);
+    out.push(CODEC_VERSION);
+    write_varint(&mut out, values.len() as u64);
+    for value in values {
+        encode_value(value, &mut out);
+    }
+    out
+}
+
+/// Decodes a record written by `encode`. Trailing bytes are an error.
+pub fn decode(buf: &[u8]) -> Result<Vec<Value>, DbError> {
+    match buf.first() {
+        Some(&CODEC_VERSION) => {}
+        Some(_) => return Err(DbError::Decode("unsupported record version")),
+        None => return Err(DbError::Decode("empty record")),
+    }
+    let mut pos = 1;
+    let count = read_varint(buf, &mut pos)? as usize;
+    // Every value takes at least a byte, which bounds the allocation
+    if count > buf.len() - pos {
+        return Err(DbError::Decode("bad value count"));
+    }
+    let mut values = Vec::with_capacity(count);
+    for _ in 0..count {
+        values.push(decode_value(buf, &mut pos)?);
+    }
+    if pos != buf.len() {
+        return Err(DbError::Decode("trailing bytes"));
+    }
+    Ok(values)
+}
+
+// Key encoding: byte strings that compare (memcmp) like the values they
+// encode. A rank byte comes first, matching `Value`'s cross-type order:
+//   Null      : -
+//   Bool      : 0 / 1
+//   Int/Float : f64 as sortable bits (big-endian), then i16 correction
+//   Timestamp : i64 big-endian with the sign bit flipped
+//   String    : bytes with 0x00 escaped as 00 ff, terminated by 00 00
+//   Bytes     : as String
+//
+// Ints are first rounded to the nearest f64. Rounding is monotonic, so the
+// f64 part orders Ints and Floats correctly except for ties, which the
+// exact difference (Int minus its rounded value, 0 for Floats) breaks.
+// -0.0 and NaN are canonicalized so equal values get equal keys.
+const KEY_NULL: u8 = 0;
+const KEY_BOOL: u8 = 1;
+const KEY_NUMBER: u8 = 2;
+const KEY_TIMESTAMP: u8 = 3;
+const KEY_STRING: u8 = 4;
+const KEY_BYTES: u8 = 5;
+
+/// Appends the order-preserving key form of `value`.
+pub fn encode_key(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(KEY_NULL),
+        Value::Bool(v) => {
+            out.push(KEY_BOOL);
+            out.push(*v as u8);
+        }
+        Value::Int(v) => {
+            let rounded = *v as f64;
+            // |v - rounded| is at most half an ulp near 2^63, i.e. 1024
+            let diff = *v as i128 - rounded as i128;
+            encode_number(out, rounded, diff as i16);
+        }
+        Value::Float(v) => encode_number(out, *v, 0),
+        Value::Timestamp(v) => {
+            out.push(KEY_TIMESTAMP);
+            out.extend_from_slice(&((*v as u64) ^ (1 << 63)).to_be_bytes());
+        }
+        Value::String(s) => encode_key_bytes(out, KEY_STRING, s.as_bytes()),
+        Value::Bytes(b) => encode_key_bytes(out, KEY_BYTES, b),
+    }
+}
+
+fn encode_number(out: &mut Vec<u8>, v: f64, diff: i16) {
+    let bits = if v.is_nan() {
+        f64::NAN.to_bits() & !(1 << 63)
+    } else if v == 0.0 {
+        0 // folds -0.0 into 0.0
+    } else {
+        v.to_bits()
+    };
+    // Flip all bits of negatives and only the sign bit of positives
+    let sortable = if bits >> 63 == 1 { !bits } else { bits ^ (1 << 63) };
+    out.push(KEY_NUMBER);
+    out.extend_from_slice(&sortable.to_be_bytes());
+    out.extend_from_slice(&((diff as u16) ^ 0x8000).to_be_bytes());
+}
+
+fn encode_key_bytes(out: &mut Vec<u8>, rank: u8, bytes: &[u8]) {
+    out.push(rank);
+    for &b in bytes {
+        out.push(b);
+        if b == 0 {
+            out.push(0xff);
+        }
+    }
+    out.extend_from_slice(&[0, 0]);
+}
+
+/// Decodes one key at `*pos`. The result equals (`==`) the encoded value,
+/// but numbers come back as `Int` whenever they are whole and fit in an
+/// i64, since the key form does not tell `Int(1)` from `Float(1.0)`.
+pub fn decode_key(buf: &[u8], pos: &mut usize) -> Result<Value, DbError> {
+    let rank = *buf.get(*pos).ok_or(DbError::Decode("truncated key"))?;
+    *pos += 1;
+    match rank {
+        KEY_NULL => Ok(Value::Null),
+        KEY_BOOL => {
+            let b = *take(buf, pos, 1)?.first().unwrap();
+            Ok(Value::Bool(b != 0))
+        }
+        KEY_NUMBER => {
+            let sortable = u64::from_be_bytes(take(buf, pos, 8)?.try_into().unwrap());
+            let diff = (u16::from_be_bytes(take(buf, pos, 2)?.try_into().unwrap()) ^ 0x8000) as i16;
+            let bits = if sortable >> 63 == 1 { sortable ^ (1 << 63) } else { !sortable };
+            let v = f64::from_bits(bits);
+            // Whole numbers are Ints if they (plus the correction) fit in an i64
+            let whole = v as i128;
+            if whole as f64 == v {
+                if let Ok(int) = i64::try_from(whole + diff as i128) {
+                    return Ok(Value::Int(int));
+                }
+            }
+            if diff != 0 {
+                return Err(DbError::Decode("bad number key"));
+            }
+            Ok(Value::Float(v))
+        }
+        KEY_TIMESTAMP => {
+            let v = u64::from_be_bytes(take(buf, pos, 8)?.try_into().unwrap());
+            Ok(Value::Timestamp((v ^ (1 << 63)) as i64))
+        }
+        KEY_STRING => {
+            let bytes = decode_key_bytes(buf, pos)?;
+            String::from_utf8(bytes).map(Value::String).map_err(|_| DbError::Decode("bad UTF-8"))
+        }
+        KEY_BYTES => Ok(Value::Bytes(decode_key_bytes(buf, pos)?)),
+        _ => Err(DbError::Decode("unknown key rank")),
+    }
+}
+
+fn decode_key_bytes(buf: &[u8], pos: &mut usize) -> Result<Vec<u8>, DbError> {
+    let mut out = Vec::new();
+    loop {
+        match take(buf, pos, 1)?[0] {
+            0 => match take(buf, pos, 1)?[0] {
+                0 => return Ok(out),
+                0xff => out.push(0),
+                _ => return Err(DbError::Decode("bad key escape")),
+            },
+            b => out.push(b),
+        }
+    }
+}
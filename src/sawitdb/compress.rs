@@ -0,0 +1,397 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use alloc::vec;
+use core::convert::TryInto;
+use crate::drivers::block::{BlockIO, BlockError, BLOCK_SIZE};
+use super::lz;
+use super::pager::{stamp_trailer, PAGE_DATA_SIZE};
+use super::wal::Wal;
+
+// Compressed layout of the data area (blocks below the WAL):
+// 0               : Page 0, stored as is
+// 1 .. 1 + maps   : Page map - one u32 location per page id
+// 1 + maps .. log : Packing blocks, each split into SECTORS sectors
+//
+// A page is stored compressed in a run of sectors inside one packing block
+// (2-byte length, then the LZ data), or as is in a whole block when it
+// does not compress well enough. A location is the block (bits 8..32),
+// the sector count (bits 4..8) and the first sector (bits 0..4); 0 means
+// the page was never written. Page ids are therefore no longer block
+// numbers, and more pages than blocks can fit.
+//
+// Packing and map blocks are written through the log like any page, so a
+// commit moves pages and their map entries together. Which sectors are
+// free is not stored: it is rebuilt from the map when the store opens or
+// rolls back.
+
+const SECTORS: usize = 8;
+const SECTOR_SIZE: usize = PAGE_DATA_SIZE / SECTORS;
+const ENTRIES_PER_BLOCK: usize = PAGE_DATA_SIZE / 4;
+const FIRST_MAP_BLOCK: u32 = 1;
+
+/// How pages are stored on the device, fixed when it is formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Lz,
+}
+
+impl Compression {
+    /// Value stored in the page-0 header.
+    pub fn code(self) -> u32 {
+        match self {
+            Compression::None => 0,
+            Compression::Lz => 1,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        match code {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Lz),
+            _ => None,
+        }
+    }
+}
+
+/// Blocks of page map and number of page ids for a compressed data area
+/// of `data_blocks` blocks (page 0 included).
+fn layout(data_blocks: u32) -> (u32, u32) {
+    let area = data_blocks.saturating_sub(1) as u64;
+    // Enough map entries for every block's worth of sectors
+    let maps = (area * SECTORS as u64).div_ceil((ENTRIES_PER_BLOCK + SECTORS) as u64);
+    let pages = (1 + (area - maps) * SECTORS as u64).min(maps * ENTRIES_PER_BLOCK as u64);
+    (maps as u32, pages as u32)
+}
+
+fn location(block: u32, first: usize, count: usize) -> u32 {
+    (block << 8) | ((count as u32) << 4) | first as u32
+}
+
+// (block, first sector, sector count) of a location
+fn unpack(location: u32) -> (u32, usize, usize) {
+    (location >> 8, (location & 0x0F) as usize, ((location >> 4) & 0x0F) as usize)
+}
+
+fn sector_bits(first: usize, count: usize) -> u8 {
+    (((1u16 << count) - 1) << first) as u8
+}
+
+/// Sectors needed for `len` bytes of compressed page (with its length).
+fn sectors_for(len: usize) -> usize {
+    (len + 2).div_ceil(SECTOR_SIZE)
+}
+
+/// Page storage below the buffer pool: the write-ahead log, optionally
+/// with pages compressed into a variable-length mapping on top of it.
+pub struct PageStore {
+    wal: Wal,
+    map: Option<PageMap>,
+}
+
+struct PageMap {
+    maps: u32,
+    pages: u32,
+    packing: u32, // First packing block
+    end: u32,     // First block of the log
+    entries: Vec<u32>,
+    // Per packing block: sectors in use now, and as of the last commit
+    used: Vec<u8>,
+    committed: Vec<u8>,
+    // Scratch pages, which take whole packing blocks: page id -> block
+    scratch: Vec<(u32, u32)>,
+}
+
+impl PageStore {
+    /// Writes the empty page map of a freshly formatted compressed device
+    /// whose log starts at `wal_start`.
+    pub fn format(disk: &mut dyn BlockIO, wal_start: u32) -> Result<(), BlockError> {
+        let (maps, _) = layout(wal_start);
+        if FIRST_MAP_BLOCK + maps >= wal_start {
+            return Err(BlockError::DiskFull);
+        }
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        for block in FIRST_MAP_BLOCK..FIRST_MAP_BLOCK + maps {
+            buf.fill(0);
+            stamp_trailer(&mut buf, 0, block);
+            disk.write_block(block, &buf)?;
+        }
+        Ok(())
+    }
+
+    pub fn open(wal: Wal, compression: Compression) -> Result<Self, BlockError> {
+        let mut store = PageStore { wal, map: None };
+        if compression == Compression::Lz {
+            let end = store.wal.num_blocks();
+            let (maps, pages) = layout(end);
+            let packing = FIRST_MAP_BLOCK + maps;
+            if packing >= end {
+                return Err(BlockError::Corrupt { page_id: 0 });
+            }
+            let blocks = (end - packing) as usize;
+            let mut map = PageMap {
+                maps,
+                pages,
+                packing,
+                end,
+                entries: Vec::new(),
+                used: vec![0; blocks],
+                committed: vec![0; blocks],
+                scratch: Vec::new(),
+            };
+            map.load(&store.wal)?;
+            store.map = Some(map);
+        }
+        Ok(store)
+    }
+
+    pub fn into_disk(self) -> Box<dyn BlockIO> {
+        self.wal.into_disk()
+    }
+
+    pub fn compression(&self) -> Compression {
+        if self.map.is_some() { Compression::Lz } else { Compression::None }
+    }
+
+    /// (pages stored, packing blocks they occupy) when compressed.
+    pub fn packing_stats(&self) -> Option<(u32, u32)> {
+        let map = self.map.as_ref()?;
+        let pages = map.entries.iter().filter(|&&e| e != 0).count() as u32;
+        let blocks = map.used.iter().filter(|&&bits| bits != 0).count() as u32;
+        Some((pages, blocks))
+    }
+
+    pub fn corruptions(&self) -> u64 {
+        self.wal.corruptions()
+    }
+
+    pub fn usage(&self) -> (u32, u32) {
+        self.wal.usage()
+    }
+
+    /// Log blocks a full commit may need besides one per cached page.
+    pub fn overhead(&self) -> u32 {
+        self.map.as_ref().map_or(0, |map| map.maps)
+    }
+
+    pub fn commit(&mut self) -> Result<(), BlockError> {
+        self.wal.commit()?;
+        if let Some(map) = &mut self.map {
+            map.committed.copy_from_slice(&map.used);
+        }
+        Ok(())
+    }
+
+    pub fn abort(&mut self) {
+        self.wal.abort();
+        if let Some(map) = &mut self.map {
+            // The map blocks are back to their committed contents; if they
+            // cannot even be read, no page is reachable until reopened
+            if map.load(&self.wal).is_err() {
+                map.entries.fill(0);
+                map.used.fill(0xFF);
+            }
+        }
+    }
+
+    pub fn read_unlogged(&self, page_id: u32, buf: &mut [u8]) -> Result<(), BlockError> {
+        match &self.map {
+            Some(map) => {
+                let block = map.scratch_block(page_id).ok_or(BlockError::OutOfBounds)?;
+                self.wal.read_unlogged(block, buf)
+            }
+            None => self.wal.read_unlogged(page_id, buf),
+        }
+    }
+
+    pub fn write_unlogged(&mut self, page_id: u32, buf: &mut [u8]) -> Result<(), BlockError> {
+        match &mut self.map {
+            Some(map) => {
+                let block = match map.scratch_block(page_id) {
+                    Some(block) => block,
+                    None => map.take_scratch(&self.wal, page_id)?,
+                };
+                self.wal.write_unlogged(block, buf)
+            }
+            None => self.wal.write_unlogged(page_id, buf),
+        }
+    }
+
+    /// Gives back the block behind a scratch page once it is freed.
+    pub fn forget_unlogged(&mut self, page_id: u32) {
+        if let Some(map) = &mut self.map {
+            map.scratch.retain(|&(id, _)| id != page_id);
+        }
+    }
+}
+
+impl PageMap {
+    fn index(&self, block: u32) -> usize {
+        (block - self.packing) as usize
+    }
+
+    /// Reads the map and works out which sectors are in use.
+    fn load(&mut self, wal: &Wal) -> Result<(), BlockError> {
+        let mut entries = Vec::with_capacity(self.pages as usize);
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        for block in FIRST_MAP_BLOCK..FIRST_MAP_BLOCK + self.maps {
+            wal.read_block(block, &mut buf)?;
+            let left = self.pages as usize - entries.len();
+            entries.extend(buf[..PAGE_DATA_SIZE].chunks_exact(4).take(left).map(|e| {
+                u32::from_le_bytes(e.try_into().unwrap_or([0; 4]))
+            }));
+        }
+        self.used.fill(0);
+        for (page_id, &entry) in entries.iter().enumerate().skip(1) {
+            if entry == 0 {
+                continue;
+            }
+            let (block, first, count) = unpack(entry);
+            if block < self.packing || block >= self.end || count == 0 || first + count > SECTORS {
+                return Err(BlockError::Corrupt { page_id: page_id as u32 });
+            }
+            let index = self.index(block);
+            self.used[index] |= sector_bits(first, count);
+        }
+        self.entries = entries;
+        self.committed.copy_from_slice(&self.used);
+        Ok(())
+    }
+
+    fn is_scratch(&self, block: u32) -> bool {
+        self.scratch.iter().any(|&(_, b)| b == block)
+    }
+
+    fn scratch_block(&self, page_id: u32) -> Option<u32> {
+        self.scratch.iter().find(|&&(id, _)| id == page_id).map(|&(_, block)| block)
+    }
+
+    /// Takes a block for a scratch page. Scratch writes skip the log, so
+    /// the block must be free in the committed state too, and have no
+    /// frame in the log that a commit would copy over it.
+    fn take_scratch(&mut self, wal: &Wal, page_id: u32) -> Result<u32, BlockError> {
+        let block = (self.packing..self.end).rev()
+            .find(|&b| {
+                let index = self.index(b);
+                self.used[index] == 0 && self.committed[index] == 0 && !self.is_scratch(b) && !wal.is_logged(b)
+            })
+            .ok_or(BlockError::DiskFull)?;
+        self.scratch.push((page_id, block));
+        Ok(block)
+    }
+
+    /// First run of `count` sectors free in `used` in a packing block
+    /// that passes `filter`.
+    fn find(&self, used: &[u8], count: usize, filter: impl Fn(u32) -> bool) -> Option<(u32, usize)> {
+        (self.packing..self.end).filter(|&b| !self.is_scratch(b) && filter(b)).find_map(|block| {
+            let used = used[self.index(block)];
+            (0..=SECTORS - count)
+                .find(|&first| used & sector_bits(first, count) == 0)
+                .map(|first| (block, first))
+        })
+    }
+
+    fn read(&self, wal: &Wal, page_id: u32, buf: &mut [u8]) -> Result<(), BlockError> {
+        let entry = *self.entries.get(page_id as usize).ok_or(BlockError::OutOfBounds)?;
+        if entry == 0 {
+            return Err(BlockError::Corrupt { page_id });
+        }
+        let (block, first, count) = unpack(entry);
+        let mut packed = vec![0u8; BLOCK_SIZE];
+        wal.read_block(block, &mut packed)?;
+        if count == SECTORS {
+            buf[..PAGE_DATA_SIZE].copy_from_slice(&packed[..PAGE_DATA_SIZE]);
+        } else {
+            let start = first * SECTOR_SIZE;
+            let len = u16::from_le_bytes(packed[start..start + 2].try_into().unwrap_or([0; 2])) as usize;
+            let data = packed.get(start + 2..start + 2 + len).ok_or(BlockError::Corrupt { page_id })?;
+            lz::decompress(data, &mut buf[..PAGE_DATA_SIZE]).ok_or(BlockError::Corrupt { page_id })?;
+        }
+        // The pool expects a whole page; the checksum was on the block
+        stamp_trailer(buf, 0, page_id);
+        Ok(())
+    }
+
+    fn write(&mut self, wal: &mut Wal, page_id: u32, buf: &[u8]) -> Result<(), BlockError> {
+        let old = *self.entries.get(page_id as usize).ok_or(BlockError::OutOfBounds)?;
+        let data = &buf[..PAGE_DATA_SIZE];
+        let compressed = lz::compress(data);
+        let count = match sectors_for(compressed.len()) {
+            count if count < SECTORS => count,
+            _ => SECTORS,
+        };
+
+        // Log slots run out long before blocks do, so the page goes to a
+        // block the batch already wrote if it can (its old sectors then
+        // come free without writing their block), else where it was
+        let mut used = self.used.clone();
+        let (old_block, old_first, old_count) = unpack(old);
+        let in_place = (old != 0 && count <= old_count).then_some((old_block, old_first));
+        if old != 0 {
+            let index = self.index(old_block);
+            used[index] &= !sector_bits(old_first, old_count);
+        }
+        let (block, first) = in_place
+            .filter(|&(block, _)| wal.is_logged(block))
+            .or_else(|| self.find(&used, count, |b| wal.is_logged(b)))
+            .or(in_place)
+            .or_else(|| self.find(&used, count, |_| true))
+            .ok_or(BlockError::DiskFull)?;
+
+        let index = self.index(block);
+        let mut packed = vec![0u8; BLOCK_SIZE];
+        // A block with no other page in it may never have been written
+        if used[index] != 0 {
+            wal.read_block(block, &mut packed)?;
+        }
+        if count == SECTORS {
+            packed[..PAGE_DATA_SIZE].copy_from_slice(data);
+        } else {
+            let start = first * SECTOR_SIZE;
+            packed[start..start + 2].copy_from_slice(&(compressed.len() as u16).to_le_bytes());
+            packed[start + 2..start + 2 + compressed.len()].copy_from_slice(&compressed);
+        }
+        wal.write_block(block, &packed)?;
+
+        used[index] |= sector_bits(first, count);
+        self.used = used;
+        self.entries[page_id as usize] = location(block, first, count);
+        self.write_entry(wal, page_id)
+    }
+
+    // Writes the map block holding `page_id`'s entry.
+    fn write_entry(&self, wal: &mut Wal, page_id: u32) -> Result<(), BlockError> {
+        let map_index = page_id as usize / ENTRIES_PER_BLOCK;
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        let entries = self.entries.iter().skip(map_index * ENTRIES_PER_BLOCK).take(ENTRIES_PER_BLOCK);
+        for (i, entry) in entries.enumerate() {
+            buf[i * 4..i * 4 + 4].copy_from_slice(&entry.to_le_bytes());
+        }
+        wal.write_block(FIRST_MAP_BLOCK + map_index as u32, &buf)
+    }
+}
+
+impl BlockIO for PageStore {
+    fn read_block(&self, block_id: u32, buf: &mut [u8]) -> Result<(), BlockError> {
+        match &self.map {
+            Some(map) if block_id != 0 => map.read(&self.wal, block_id, buf),
+            _ => self.wal.read_block(block_id, buf),
+        }
+    }
+
+    fn write_block(&mut self, block_id: u32, buf: &[u8]) -> Result<(), BlockError> {
+        if buf.len() != BLOCK_SIZE {
+            return Err(BlockError::OutOfBounds);
+        }
+        match &mut self.map {
+            Some(map) if block_id != 0 => map.write(&mut self.wal, block_id, buf),
+            _ => self.wal.write_block(block_id, buf),
+        }
+    }
+
+    /// Page ids available: blocks below the log, or as many pages as the
+    /// map can place when compressed.
+    fn num_blocks(&self) -> u32 {
+        self.map.as_ref().map_or(self.wal.num_blocks(), |map| map.pages)
+    }
+}
@@ -0,0 +1,994 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use alloc::string::ToString;
+use core::ops::RangeBounds;
+use crate::drivers::block::BlockIO;
+use super::pager::{Pager, MAX_IN_FLIGHT};
+use super::compress::Compression;
+use super::btree::BTreeIndex;
+use super::catalog::{self, Catalog, Column, IndexInfo, TableInfo};
+use super::heap::{HeapFile, RecordId};
+use super::lock::{LockManager, LockMode, LockTarget, SessionId};
+use super::mvcc::{self, Horizon, Snapshot, Version};
+use super::row::Row;
+use super::types::Value;
+use super::error::DbError;
+
+// B-tree order used for table indexes (0 = BTreeIndex default)
+const TABLE_INDEX_ORDER: usize = 0;
+
+// Replaced or deleted versions left behind before a statement's commit
+// runs `vacuum` on its own
+const AUTO_VACUUM_GARBAGE: usize = 64;
+
+/// Key field of tables created without a primary key. Rows inserted
+/// without it get the next free integer id.
+pub const AUTO_KEY: &str = "_id";
+
+/// Sessions open at once. Each may have a transaction in flight, and the
+/// page-0 header has room for that many.
+pub const MAX_SESSIONS: usize = MAX_IN_FLIGHT;
+
+/// An open SawitDB database: the pager plus the table catalog loaded from
+/// it. Changes become durable on `flush`.
+///
+/// Rows are versioned (see `mvcc`): queries read from a `Snapshot`, so a
+/// result stream left open keeps seeing the rows as they were when it was
+/// opened, whatever is written meanwhile. Updates and deletes work on the
+/// current versions.
+///
+/// Work is done on behalf of the session last `enter`ed (session 0 unless
+/// `open_session` was used). Each session has its own transaction and
+/// locks; a statement that has to wait for another session's lock fails
+/// with `LockWait` and is undone by `autocommit`, ready to be run again.
+/// All sessions write into the pager's one batch, so a commit also makes
+/// the changes of other open transactions durable. Those transactions are
+/// listed as in flight in the page-0 header, and reverted on `open` if
+/// they never got to commit.
+pub struct Database {
+    pager: Pager,
+    catalog: Catalog,
+    sessions: BTreeMap<SessionId, Session>,
+    current: SessionId,
+    next_session: SessionId,
+    locks: LockManager,
+    // Next transaction id. Ids of rolled-back transactions are not handed
+    // out again while the database is open, so an open snapshot never
+    // takes a later transaction for one it includes.
+    next_txn: u64,
+    // Snapshots handed out; `vacuum` keeps what the live ones can see
+    snapshots: Vec<Weak<Snapshot>>,
+    // Versions replaced or deleted since the last vacuum
+    garbage: usize,
+}
+
+#[derive(Default)]
+struct Session {
+    txn: Option<Transaction>,
+    // Transaction stamped on versions written now: the open transaction,
+    // or one started by the first write of a statement and ended by its
+    // commit
+    writer: Option<u64>,
+    // How to take back the writer's heap changes, oldest first
+    undo: Vec<Undo>,
+    // Undo entries made before the current statement
+    mark: usize,
+    // The transaction was rolled back by another session's failure; the
+    // session's next statement reports it
+    aborted: bool,
+    // Copying pages for a backup (see `begin_backup`)
+    backup: bool,
+}
+
+// A heap record as it was before a change, or None if the change
+// inserted it.
+struct Undo {
+    table: String,
+    rid: RecordId,
+    before: Option<Vec<u8>>,
+}
+
+/// An explicit transaction, from BEGIN to COMMIT or ROLLBACK. Its changes
+/// go into the pager's uncommitted batch - dirty cached pages and log
+/// frames - so commit is the log's atomic commit. Rollback abandons the
+/// batch when no other session is writing, and otherwise restores the
+/// records the transaction changed. The batch can hold at most as many
+/// pages as the write-ahead log has slots; beyond that the statement fails
+/// with `LogFull` and the transaction is rolled back. Its statements all
+/// read from the snapshot taken at BEGIN, plus its own changes, and it
+/// holds its locks until it ends. Changing a row that a transaction
+/// committed since BEGIN fails with `WriteConflict`.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub id: u64,
+    snapshot: Arc<Snapshot>,
+}
+
+/// What `vacuum` reclaimed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VacuumStats {
+    /// Row versions removed from the heaps
+    pub versions: usize,
+    /// Deleted rows among them (their last version went)
+    pub rows: usize,
+    /// Index entries that no longer pointed at a matching version
+    pub entries: usize,
+}
+
+impl Database {
+    /// Opens (or formats) a database on `disk` and loads its catalog.
+    /// Transactions left in flight by a crash are reverted.
+    pub fn open(disk: Box<dyn BlockIO>) -> Result<Self, DbError> {
+        Self::with_compression(disk, Compression::None)
+    }
+
+    /// Like `open`, but a blank `disk` is formatted to store its pages
+    /// with `compression`.
+    pub fn with_compression(disk: Box<dyn BlockIO>, compression: Compression) -> Result<Self, DbError> {
+        let mut pager = Pager::with_compression(disk, compression)?;
+        let catalog = Catalog::load(&mut pager)?;
+        let next_txn = pager.next_txn_id()?;
+        let mut db = Database {
+            pager,
+            catalog,
+            sessions: BTreeMap::new(),
+            current: 0,
+            next_session: 1,
+            locks: LockManager::new(),
+            next_txn,
+            snapshots: Vec::new(),
+            garbage: 0,
+        };
+        db.sessions.insert(0, Session::default());
+        db.recover()?;
+        Ok(db)
+    }
+
+    pub fn pager(&mut self) -> &mut Pager {
+        &mut self.pager
+    }
+
+    pub fn into_disk(self) -> Box<dyn BlockIO> {
+        self.pager.into_disk()
+    }
+
+    /// Opens a new session; `enter` it to work on its behalf.
+    pub fn open_session(&mut self) -> Result<SessionId, DbError> {
+        if self.sessions.len() >= MAX_SESSIONS {
+            return Err(DbError::TooManySessions);
+        }
+        while self.sessions.contains_key(&self.next_session) {
+            self.next_session = self.next_session.wrapping_add(1).max(1);
+        }
+        let id = self.next_session;
+        self.sessions.insert(id, Session::default());
+        self.next_session = id.wrapping_add(1).max(1);
+        Ok(id)
+    }
+
+    /// Rolls back the session's transaction, releases its locks and
+    /// forgets it. Session 0 stays open.
+    pub fn close_session(&mut self, session: SessionId) -> Result<(), DbError> {
+        let previous = self.current;
+        self.enter(session);
+        let result = match self.session().txn.take() {
+            Some(_) => self.discard(),
+            None => Ok(()),
+        };
+        self.locks.release_all(session);
+        if session != 0 {
+            self.sessions.remove(&session);
+        }
+        self.current = if previous == session { 0 } else { previous };
+        result
+    }
+
+    /// Sessions open, including session 0.
+    pub fn session_count(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Makes `session` the one later calls work for.
+    pub fn enter(&mut self, session: SessionId) {
+        self.current = session;
+    }
+
+    fn session(&mut self) -> &mut Session {
+        self.sessions.entry(self.current).or_default()
+    }
+
+    /// Takes a lock for the current session. Fails with `LockWait` if
+    /// another session holds a conflicting one; the request stays queued
+    /// and `lock_granted` tells when the statement can be run again.
+    pub fn lock(&mut self, target: LockTarget, mode: LockMode) -> Result<(), DbError> {
+        match self.locks.acquire(self.current, target, mode)? {
+            true => Ok(()),
+            false => Err(DbError::LockWait),
+        }
+    }
+
+    /// True once the lock the session waits for has been granted. Fails
+    /// with `Deadlock` if it never can be; pass that to `autocommit`.
+    pub fn lock_granted(&mut self) -> Result<bool, DbError> {
+        self.locks.retry(self.current)
+    }
+
+    // Locks taken by a statement outside a transaction last until the
+    // statement ends.
+    fn release(&mut self) {
+        if self.session().txn.is_none() {
+            self.locks.release_all(self.current);
+        }
+    }
+
+    // Locks for writing rows of `table` with the given keys; returns the
+    // writer id.
+    fn lock_rows(&mut self, table: &str, keys: &[&Value]) -> Result<u64, DbError> {
+        self.lock(LockTarget::Table(String::from(table)), LockMode::Shared)?;
+        for key in keys {
+            self.lock(LockTarget::Key(String::from(table), (*key).clone()), LockMode::Exclusive)?;
+        }
+        self.writer()
+    }
+
+    // Locks for changing the schema of `table`. No other session writes
+    // meanwhile, so the changes are never mixed with another's in a batch.
+    fn lock_schema(&mut self, table: &str) -> Result<(), DbError> {
+        self.lock(LockTarget::Catalog, LockMode::Exclusive)?;
+        self.lock(LockTarget::Table(String::from(table)), LockMode::Exclusive)?;
+        self.writer()?;
+        Ok(())
+    }
+
+    /// Commits every pending change, including those of open transactions.
+    /// Statements should end with `autocommit` instead.
+    pub fn flush(&mut self) -> Result<(), DbError> {
+        let ending = self.session().txn.is_none();
+        let current = self.current;
+        let in_flight: Vec<u64> = self.sessions.iter()
+            .filter(|(&id, _)| id != current || !ending)
+            .filter_map(|(_, s)| s.writer)
+            .collect();
+        self.pager.set_in_flight(&in_flight)?;
+        self.pager.flush()?;
+        if ending {
+            let session = self.session();
+            session.writer = None;
+            session.undo.clear();
+            session.mark = 0;
+        }
+        Ok(())
+    }
+
+    /// Starts a transaction. Pending changes made outside one are
+    /// committed first, so ROLLBACK only undoes the transaction's own work.
+    pub fn begin(&mut self) -> Result<Transaction, DbError> {
+        if self.session().txn.is_some() {
+            return Err(DbError::TransactionActive);
+        }
+        self.flush()?;
+        let id = self.writer()?;
+        let snapshot = Snapshot { high: id + 1, active: self.active() };
+        let snapshot = self.register(snapshot);
+        let txn = Transaction { id, snapshot };
+        self.session().txn = Some(txn.clone());
+        Ok(txn)
+    }
+
+    /// The current session's open transaction, if any.
+    pub fn transaction(&self) -> Option<&Transaction> {
+        self.sessions.get(&self.current).and_then(|s| s.txn.as_ref())
+    }
+
+    /// The snapshot a query reads from: the open transaction's, or else
+    /// every change committed so far plus the session's own. It stays
+    /// registered, holding back `vacuum`, until the last clone is dropped.
+    pub fn snapshot(&mut self) -> Result<Arc<Snapshot>, DbError> {
+        self.check_aborted()?;
+        if let Some(txn) = &self.session().txn {
+            return Ok(txn.snapshot.clone());
+        }
+        let snapshot = Snapshot { high: self.next_txn, active: self.active() };
+        Ok(self.register(snapshot))
+    }
+
+    fn register(&mut self, snapshot: Snapshot) -> Arc<Snapshot> {
+        let snapshot = Arc::new(snapshot);
+        self.snapshots.retain(|s| s.strong_count() > 0);
+        self.snapshots.push(Arc::downgrade(&snapshot));
+        snapshot
+    }
+
+    // Transactions other sessions are writing
+    fn active(&self) -> Vec<u64> {
+        self.sessions.iter().filter(|(&id, _)| id != self.current).filter_map(|(_, s)| s.writer).collect()
+    }
+
+    // Reports, once, that another session's failure rolled back the
+    // current session's transaction.
+    fn check_aborted(&mut self) -> Result<(), DbError> {
+        let session = self.session();
+        if session.aborted {
+            session.aborted = false;
+            return Err(DbError::TransactionAborted);
+        }
+        Ok(())
+    }
+
+    // The id to stamp on new versions, starting a transaction for the
+    // current statement if none is open. The counter in the page-0 header
+    // is part of the batch, so after a restart numbering resumes from the
+    // last committed transaction.
+    fn writer(&mut self) -> Result<u64, DbError> {
+        self.check_aborted()?;
+        if let Some(id) = self.session().writer {
+            return Ok(id);
+        }
+        self.lock(LockTarget::Catalog, LockMode::Shared)?;
+        let id = self.next_txn.max(self.pager.next_txn_id()?);
+        self.pager.set_next_txn_id(id + 1)?;
+        self.next_txn = id + 1;
+        self.session().writer = Some(id);
+        Ok(id)
+    }
+
+    /// Makes the open transaction's changes durable, all at once. If the
+    /// commit itself fails, nothing of the transaction is kept.
+    pub fn commit(&mut self) -> Result<(), DbError> {
+        self.check_aborted()?;
+        if self.session().txn.take().is_none() {
+            return Err(DbError::NoTransaction);
+        }
+        let result = self.flush();
+        if result.is_err() {
+            self.abort_all()?;
+        }
+        self.release();
+        result
+    }
+
+    /// Undoes every change of the open transaction.
+    pub fn rollback(&mut self) -> Result<(), DbError> {
+        if self.check_aborted().is_err() {
+            // Already rolled back
+            return Ok(());
+        }
+        if self.session().txn.take().is_none() {
+            return Err(DbError::NoTransaction);
+        }
+        let result = self.discard();
+        self.release();
+        result
+    }
+
+    /// Ends one statement given its result. Outside a transaction the
+    /// statement's changes are committed, or undone if it failed. Inside
+    /// one a failure rolls back the whole transaction - its earlier work
+    /// cannot be kept without the failed statement's partial changes.
+    /// Errors raised before a statement could change anything (syntax,
+    /// BEGIN inside a transaction, COMMIT outside one) leave it open, and
+    /// so does `LockWait`: only the waiting statement is undone.
+    pub fn autocommit<T>(&mut self, result: Result<T, DbError>) -> Result<T, DbError> {
+        let result = match result {
+            Ok(value) => {
+                if self.session().txn.is_none() {
+                    if let Err(e) = self.flush() {
+                        self.abort_all()?;
+                        self.release();
+                        return Err(e);
+                    }
+                    self.release();
+                    if self.garbage >= AUTO_VACUUM_GARBAGE && self.idle() {
+                        self.auto_vacuum()?;
+                    }
+                }
+                Ok(value)
+            }
+            Err(DbError::LockWait) => {
+                let session = self.session();
+                let undo = session.undo.split_off(session.mark);
+                if session.txn.is_none() {
+                    // Retried with a new writer, which sees the rows as
+                    // committed by then
+                    session.writer = None;
+                }
+                if let Err(e) = self.undo(undo) {
+                    self.session().txn = None;
+                    self.abort_all()?;
+                    self.release();
+                    return Err(e);
+                }
+                Err(DbError::LockWait)
+            }
+            Err(e @ (DbError::Syntax(_) | DbError::TransactionActive | DbError::NoTransaction | DbError::TransactionAborted)) => {
+                self.release();
+                Err(e)
+            }
+            Err(e) => {
+                self.session().txn = None;
+                self.discard()?;
+                self.release();
+                Err(e)
+            }
+        };
+        let session = self.session();
+        session.mark = session.undo.len();
+        result
+    }
+
+    // Drops the current session's uncommitted changes. With no other
+    // session writing, that is the pager's whole batch, plus whatever
+    // another session's commit already made durable; otherwise the
+    // session's records are restored one by one.
+    fn discard(&mut self) -> Result<(), DbError> {
+        let current = self.current;
+        let others = self.sessions.iter().any(|(&id, s)| id != current && s.writer.is_some());
+        let session = self.session();
+        session.writer = None;
+        session.mark = 0;
+        let undo = core::mem::take(&mut session.undo);
+        if !others {
+            self.pager.rollback();
+            self.catalog = Catalog::load(&mut self.pager)?;
+            return self.recover();
+        }
+        if undo.is_empty() {
+            return Ok(());
+        }
+        if let Err(e) = self.undo(undo).and_then(|_| self.flush()) {
+            self.abort_all()?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // Restores the records in `undo`, newest change first.
+    fn undo(&mut self, undo: Vec<Undo>) -> Result<(), DbError> {
+        for entry in undo.into_iter().rev() {
+            let mut heap = self.heap(&entry.table)?;
+            match &entry.before {
+                Some(record) => heap.update(&mut self.pager, entry.rid, record)?,
+                None => heap.delete(&mut self.pager, entry.rid)?,
+            };
+            self.sync_heap(&entry.table, &heap)?;
+        }
+        Ok(())
+    }
+
+    // The batch itself failed: every session's uncommitted work is lost.
+    // Transactions of other sessions end, and those sessions are told so
+    // by their next statement.
+    fn abort_all(&mut self) -> Result<(), DbError> {
+        self.pager.rollback();
+        for (&id, session) in self.sessions.iter_mut() {
+            session.writer = None;
+            session.undo.clear();
+            session.mark = 0;
+            if session.txn.take().is_some() {
+                session.aborted = id != self.current;
+                self.locks.release_all(id);
+            }
+        }
+        self.catalog = Catalog::load(&mut self.pager)?;
+        self.recover()
+    }
+
+    // Reverts the transactions the page-0 header lists as in flight: their
+    // changes went out with another session's commit, but they never
+    // committed themselves.
+    fn recover(&mut self) -> Result<(), DbError> {
+        let txns = self.pager.in_flight()?;
+        if txns.is_empty() {
+            return Ok(());
+        }
+        let names: Vec<String> = self.tables().iter().map(|t| t.name.clone()).collect();
+        for name in names {
+            let mut heap = self.heap(&name)?;
+            let mut rows = Vec::new();
+            let mut cursor = heap.cursor();
+            while let Some(record) = cursor.next_record(&mut self.pager) {
+                rows.push(record?.0);
+            }
+            for rid in rows {
+                mvcc::revert(&mut self.pager, &mut heap, rid, &txns)?;
+            }
+            self.sync_heap(&name, &heap)?;
+        }
+        self.pager.set_in_flight(&[])?;
+        self.pager.flush()?;
+        Ok(())
+    }
+
+    // No session is writing, in a transaction or copying a backup
+    fn idle(&self) -> bool {
+        self.sessions.values().all(|s| s.writer.is_none() && s.txn.is_none() && !s.backup)
+    }
+
+    /// Starts a backup for the current session and returns how many pages
+    /// it copies. The catalog lock is taken exclusively, so once no session
+    /// is writing none starts until `end_backup` (or the session closes),
+    /// and the pages read meanwhile are all as of the same commit. Readers
+    /// carry on; writers wait with `LockWait`, and so does this call until
+    /// the open writers are done.
+    pub fn begin_backup(&mut self) -> Result<u32, DbError> {
+        self.check_aborted()?;
+        if self.session().txn.is_some() {
+            return Err(DbError::TransactionActive);
+        }
+        self.lock(LockTarget::Catalog, LockMode::Exclusive)?;
+        self.session().backup = true;
+        Ok(self.pager.total_pages()?)
+    }
+
+    /// Page `page_id`, for the backup the current session is copying.
+    pub fn backup_page(&mut self, page_id: u32) -> Result<Vec<u8>, DbError> {
+        if !self.session().backup {
+            return Err(DbError::NoBackup);
+        }
+        Ok(self.pager.read_page(page_id)?)
+    }
+
+    /// Ends the current session's backup and lets writers go on.
+    pub fn end_backup(&mut self) {
+        self.session().backup = false;
+        self.release();
+    }
+
+    // Vacuum on behalf of a statement that has already committed: its
+    // failure is not the statement's, so it only drops its own leftovers.
+    fn auto_vacuum(&mut self) -> Result<(), DbError> {
+        self.garbage = 0;
+        if self.vacuum().is_err() {
+            self.discard()?;
+        }
+        Ok(())
+    }
+
+    /// Reclaims row versions no open snapshot can see: deleted rows and
+    /// replaced versions whose removal every snapshot already sees. While
+    /// no snapshot is open it also drops the index entries updates and
+    /// deletes left behind (with one open, a reader's cursor may hold the
+    /// index pages that would be merged). Work is committed as it goes,
+    /// so this cannot run while any session is in a transaction.
+    pub fn vacuum(&mut self) -> Result<VacuumStats, DbError> {
+        if !self.idle() {
+            return Err(DbError::TransactionActive);
+        }
+        self.flush()?;
+        self.snapshots.retain(|s| s.strong_count() > 0);
+        let open: Vec<Snapshot> = self.snapshots.iter().filter_map(Weak::upgrade).map(|s| (*s).clone()).collect();
+        let prune_indexes = open.is_empty();
+        let horizon = Horizon::new(open);
+
+        let mut stats = VacuumStats::default();
+        let names: Vec<String> = self.tables().iter().map(|t| t.name.clone()).collect();
+        for name in names {
+            let mut heap = self.heap(&name)?;
+            let mut rows = Vec::new();
+            let mut cursor = heap.cursor();
+            while let Some(record) = cursor.next_record(&mut self.pager) {
+                let (rid, data) = record?;
+                if !Version::decode(&data)?.0.old {
+                    rows.push(rid);
+                }
+            }
+            for rid in rows {
+                let versions = mvcc::chain(&mut self.pager, &heap, rid)?;
+                // Versions are newest first, so everything past the first
+                // garbage version is garbage as well
+                let Some(cut) = versions.iter().position(|(_, v, _)| horizon.is_garbage(v)) else { continue };
+                if cut == 0 {
+                    stats.rows += 1;
+                }
+                // Oldest first, each cut off the version above it, so every
+                // commit leaves a whole chain and stays small enough for
+                // the log even when the versions have overflow pages
+                for k in (cut..versions.len()).rev() {
+                    if k > 0 {
+                        let (above, version, _) = &versions[k - 1];
+                        heap.set_head(&mut self.pager, *above, &Version { prev: None, ..*version }.encode(&[]))?;
+                    }
+                    heap.delete(&mut self.pager, versions[k].0)?;
+                    self.flush()?;
+                }
+                stats.versions += versions.len() - cut;
+            }
+            if prune_indexes {
+                stats.entries += self.prune_index(&name, None)?;
+                for column in self.table(&name)?.indexes.iter().map(|i| i.column.clone()).collect::<Vec<_>>() {
+                    stats.entries += self.prune_index(&name, Some(&column))?;
+                }
+            }
+        }
+        self.flush()?;
+        Ok(stats)
+    }
+
+    // Deletes the entries of the primary (`column: None`) or a secondary
+    // index whose row has no version carrying the entry's key.
+    fn prune_index(&mut self, table: &str, column: Option<&str>) -> Result<usize, DbError> {
+        let info = self.table(table)?.clone();
+        let (index, column) = match column {
+            None => (self.index(table)?, info.key_field.clone()),
+            Some(column) => {
+                let root = info.index_on(column).map(|i| i.root_page).ok_or_else(|| DbError::NoSuchColumn(String::from(column)))?;
+                let index = BTreeIndex::open(&mut self.pager, root, TABLE_INDEX_ORDER, false, info.name.clone(), String::from(column))?;
+                (index, String::from(column))
+            }
+        };
+        let heap = self.heap(table)?;
+        let entries = index.iter(&mut self.pager).collect::<Result<Vec<_>, _>>()?;
+        let mut stale = Vec::new();
+        for (key, value) in entries {
+            let mut live = false;
+            for (_, _, row) in mvcc::chain(&mut self.pager, &heap, record_id(&value)?)? {
+                if field(&Row::decode(&row, &info.schema)?, &column) == key {
+                    live = true;
+                    break;
+                }
+            }
+            if !live {
+                stale.push((key, value));
+            }
+        }
+        for (key, value) in &stale {
+            index.delete_entry(&mut self.pager, key, value)?;
+            self.flush()?;
+        }
+        Ok(stale.len())
+    }
+
+    pub fn tables(&self) -> &[TableInfo] {
+        self.catalog.tables()
+    }
+
+    pub fn table(&self, name: &str) -> Result<&TableInfo, DbError> {
+        self.catalog.get(name).ok_or_else(|| DbError::NoSuchTable(String::from(name)))
+    }
+
+    /// Creates a table: a heap for its rows and an empty index on
+    /// `key_field`. With a non-empty schema the key field must be one of
+    /// its columns.
+    pub fn create_table(&mut self, name: &str, key_field: &str, schema: Vec<Column>) -> Result<(), DbError> {
+        catalog::check_name(name)?;
+        catalog::check_name(key_field)?;
+        for (i, col) in schema.iter().enumerate() {
+            catalog::check_name(&col.name)?;
+            if schema[..i].iter().any(|c| c.name == col.name) {
+                return Err(DbError::DuplicateColumn(col.name.clone()));
+            }
+        }
+        self.lock_schema(name)?;
+        if self.catalog.get(name).is_some() {
+            return Err(DbError::TableExists(String::from(name)));
+        }
+        if !schema.is_empty() && !schema.iter().any(|c| c.name == key_field) {
+            return Err(DbError::NoSuchColumn(String::from(key_field)));
+        }
+        let mut info = TableInfo {
+            name: String::from(name),
+            key_field: String::from(key_field),
+            root_page: 0,
+            heap_first: 0,
+            heap_last: 0,
+            schema,
+            indexes: Vec::new(),
+        };
+        catalog::check_entry(&info)?;
+
+        // Not unique: old versions keep their entries until `vacuum`
+        let index = BTreeIndex::create(&mut self.pager, TABLE_INDEX_ORDER, false, String::from(name), String::from(key_field))?;
+        let heap = HeapFile::create(&mut self.pager)?;
+        info.root_page = index.root;
+        info.heap_first = heap.first_page;
+        info.heap_last = heap.last_page();
+        self.catalog.add(info);
+        self.catalog.save(&mut self.pager)?;
+        Ok(())
+    }
+
+    /// Drops a table and frees all of its index and heap pages.
+    pub fn drop_table(&mut self, name: &str) -> Result<(), DbError> {
+        self.lock_schema(name)?;
+        let index = self.index(name)?;
+        index.destroy(&mut self.pager)?;
+        for index in self.secondary_indexes(name)? {
+            index.destroy(&mut self.pager)?;
+        }
+        self.heap(name)?.destroy(&mut self.pager)?;
+        self.catalog.remove(name);
+        self.catalog.save(&mut self.pager)?;
+        Ok(())
+    }
+
+    /// Index handle for a table's primary key. A key is unique among
+    /// current rows, but may also have entries for old versions.
+    pub fn index(&mut self, name: &str) -> Result<BTreeIndex, DbError> {
+        let info = self.table(name)?;
+        let (root, table, key_field) = (info.root_page, info.name.clone(), info.key_field.clone());
+        Ok(BTreeIndex::open(&mut self.pager, root, TABLE_INDEX_ORDER, false, table, key_field)?)
+    }
+
+    /// Adds a secondary index on `column` and fills it from the existing
+    /// rows, old versions included. Rows without the column are indexed
+    /// under NULL.
+    pub fn create_index(&mut self, table: &str, column: &str) -> Result<(), DbError> {
+        catalog::check_name(column)?;
+        self.lock_schema(table)?;
+        let info = self.table(table)?.clone();
+        if info.key_field == column || info.index_on(column).is_some() {
+            return Err(DbError::IndexExists(String::from(column)));
+        }
+        if !info.schema.is_empty() && !info.schema.iter().any(|c| c.name == column) {
+            return Err(DbError::NoSuchColumn(String::from(column)));
+        }
+        let mut grown = info.clone();
+        grown.indexes.push(IndexInfo { column: String::from(column), root_page: 0 });
+        catalog::check_entry(&grown)?;
+
+        let index = BTreeIndex::create(&mut self.pager, TABLE_INDEX_ORDER, false, String::from(table), String::from(column))?;
+        let heap = self.heap(table)?;
+        let mut cursor = heap.cursor();
+        while let Some(record) = cursor.next_record(&mut self.pager) {
+            let (rid, _) = record?;
+            let mut keys: Vec<Value> = Vec::new();
+            for (_, _, row) in mvcc::chain(&mut self.pager, &heap, rid)? {
+                let key = field(&Row::decode(&row, &info.schema)?, column);
+                if !keys.contains(&key) {
+                    index.insert(&mut self.pager, key.clone(), rid.to_value())?;
+                    keys.push(key);
+                }
+            }
+        }
+        if let Some(info) = self.catalog.get_mut(table) {
+            info.indexes.push(IndexInfo { column: String::from(column), root_page: index.root });
+        }
+        self.catalog.save(&mut self.pager)?;
+        Ok(())
+    }
+
+    /// Handles for every secondary index of a table.
+    pub fn secondary_indexes(&mut self, table: &str) -> Result<Vec<BTreeIndex>, DbError> {
+        let info = self.table(table)?.clone();
+        info.indexes.iter().map(|i| {
+            let index = BTreeIndex::open(&mut self.pager, i.root_page, TABLE_INDEX_ORDER, false, info.name.clone(), i.column.clone())?;
+            Ok(index)
+        }).collect()
+    }
+
+    /// Heap handle for a table's rows.
+    pub fn heap(&self, name: &str) -> Result<HeapFile, DbError> {
+        let info = self.table(name)?;
+        Ok(HeapFile::at(info.heap_first, info.heap_last))
+    }
+
+    /// Inserts a row under the value of the table's key field. The row is
+    /// checked against the schema and rejected if the key already exists.
+    /// Tables keyed by `AUTO_KEY` number rows that do not bring their own.
+    pub fn insert(&mut self, table: &str, row: &Row) -> Result<RecordId, DbError> {
+        let info = self.table(table)?.clone();
+        let mut row = row.clone();
+        if info.key_field == AUTO_KEY && row.get(AUTO_KEY).is_none_or(Value::is_null) {
+            let next = match self.index(table)?.iter(&mut self.pager).next_back().transpose()? {
+                Some((Value::Int(last), _)) => last.checked_add(1).ok_or(DbError::MissingKey(String::from(AUTO_KEY)))?,
+                Some(_) => return Err(DbError::MissingKey(String::from(AUTO_KEY))),
+                None => 1,
+            };
+            row.set(AUTO_KEY, Value::Int(next));
+        }
+        let key = row.key(&info.key_field)?.clone();
+        let data = row.encode(&info.schema)?;
+        let id = self.lock_rows(table, &[&key])?;
+        if self.lookup(table, &key)?.is_some() {
+            return Err(DbError::DuplicateKey(key.to_string()));
+        }
+
+        let index = self.index(table)?;
+        let mut heap = self.heap(table)?;
+        let rid = heap.insert(&mut self.pager, &Version::new(id).encode(&data))?;
+        // The heap may have grown even if the key cannot be indexed
+        self.sync_heap(table, &heap)?;
+        if let Err(e) = add_entry(&mut self.pager, &index, key, rid) {
+            heap.delete(&mut self.pager, rid)?;
+            return Err(e);
+        }
+        self.session().undo.push(Undo { table: String::from(table), rid, before: None });
+        for index in self.secondary_indexes(table)? {
+            add_entry(&mut self.pager, &index, field(&row, &index.key_field), rid)?;
+        }
+        Ok(rid)
+    }
+
+    /// Replaces the row stored under `key`; the new row may carry a new
+    /// key. The replaced version stays readable to older snapshots.
+    /// Returns false if there is no row with `key`.
+    pub fn update(&mut self, table: &str, key: &Value, row: &Row) -> Result<bool, DbError> {
+        let info = self.table(table)?.clone();
+        let new_key = row.key(&info.key_field)?.clone();
+        let data = row.encode(&info.schema)?;
+        let id = self.lock_rows(table, &[key, &new_key])?;
+        let (rid, version, old_data) = match self.changeable(&info, key)? {
+            Some(found) => found,
+            None => return Ok(false),
+        };
+        let old = Row::decode(&old_data, &info.schema)?;
+
+        if &new_key != key && self.lookup(table, &new_key)?.is_some() {
+            return Err(DbError::DuplicateKey(new_key.to_string()));
+        }
+        let mut heap = self.heap(table)?;
+        let prev = if version.xmin == id {
+            // Written by this transaction, so no one else can see it
+            version.prev
+        } else {
+            // The old version moves out, taking its overflow pages along
+            let head = Version { xmax: id, old: true, ..version }.encode(&[]);
+            self.garbage += 1;
+            let copy = heap.move_out(&mut self.pager, rid, &head)?.ok_or(DbError::Decode("index points at a missing row"))?;
+            self.session().undo.push(Undo { table: String::from(table), rid: copy, before: None });
+            Some(copy)
+        };
+        heap.update(&mut self.pager, rid, &Version { prev, ..Version::new(id) }.encode(&data))?;
+        self.session().undo.push(Undo { table: String::from(table), rid, before: Some(version.encode(&old_data)) });
+        self.sync_heap(table, &heap)?;
+        if &new_key != key {
+            let index = self.index(table)?;
+            add_entry(&mut self.pager, &index, new_key, rid)?;
+        }
+        for index in self.secondary_indexes(table)? {
+            let (before, after) = (field(&old, &index.key_field), field(row, &index.key_field));
+            if before != after {
+                add_entry(&mut self.pager, &index, after, rid)?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Looks a row up by key, as a query would see it. Like a query, it
+    /// holds a shared lock on the table until the statement ends.
+    pub fn get(&mut self, table: &str, key: &Value) -> Result<Option<Row>, DbError> {
+        self.lock(LockTarget::Table(String::from(table)), LockMode::Shared)?;
+        let snapshot = self.snapshot()?;
+        let info = self.table(table)?.clone();
+        self.visible(&info, key, &snapshot)
+    }
+
+    /// Deletes the row with `key`; older snapshots still see it. Returns
+    /// false if there is none.
+    pub fn delete(&mut self, table: &str, key: &Value) -> Result<bool, DbError> {
+        let info = self.table(table)?.clone();
+        let id = self.lock_rows(table, &[key])?;
+        let (rid, version, data) = match self.changeable(&info, key)? {
+            Some(found) => found,
+            None => return Ok(false),
+        };
+        let mut heap = self.heap(table)?;
+        heap.set_head(&mut self.pager, rid, &Version { xmax: id, ..version }.encode(&[]))?;
+        self.session().undo.push(Undo { table: String::from(table), rid, before: Some(version.encode(&data)) });
+        self.garbage += 1;
+        Ok(true)
+    }
+
+    /// Rows whose key lies in `range`, in key order, as a query would see
+    /// them (see `get`).
+    pub fn range<R: RangeBounds<Value>>(&mut self, table: &str, range: R) -> Result<Vec<Row>, DbError> {
+        self.lock(LockTarget::Table(String::from(table)), LockMode::Shared)?;
+        let snapshot = self.snapshot()?;
+        let info = self.table(table)?.clone();
+        let index = self.index(table)?;
+        let entries = index.range(&mut self.pager, range).collect::<Result<Vec<_>, _>>()?;
+        let mut rows = Vec::new();
+        for (key, value) in entries {
+            if let Some(row) = self.fetch(&info, record_id(&value)?, &snapshot)? {
+                // Entries left behind by updates point at rows with other keys
+                if field(&row, &info.key_field) == key {
+                    rows.push(row);
+                }
+            }
+        }
+        Ok(rows)
+    }
+
+    // The row `snapshot` sees under `key`.
+    fn visible(&mut self, info: &TableInfo, key: &Value, snapshot: &Snapshot) -> Result<Option<Row>, DbError> {
+        let index = self.index(&info.name)?;
+        for value in index.search(&mut self.pager, key)? {
+            if let Some(row) = self.fetch(info, record_id(&value)?, snapshot)? {
+                if field(&row, &info.key_field) == *key {
+                    return Ok(Some(row));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // The current version of the row under `key`, which the caller holds
+    // the key lock for. A transaction may only change rows as its snapshot
+    // sees them: a row another transaction changed or deleted since is a
+    // `WriteConflict`.
+    fn changeable(&mut self, info: &TableInfo, key: &Value) -> Result<Option<(RecordId, Version, Vec<u8>)>, DbError> {
+        let snapshot = self.transaction().map(|txn| txn.snapshot.clone());
+        let Some(rid) = self.lookup(&info.name, key)? else {
+            if let Some(snapshot) = snapshot {
+                if self.visible(info, key, &snapshot)?.is_some() {
+                    return Err(DbError::WriteConflict);
+                }
+            }
+            return Ok(None);
+        };
+        let (version, data) = self.current(info, rid)?.ok_or(DbError::Decode("index points at a missing row"))?;
+        if snapshot.is_some_and(|s| !s.sees(version.xmin)) {
+            return Err(DbError::WriteConflict);
+        }
+        Ok(Some((rid, version, data)))
+    }
+
+    // The row currently stored under `key`.
+    fn lookup(&mut self, table: &str, key: &Value) -> Result<Option<RecordId>, DbError> {
+        let info = self.table(table)?.clone();
+        let index = self.index(table)?;
+        for value in index.search(&mut self.pager, key)? {
+            let rid = record_id(&value)?;
+            if let Some((_, data)) = self.current(&info, rid)? {
+                if field(&Row::decode(&data, &info.schema)?, &info.key_field) == *key {
+                    return Ok(Some(rid));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // The newest version of the row at `rid` and its encoded row, unless
+    // the row was deleted.
+    fn current(&mut self, info: &TableInfo, rid: RecordId) -> Result<Option<(Version, Vec<u8>)>, DbError> {
+        let heap = HeapFile::at(info.heap_first, info.heap_last);
+        let Some(record) = heap.get(&mut self.pager, rid)? else { return Ok(None) };
+        let (version, data) = Version::decode(&record)?;
+        if version.old || !version.is_current() {
+            return Ok(None);
+        }
+        Ok(Some((version, data.to_vec())))
+    }
+
+    /// Reads the version of the row at `rid` that `snapshot` sees.
+    pub fn fetch(&mut self, info: &TableInfo, rid: RecordId, snapshot: &Snapshot) -> Result<Option<Row>, DbError> {
+        let heap = HeapFile::at(info.heap_first, info.heap_last);
+        let Some(record) = heap.get(&mut self.pager, rid)? else { return Ok(None) };
+        match mvcc::resolve(&mut self.pager, &heap, record, snapshot)? {
+            Some(data) => Ok(Some(Row::decode(&data, &info.schema)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Records a new last heap page in the catalog after the heap grew.
+    fn sync_heap(&mut self, table: &str, heap: &HeapFile) -> Result<(), DbError> {
+        if let Some(info) = self.catalog.get_mut(table) {
+            if info.heap_last != heap.last_page() {
+                info.heap_last = heap.last_page();
+                self.catalog.save(&mut self.pager)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A row's value for `column` as stored in a secondary index.
+fn field(row: &Row, column: &str) -> Value {
+    row.get(column).cloned().unwrap_or(Value::Null)
+}
+
+// Indexes `rid` under `key` unless it already is: entries outlive the
+// versions they were made for, and a slot freed by `vacuum` may be reused.
+fn add_entry(pager: &mut Pager, index: &BTreeIndex, key: Value, rid: RecordId) -> Result<(), DbError> {
+    if !index.search(pager, &key)?.contains(&rid.to_value()) {
+        index.insert(pager, key, rid.to_value())?;
+    }
+    Ok(())
+}
+
+pub fn record_id(value: &Value) -> Result<RecordId, DbError> {
+    RecordId::from_value(value).ok_or(DbError::Decode("bad record id in index"))
+}
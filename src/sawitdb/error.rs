@@ -0,0 +1,91 @@
+use alloc::string::String;
+use alloc::fmt;
+use crate::drivers::block::BlockError;
+use super::types::ValueType;
+use super::query::ParseError;
+
+/// Errors above the page layer. Storage failures are wrapped as `Block`.
+#[derive(Debug)]
+pub enum DbError {
+    Block(BlockError),
+    TableExists(String),
+    NoSuchTable(String),
+    NoSuchColumn(String),
+    DuplicateColumn(String),
+    NameTooLong(String),
+    /// The table's columns and indexes do not fit in its catalog entry.
+    SchemaTooLarge(String),
+    RecordTooLarge(usize),
+    DuplicateKey(String),
+    Decode(&'static str),
+    MissingKey(String),
+    TypeMismatch { column: String, expected: ValueType, found: Option<ValueType> },
+    Syntax(ParseError),
+    IndexExists(String),
+    Eval(String),
+    TransactionActive,
+    NoTransaction,
+    /// Another session holds a conflicting lock. The statement was undone
+    /// and can be retried once the lock is granted.
+    LockWait,
+    /// Waiting for a lock would never end; the transaction was rolled back.
+    Deadlock,
+    /// A row the transaction changes was changed by a transaction that
+    /// committed after it began.
+    WriteConflict,
+    /// Another session's failure forced this transaction to roll back.
+    TransactionAborted,
+    TooManySessions,
+    NoBackup,
+    /// Sessions other than the caller's still use the database.
+    InUse,
+    /// The user called the operation off before it finished.
+    Cancelled,
+}
+
+impl From<BlockError> for DbError {
+    fn from(e: BlockError) -> Self {
+        DbError::Block(e)
+    }
+}
+
+impl From<ParseError> for DbError {
+    fn from(e: ParseError) -> Self {
+        DbError::Syntax(e)
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Block(e) => write!(f, "storage error: {:?}", e),
+            DbError::TableExists(name) => write!(f, "table '{}' already exists", name),
+            DbError::NoSuchTable(name) => write!(f, "no such table '{}'", name),
+            DbError::NoSuchColumn(name) => write!(f, "no such column '{}'", name),
+            DbError::DuplicateColumn(name) => write!(f, "column '{}' appears twice", name),
+            DbError::NameTooLong(name) => write!(f, "name too long: '{}'", name),
+            DbError::SchemaTooLarge(name) => write!(f, "table '{}' has too many columns or indexes", name),
+            DbError::RecordTooLarge(len) => write!(f, "record of {} bytes does not fit in a page", len),
+            DbError::DuplicateKey(key) => write!(f, "duplicate key {}", key),
+            DbError::Decode(what) => write!(f, "malformed data: {}", what),
+            DbError::MissingKey(field) => write!(f, "row has no value for key field '{}'", field),
+            DbError::TypeMismatch { column, expected, found } => match found {
+                Some(found) => write!(f, "column '{}' expects {}, got {}", column, expected, found),
+                None => write!(f, "column '{}' expects {}, got NULL", column, expected),
+            },
+            DbError::Syntax(e) => write!(f, "syntax error: {}", e),
+            DbError::IndexExists(column) => write!(f, "column '{}' is already indexed", column),
+            DbError::Eval(msg) => write!(f, "cannot evaluate: {}", msg),
+            DbError::TransactionActive => write!(f, "a transaction is already in progress"),
+            DbError::NoTransaction => write!(f, "no transaction in progress"),
+            DbError::LockWait => write!(f, "waiting for a lock held by another session"),
+            DbError::Deadlock => write!(f, "deadlock with another session; this transaction was rolled back"),
+            DbError::WriteConflict => write!(f, "row was changed by a concurrent transaction"),
+            DbError::TransactionAborted => write!(f, "transaction was rolled back after a storage failure in another session"),
+            DbError::TooManySessions => write!(f, "too many open sessions"),
+            DbError::NoBackup => write!(f, "no backup in progress"),
+            DbError::InUse => write!(f, "database is in use by other sessions"),
+            DbError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
@@ -0,0 +1,623 @@
+use alloc::vec::Vec;
+use super::pager::{Pager, PAGE_DATA_SIZE};
+use super::types::Value;
+use super::error::DbError;
+use crate::drivers::block::BlockError;
+use core::convert::TryInto;
+
+// Heap page layout (as stamped by Pager::alloc_page):
+// 0..4 : Next heap page (0 = last)
+// 4..6 : Slot count (u16)
+// 6..8 : Free offset - records grow upward from HEAP_HEADER_SIZE
+// Slot directory grows downward from PAGE_DATA_SIZE, 4 bytes per slot:
+// record offset (u16, 0 = empty slot), record length + flags (u16).
+const HEAP_HEADER_SIZE: usize = 8;
+const SLOT_SIZE: usize = 4;
+
+// Slot flags, stored in the top bits of the length field
+const FLAG_FORWARD: u16 = 0x8000;  // Record moved: payload is the new RecordId
+const FLAG_MOVED: u16 = 0x4000;    // Target of a forward, skipped by scans
+const FLAG_OVERFLOW: u16 = 0x2000; // Payload is an overflow stub
+const LEN_MASK: u16 = 0x0FFF;
+
+const FORWARD_LEN: usize = 6;
+
+// Records longer than this live in a chain of overflow pages; the slot
+// keeps a stub: first overflow page (u32), record length (u32). A quarter
+// page keeps several rows per heap page however long their values get.
+const OVERFLOW_THRESHOLD: usize = PAGE_DATA_SIZE / 4;
+const STUB_LEN: usize = 8;
+
+// Overflow page layout:
+// 0..4 : Next overflow page (0 = last)
+// 4..6 : Bytes of the record on this page (u16)
+// 6..  : Those bytes
+const OVERFLOW_HEADER_SIZE: usize = 6;
+const OVERFLOW_CAPACITY: usize = PAGE_DATA_SIZE - OVERFLOW_HEADER_SIZE;
+
+/// Bytes a record occupies on the page. Every record reserves room for a
+/// forward pointer so an update can always leave one behind.
+fn reserved(len: usize) -> usize {
+    len.max(FORWARD_LEN)
+}
+
+/// Location of a record: heap page and slot number. Record ids stay valid
+/// until the record is deleted, even when an update moves the bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RecordId {
+    pub page: u32,
+    pub slot: u16,
+}
+
+impl RecordId {
+    /// Packs the id into a `Value` so it can be stored in a B-tree.
+    pub fn to_value(self) -> Value {
+        Value::Int(((self.page as i64) << 16) | self.slot as i64)
+    }
+
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Int(v) if *v >= 0 => Some(RecordId { page: (*v >> 16) as u32, slot: (*v & 0xFFFF) as u16 }),
+            _ => None,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; FORWARD_LEN] {
+        let mut out = [0u8; FORWARD_LEN];
+        out[0..4].copy_from_slice(&self.page.to_le_bytes());
+        out[4..6].copy_from_slice(&self.slot.to_le_bytes());
+        out
+    }
+
+    fn from_bytes(buf: &[u8]) -> Option<Self> {
+        Some(RecordId {
+            page: u32::from_le_bytes(buf.get(0..4)?.try_into().ok()?),
+            slot: u16::from_le_bytes(buf.get(4..6)?.try_into().ok()?),
+        })
+    }
+}
+
+/// Decoded view of one heap page.
+struct HeapPage {
+    buf: Vec<u8>,
+}
+
+impl HeapPage {
+    fn next(&self) -> u32 {
+        u32::from_le_bytes(self.buf[0..4].try_into().unwrap())
+    }
+
+    fn set_next(&mut self, next: u32) {
+        self.buf[0..4].copy_from_slice(&next.to_le_bytes());
+    }
+
+    fn slot_count(&self) -> usize {
+        u16::from_le_bytes(self.buf[4..6].try_into().unwrap()) as usize
+    }
+
+    fn free_offset(&self) -> usize {
+        u16::from_le_bytes(self.buf[6..8].try_into().unwrap()) as usize
+    }
+
+    fn set_header(&mut self, slots: usize, free_offset: usize) {
+        self.buf[4..6].copy_from_slice(&(slots as u16).to_le_bytes());
+        self.buf[6..8].copy_from_slice(&(free_offset as u16).to_le_bytes());
+    }
+
+    fn slot_pos(slot: usize) -> usize {
+        PAGE_DATA_SIZE - (slot + 1) * SLOT_SIZE
+    }
+
+    /// (offset, length, flags) of a slot; offset 0 means empty.
+    fn slot(&self, slot: usize) -> (usize, usize, u16) {
+        let pos = Self::slot_pos(slot);
+        let offset = u16::from_le_bytes(self.buf[pos..pos + 2].try_into().unwrap());
+        let len = u16::from_le_bytes(self.buf[pos + 2..pos + 4].try_into().unwrap());
+        (offset as usize, (len & LEN_MASK) as usize, len & !LEN_MASK)
+    }
+
+    fn set_slot(&mut self, slot: usize, offset: usize, len: usize, flags: u16) {
+        let pos = Self::slot_pos(slot);
+        self.buf[pos..pos + 2].copy_from_slice(&(offset as u16).to_le_bytes());
+        self.buf[pos + 2..pos + 4].copy_from_slice(&(len as u16 | flags).to_le_bytes());
+    }
+
+    fn record(&self, slot: usize) -> &[u8] {
+        let (offset, len, _) = self.slot(slot);
+        &self.buf[offset..offset + len]
+    }
+
+    fn is_live(&self, slot: usize) -> bool {
+        slot < self.slot_count() && self.slot(slot).0 != 0
+    }
+
+    fn free_slot(&self) -> Option<usize> {
+        (0..self.slot_count()).find(|&s| self.slot(s).0 == 0)
+    }
+
+    /// Contiguous free bytes between the records and the slot directory.
+    fn contiguous_free(&self) -> usize {
+        Self::slot_pos(self.slot_count()) + SLOT_SIZE - self.free_offset()
+    }
+
+    /// Free bytes once deleted records are compacted away.
+    fn total_free(&self) -> usize {
+        let live: usize = (0..self.slot_count()).filter(|&s| self.slot(s).0 != 0).map(|s| reserved(self.slot(s).1)).sum();
+        PAGE_DATA_SIZE - HEAP_HEADER_SIZE - self.slot_count() * SLOT_SIZE - live
+    }
+
+    /// Bytes needed to place a record of `len` bytes, including a new slot
+    /// if no empty one can be reused.
+    fn space_needed(&self, len: usize) -> usize {
+        reserved(len) + if self.free_slot().is_some() { 0 } else { SLOT_SIZE }
+    }
+
+    /// Moves all live records to the start of the page, keeping slot numbers.
+    fn compact(&mut self) {
+        let mut records: Vec<(usize, Vec<u8>, u16)> = Vec::new();
+        for s in 0..self.slot_count() {
+            let (offset, len, flags) = self.slot(s);
+            if offset != 0 {
+                records.push((s, self.buf[offset..offset + len].to_vec(), flags));
+            }
+        }
+        let mut pos = HEAP_HEADER_SIZE;
+        for (s, data, flags) in records {
+            self.buf[pos..pos + data.len()].copy_from_slice(&data);
+            self.set_slot(s, pos, data.len(), flags);
+            pos += reserved(data.len());
+        }
+        let slots = self.slot_count();
+        self.set_header(slots, pos);
+    }
+
+    /// Places `data` into the page. Caller checks `space_needed` first.
+    fn place(&mut self, data: &[u8], flags: u16) -> usize {
+        let needed = self.space_needed(data.len());
+        if self.contiguous_free() < needed {
+            self.compact();
+        }
+        let slot = match self.free_slot() {
+            Some(s) => s,
+            None => self.slot_count(),
+        };
+        let offset = self.free_offset();
+        self.buf[offset..offset + data.len()].copy_from_slice(data);
+        let slots = self.slot_count().max(slot + 1);
+        self.set_header(slots, offset + reserved(data.len()));
+        self.set_slot(slot, offset, data.len(), flags);
+        slot
+    }
+
+    /// Rewrites a slot's record in place if the page has room for it.
+    fn replace(&mut self, slot: usize, data: &[u8], flags: u16) -> bool {
+        let (offset, len, _) = self.slot(slot);
+        if data.len() <= reserved(len) {
+            self.buf[offset..offset + data.len()].copy_from_slice(data);
+            self.set_slot(slot, offset, data.len(), flags);
+            return true;
+        }
+        // Free the old bytes first so compaction can reclaim them
+        if self.total_free() + reserved(len) < reserved(data.len()) {
+            return false;
+        }
+        self.set_slot(slot, 0, 0, 0);
+        if self.contiguous_free() < reserved(data.len()) {
+            self.compact();
+        }
+        let offset = self.free_offset();
+        self.buf[offset..offset + data.len()].copy_from_slice(data);
+        let slots = self.slot_count();
+        self.set_header(slots, offset + reserved(data.len()));
+        self.set_slot(slot, offset, data.len(), flags);
+        true
+    }
+
+    fn clear_slot(&mut self, slot: usize) {
+        self.set_slot(slot, 0, 0, 0);
+    }
+}
+
+/// Heap file of variable-length records stored in a chain of slotted
+/// pages. Like `BTreeIndex` it is only a handle; all state is in pages.
+/// Records of any length are accepted: long ones are split across a chain
+/// of overflow pages, read back whole and freed with the record.
+pub struct HeapFile {
+    pub first_page: u32,
+    last_page: u32,
+}
+
+impl HeapFile {
+    pub fn create(pager: &mut Pager) -> Result<Self, BlockError> {
+        let page = pager.alloc_page()?;
+        Ok(HeapFile { first_page: page, last_page: page })
+    }
+
+    /// Handle for a heap whose chain ends are already known (e.g. kept in
+    /// the table catalog), skipping the walk `open` does.
+    pub fn at(first_page: u32, last_page: u32) -> Self {
+        HeapFile { first_page, last_page }
+    }
+
+    pub fn last_page(&self) -> u32 {
+        self.last_page
+    }
+
+    pub fn open(pager: &mut Pager, first_page: u32) -> Result<Self, BlockError> {
+        let mut last_page = first_page;
+        loop {
+            let next = load(pager, last_page)?.next();
+            if next == 0 {
+                break;
+            }
+            last_page = next;
+        }
+        Ok(HeapFile { first_page, last_page })
+    }
+
+    /// Frees every page of the heap, overflow pages included.
+    pub fn destroy(self, pager: &mut Pager) -> Result<(), BlockError> {
+        let mut page_id = self.first_page;
+        while page_id != 0 {
+            let page = load(pager, page_id)?;
+            for slot in 0..page.slot_count() {
+                free_overflow(pager, &page, slot)?;
+            }
+            pager.free_page(page_id)?;
+            page_id = page.next();
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, pager: &mut Pager, data: &[u8]) -> Result<RecordId, DbError> {
+        let (stored, flags) = store(pager, data)?;
+        self.insert_with_flags(pager, &stored, flags)
+    }
+
+    /// Appends to the last page of the chain, starting a new page when it
+    /// is full. `data` is a record as stored in a slot.
+    fn insert_with_flags(&mut self, pager: &mut Pager, data: &[u8], flags: u16) -> Result<RecordId, DbError> {
+        let mut page = load(pager, self.last_page)?;
+        if page.total_free() < page.space_needed(data.len()) {
+            let new_id = pager.alloc_page()?;
+            page.set_next(new_id);
+            pager.write_page(self.last_page, &page.buf)?;
+            self.last_page = new_id;
+            page = load(pager, new_id)?;
+        }
+        let slot = page.place(data, flags);
+        pager.write_page(self.last_page, &page.buf)?;
+        Ok(RecordId { page: self.last_page, slot: slot as u16 })
+    }
+
+    /// Reads a record. The slot of a moved copy is not a record id of its
+    /// own and reads as missing.
+    pub fn get(&self, pager: &mut Pager, rid: RecordId) -> Result<Option<Vec<u8>>, DbError> {
+        let page = load(pager, rid.page)?;
+        if !page.is_live(rid.slot as usize) {
+            return Ok(None);
+        }
+        let (_, _, flags) = page.slot(rid.slot as usize);
+        if flags & FLAG_MOVED != 0 {
+            return Ok(None);
+        }
+        if flags & FLAG_FORWARD != 0 {
+            let target = forward_target(&page, rid)?;
+            let target_page = load(pager, target.page)?;
+            return Ok(Some(read(pager, &target_page, target.slot as usize)?));
+        }
+        Ok(Some(read(pager, &page, rid.slot as usize)?))
+    }
+
+    /// Replaces a record. If it no longer fits in its page the bytes move
+    /// elsewhere and the original slot keeps a forward pointer, so `rid`
+    /// stays valid. Returns false if `rid` does not exist.
+    pub fn update(&mut self, pager: &mut Pager, rid: RecordId, data: &[u8]) -> Result<bool, DbError> {
+        let page = load(pager, rid.page)?;
+        let slot = rid.slot as usize;
+        if !page.is_live(slot) {
+            return Ok(false);
+        }
+        let (_, _, flags) = page.slot(slot);
+        let old = if flags & FLAG_FORWARD != 0 {
+            let target = forward_target(&page, rid)?;
+            stub(&load(pager, target.page)?, target.slot as usize)
+        } else {
+            stub(&page, slot)
+        };
+        let (data, overflow) = store(pager, data)?;
+        self.rewrite(pager, rid, page, &data, overflow)?;
+        // Only now that no slot points at the old chain can it go
+        free_chain(pager, old)?;
+        Ok(true)
+    }
+
+    // Puts a stored record (see `store`) in the place of the live record
+    // at `rid`, whose home page is `page`.
+    fn rewrite(&mut self, pager: &mut Pager, rid: RecordId, mut page: HeapPage, data: &[u8], overflow: u16) -> Result<(), DbError> {
+        let slot = rid.slot as usize;
+        let (_, _, flags) = page.slot(slot);
+        if flags & FLAG_FORWARD != 0 {
+            // Try the current location first; otherwise drop it and move again
+            let target = forward_target(&page, rid)?;
+            let mut target_page = load(pager, target.page)?;
+            if target_page.replace(target.slot as usize, data, FLAG_MOVED | overflow) {
+                pager.write_page(target.page, &target_page.buf)?;
+                return Ok(());
+            }
+            target_page.clear_slot(target.slot as usize);
+            pager.write_page(target.page, &target_page.buf)?;
+            // Home page may be the same page we just touched
+            page = load(pager, rid.page)?;
+            if page.replace(slot, data, overflow) {
+                pager.write_page(rid.page, &page.buf)?;
+                return Ok(());
+            }
+        } else if page.replace(slot, data, overflow) {
+            pager.write_page(rid.page, &page.buf)?;
+            return Ok(());
+        }
+
+        let target = self.insert_with_flags(pager, data, FLAG_MOVED | overflow)?;
+        let mut page = load(pager, rid.page)?;
+        // Always fits in place: every record reserves FORWARD_LEN bytes
+        if !page.replace(slot, &target.to_bytes(), FLAG_FORWARD) {
+            return Err(DbError::Block(BlockError::Corrupt { page_id: rid.page }));
+        }
+        pager.write_page(rid.page, &page.buf)?;
+        Ok(())
+    }
+
+    /// Moves the record at `rid` to a new record id, with its first
+    /// `head.len()` bytes replaced by `head`, and leaves `rid` holding an
+    /// empty record for `update` to fill. A long record's overflow pages
+    /// change hands instead of being copied. Returns None if `rid` does
+    /// not exist.
+    pub fn move_out(&mut self, pager: &mut Pager, rid: RecordId, head: &[u8]) -> Result<Option<RecordId>, DbError> {
+        let page = load(pager, rid.page)?;
+        if !page.is_live(rid.slot as usize) {
+            return Ok(None);
+        }
+        let (_, _, flags) = page.slot(rid.slot as usize);
+        let at = if flags & FLAG_FORWARD != 0 { forward_target(&page, rid)? } else { rid };
+        let at_page = load(pager, at.page)?;
+        let slot = at.slot as usize;
+        let moved = match stub(&at_page, slot) {
+            Some((first, _)) => {
+                write_head(pager, first, head)?;
+                self.insert_with_flags(pager, at_page.record(slot), FLAG_OVERFLOW)?
+            }
+            None => {
+                let mut record = read(pager, &at_page, slot)?;
+                if record.len() < head.len() {
+                    return Err(BlockError::Corrupt { page_id: at.page }.into());
+                }
+                record[..head.len()].copy_from_slice(head);
+                self.insert(pager, &record)?
+            }
+        };
+        // The insert may have gone to the same page
+        let mut at_page = load(pager, at.page)?;
+        let (_, _, at_flags) = at_page.slot(slot);
+        at_page.replace(slot, &[], at_flags & FLAG_MOVED);
+        pager.write_page(at.page, &at_page.buf)?;
+        Ok(Some(moved))
+    }
+
+    /// Overwrites the first `head.len()` bytes of a record in place. Of a
+    /// long record only the first overflow page is rewritten. Returns
+    /// false if `rid` does not exist.
+    pub fn set_head(&mut self, pager: &mut Pager, rid: RecordId, head: &[u8]) -> Result<bool, DbError> {
+        let page = load(pager, rid.page)?;
+        if !page.is_live(rid.slot as usize) {
+            return Ok(false);
+        }
+        let (_, _, flags) = page.slot(rid.slot as usize);
+        let at = if flags & FLAG_FORWARD != 0 { forward_target(&page, rid)? } else { rid };
+        let mut at_page = load(pager, at.page)?;
+        let slot = at.slot as usize;
+        if let Some((first, _)) = stub(&at_page, slot) {
+            write_head(pager, first, head)?;
+            return Ok(true);
+        }
+        let (offset, len, _) = at_page.slot(slot);
+        if len < head.len() {
+            return Err(BlockError::Corrupt { page_id: at.page }.into());
+        }
+        at_page.buf[offset..offset + head.len()].copy_from_slice(head);
+        pager.write_page(at.page, &at_page.buf)?;
+        Ok(true)
+    }
+
+    /// Deletes a record (and its moved copy, if any). Returns false if
+    /// `rid` does not exist.
+    pub fn delete(&mut self, pager: &mut Pager, rid: RecordId) -> Result<bool, DbError> {
+        let mut page = load(pager, rid.page)?;
+        let slot = rid.slot as usize;
+        if !page.is_live(slot) {
+            return Ok(false);
+        }
+        let (_, _, flags) = page.slot(slot);
+        if flags & FLAG_FORWARD != 0 {
+            let target = forward_target(&page, rid)?;
+            let mut target_page = load(pager, target.page)?;
+            free_overflow(pager, &target_page, target.slot as usize)?;
+            target_page.clear_slot(target.slot as usize);
+            pager.write_page(target.page, &target_page.buf)?;
+            page = load(pager, rid.page)?;
+        } else {
+            free_overflow(pager, &page, slot)?;
+        }
+        page.clear_slot(slot);
+        pager.write_page(rid.page, &page.buf)?;
+        Ok(true)
+    }
+
+    /// Iterates over all records in page order, yielding each once under
+    /// its stable id.
+    pub fn scan<'a>(&self, pager: &'a mut Pager) -> HeapScan<'a> {
+        HeapScan { pager, cursor: self.cursor() }
+    }
+
+    /// Same walk as `scan`, but the pager is passed to every step.
+    pub fn cursor(&self) -> HeapCursor {
+        HeapCursor { page_id: self.first_page, page: None, slot: 0 }
+    }
+}
+
+fn load(pager: &mut Pager, page_id: u32) -> Result<HeapPage, BlockError> {
+    Ok(HeapPage { buf: pager.read_page(page_id)? })
+}
+
+fn forward_target(page: &HeapPage, rid: RecordId) -> Result<RecordId, BlockError> {
+    RecordId::from_bytes(page.record(rid.slot as usize)).ok_or(BlockError::Corrupt { page_id: rid.page })
+}
+
+// The bytes to put in a slot for `data`, and the flag marking them as an
+// overflow stub. Long records are written out to new overflow pages.
+fn store(pager: &mut Pager, data: &[u8]) -> Result<(Vec<u8>, u16), DbError> {
+    if data.len() <= OVERFLOW_THRESHOLD {
+        return Ok((data.to_vec(), 0));
+    }
+    let len = u32::try_from(data.len()).map_err(|_| DbError::RecordTooLarge(data.len()))?;
+    // Written back to front so each page can point at the one after it
+    let mut next = 0u32;
+    for chunk in data.chunks(OVERFLOW_CAPACITY).rev() {
+        let page_id = pager.alloc_page()?;
+        let mut buf = pager.read_page(page_id)?;
+        buf[0..4].copy_from_slice(&next.to_le_bytes());
+        buf[4..6].copy_from_slice(&(chunk.len() as u16).to_le_bytes());
+        buf[OVERFLOW_HEADER_SIZE..OVERFLOW_HEADER_SIZE + chunk.len()].copy_from_slice(chunk);
+        pager.write_page(page_id, &buf)?;
+        next = page_id;
+    }
+    let mut stub = Vec::with_capacity(STUB_LEN);
+    stub.extend_from_slice(&next.to_le_bytes());
+    stub.extend_from_slice(&len.to_le_bytes());
+    Ok((stub, FLAG_OVERFLOW))
+}
+
+// (first page, length) of the overflow chain a slot's stub points at.
+fn stub(page: &HeapPage, slot: usize) -> Option<(u32, usize)> {
+    let (offset, len, flags) = page.slot(slot);
+    if offset == 0 || flags & FLAG_OVERFLOW == 0 || len != STUB_LEN {
+        return None;
+    }
+    let record = page.record(slot);
+    let first = u32::from_le_bytes(record[0..4].try_into().ok()?);
+    let total = u32::from_le_bytes(record[4..8].try_into().ok()?);
+    Some((first, total as usize))
+}
+
+// A slot's record, reassembled from its overflow pages if it has any.
+fn read(pager: &mut Pager, page: &HeapPage, slot: usize) -> Result<Vec<u8>, BlockError> {
+    let Some((mut page_id, len)) = stub(page, slot) else { return Ok(page.record(slot).to_vec()) };
+    let mut data = Vec::with_capacity(len);
+    while page_id != 0 && data.len() < len {
+        let buf = pager.read_page(page_id)?;
+        let used = u16::from_le_bytes(buf[4..6].try_into().unwrap_or([0; 2])) as usize;
+        if used > OVERFLOW_CAPACITY {
+            return Err(BlockError::Corrupt { page_id });
+        }
+        data.extend_from_slice(&buf[OVERFLOW_HEADER_SIZE..OVERFLOW_HEADER_SIZE + used]);
+        page_id = u32::from_le_bytes(buf[0..4].try_into().unwrap_or([0; 4]));
+    }
+    if data.len() != len {
+        return Err(BlockError::Corrupt { page_id });
+    }
+    Ok(data)
+}
+
+// Overwrites the start of the record held by the overflow chain at `first`.
+fn write_head(pager: &mut Pager, first: u32, head: &[u8]) -> Result<(), BlockError> {
+    let mut buf = pager.read_page(first)?;
+    let used = u16::from_le_bytes(buf[4..6].try_into().unwrap_or([0; 2])) as usize;
+    if used < head.len() || used > OVERFLOW_CAPACITY {
+        return Err(BlockError::Corrupt { page_id: first });
+    }
+    buf[OVERFLOW_HEADER_SIZE..OVERFLOW_HEADER_SIZE + head.len()].copy_from_slice(head);
+    pager.write_page(first, &buf)
+}
+
+// Returns the overflow pages of a slot's record to the free list.
+fn free_overflow(pager: &mut Pager, page: &HeapPage, slot: usize) -> Result<(), BlockError> {
+    free_chain(pager, stub(page, slot))
+}
+
+// Returns the overflow chain of a stub (see `stub`) to the free list.
+fn free_chain(pager: &mut Pager, chain: Option<(u32, usize)>) -> Result<(), BlockError> {
+    let Some((mut page_id, _)) = chain else { return Ok(()) };
+    while page_id != 0 {
+        let next = u32::from_le_bytes(pager.read_page(page_id)?[0..4].try_into().unwrap_or([0; 4]));
+        pager.free_page(page_id)?;
+        page_id = next;
+    }
+    Ok(())
+}
+
+/// Position of a heap scan that does not hold on to the pager, so callers
+/// can use the pager between records. The current page is cached: do not
+/// modify the heap while a cursor is open.
+pub struct HeapCursor {
+    page_id: u32,
+    page: Option<HeapPage>,
+    slot: usize,
+}
+
+impl HeapCursor {
+    pub fn next_record(&mut self, pager: &mut Pager) -> Option<Result<(RecordId, Vec<u8>), DbError>> {
+        loop {
+            if self.page.is_none() {
+                if self.page_id == 0 {
+                    return None;
+                }
+                match load(pager, self.page_id) {
+                    Ok(page) => self.page = Some(page),
+                    Err(e) => {
+                        self.page_id = 0;
+                        return Some(Err(e.into()));
+                    }
+                }
+                self.slot = 0;
+            }
+            let page = self.page.as_ref().unwrap();
+            if self.slot >= page.slot_count() {
+                self.page_id = page.next();
+                self.page = None;
+                continue;
+            }
+
+            let slot = self.slot;
+            self.slot += 1;
+            let (offset, _, flags) = page.slot(slot);
+            if offset == 0 || flags & FLAG_MOVED != 0 {
+                continue;
+            }
+            let rid = RecordId { page: self.page_id, slot: slot as u16 };
+            if flags & FLAG_FORWARD != 0 {
+                let target = match forward_target(page, rid) {
+                    Ok(t) => t,
+                    Err(e) => return Some(Err(e.into())),
+                };
+                return Some(load(pager, target.page)
+                    .and_then(|p| read(pager, &p, target.slot as usize))
+                    .map(|record| (rid, record))
+                    .map_err(DbError::from));
+            }
+            return Some(read(pager, page, slot).map(|record| (rid, record)).map_err(DbError::from));
+        }
+    }
+}
+
+pub struct HeapScan<'a> {
+    pager: &'a mut Pager,
+    cursor: HeapCursor,
+}
+
+impl<'a> Iterator for HeapScan<'a> {
+    type Item = Result<(RecordId, Vec<u8>), DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cursor.next_record(self.pager)
+    }
+}
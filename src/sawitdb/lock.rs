@@ -0,0 +1,157 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use super::error::DbError;
+use super::types::Value;
+
+// Locks are taken by sessions and held until their transaction ends (or,
+// outside a transaction, until the statement ends). Reads need no row
+// locks - they see a snapshot - so key locks are only taken exclusively,
+// by writers. A request that conflicts is recorded as waiting; the
+// waits-for graph is checked on every attempt, and the session whose wait
+// would close a cycle gets `Deadlock`.
+//
+// A session holding many key locks on one table takes the whole table
+// exclusively instead, which keeps large statements from filling the heap
+// with lock entries.
+
+/// Identifies a client of the database (the shell, a network connection).
+/// A session runs one transaction at a time and owns its locks.
+pub type SessionId = u32;
+
+/// Key locks one session takes on a table before locking the table itself.
+pub const KEY_LOCKS_PER_TABLE: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LockTarget {
+    /// The table catalog: shared by every transaction that writes,
+    /// exclusive for one that changes the schema.
+    Catalog,
+    Table(String),
+    /// A primary key value of a table
+    Key(String, Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+impl LockMode {
+    fn conflicts(self, other: LockMode) -> bool {
+        self == LockMode::Exclusive || other == LockMode::Exclusive
+    }
+}
+
+#[derive(Default)]
+pub struct LockManager {
+    held: BTreeMap<LockTarget, Vec<(SessionId, LockMode)>>,
+    waiting: BTreeMap<SessionId, (LockTarget, LockMode)>,
+    // Key locks held per session and table
+    keys: BTreeMap<(SessionId, String), usize>,
+}
+
+impl LockManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grants `mode` on `target` to `session` (upgrading a shared lock it
+    /// holds) unless another session holds a conflicting lock. In that case
+    /// the session is recorded as waiting for it and false is returned, or
+    /// `Deadlock` if the wait could never end.
+    pub fn acquire(&mut self, session: SessionId, target: LockTarget, mode: LockMode) -> Result<bool, DbError> {
+        if let LockTarget::Key(table, _) = &target {
+            // Covered by an exclusive table lock
+            if self.holds(session, &LockTarget::Table(table.clone()), LockMode::Exclusive) {
+                return Ok(true);
+            }
+            let count = self.keys.get(&(session, table.clone())).copied().unwrap_or(0);
+            if count >= KEY_LOCKS_PER_TABLE && !self.holds(session, &target, mode) {
+                return self.acquire(session, LockTarget::Table(table.clone()), LockMode::Exclusive);
+            }
+        }
+        self.waiting.insert(session, (target, mode));
+        self.retry(session)
+    }
+
+    /// Tries again to grant the lock `session` waits for. True once it is
+    /// granted (or if the session was not waiting).
+    pub fn retry(&mut self, session: SessionId) -> Result<bool, DbError> {
+        let Some((target, mode)) = self.waiting.get(&session).cloned() else { return Ok(true) };
+        if !self.blockers(session, &target, mode).is_empty() {
+            if self.in_cycle(session) {
+                self.waiting.remove(&session);
+                return Err(DbError::Deadlock);
+            }
+            return Ok(false);
+        }
+        self.waiting.remove(&session);
+        let holders = self.held.entry(target.clone()).or_default();
+        match holders.iter_mut().find(|(s, _)| *s == session) {
+            Some((_, held)) => {
+                if mode == LockMode::Exclusive {
+                    *held = LockMode::Exclusive;
+                }
+            }
+            None => {
+                holders.push((session, mode));
+                if let LockTarget::Key(table, _) = target {
+                    *self.keys.entry((session, table)).or_default() += 1;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Drops every lock of `session` and any wait it had.
+    pub fn release_all(&mut self, session: SessionId) {
+        self.waiting.remove(&session);
+        self.keys.retain(|(s, _), _| *s != session);
+        self.held.retain(|_, holders| {
+            holders.retain(|(s, _)| *s != session);
+            !holders.is_empty()
+        });
+    }
+
+    fn holds(&self, session: SessionId, target: &LockTarget, mode: LockMode) -> bool {
+        self.held.get(target).is_some_and(|holders| {
+            holders.iter().any(|&(s, m)| s == session && (m == LockMode::Exclusive || mode == LockMode::Shared))
+        })
+    }
+
+    // Other sessions holding `target` in a mode that conflicts with `mode`.
+    fn blockers(&self, session: SessionId, target: &LockTarget, mode: LockMode) -> Vec<SessionId> {
+        let mut blockers: Vec<SessionId> = self.held.get(target).map(|holders| {
+            holders.iter().filter(|&&(s, m)| s != session && mode.conflicts(m)).map(|&(s, _)| s).collect()
+        }).unwrap_or_default();
+        // An exclusive table lock covers every key of the table
+        if let LockTarget::Key(table, _) = target {
+            let table = LockTarget::Table(table.clone());
+            if let Some(holders) = self.held.get(&table) {
+                blockers.extend(holders.iter().filter(|&&(s, m)| s != session && m == LockMode::Exclusive).map(|&(s, _)| s));
+            }
+        }
+        blockers
+    }
+
+    // True if following waits-for edges from `session` leads back to it.
+    fn in_cycle(&self, session: SessionId) -> bool {
+        let mut visited = Vec::new();
+        let mut pending = Vec::from([session]);
+        while let Some(s) = pending.pop() {
+            let Some((target, mode)) = self.waiting.get(&s) else { continue };
+            for blocker in self.blockers(s, target, *mode) {
+                if blocker == session {
+                    return true;
+                }
+                if !visited.contains(&blocker) {
+                    visited.push(blocker);
+                    pending.push(blocker);
+                }
+            }
+        }
+        false
+    }
+}
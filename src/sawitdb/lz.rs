@@ -0,0 +1,124 @@
+use alloc::vec::Vec;
+use alloc::vec;
+use core::convert::TryInto;
+
+// Byte-oriented LZ77 codec in the LZ4 block format: a sequence is a token
+// (literal count in the high nibble, match length - 4 in the low one), any
+// extra length bytes for the literals, the literals, a 16-bit offset back
+// into the output and any extra match length bytes. The last sequence has
+// literals only. Lengths of 15 and more continue in bytes of 255 up to the
+// first smaller byte. Matches are found through a small hash table of
+// recent positions; the encoder favours speed over ratio, which suits
+// pages that are mostly zeros or repeated keys.
+
+const MIN_MATCH: usize = 4;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 12;
+
+fn hash(bytes: u32) -> usize {
+    (bytes.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+fn read_u32(input: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(input[at..at + 4].try_into().unwrap_or([0; 4]))
+}
+
+fn push_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn push_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let match_len = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push(((literals.len().min(15) as u8) << 4) | match_len.min(15) as u8);
+    if literals.len() >= 15 {
+        push_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = matched {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_len >= 15 {
+            push_length(out, match_len - 15);
+        }
+    }
+}
+
+/// Compresses `input`. Incompressible data comes out slightly longer.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2);
+    // Position + 1 of the last occurrence of each hashed 4-byte run
+    let mut table = vec![0u32; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut pos = 0;
+    while pos + MIN_MATCH <= input.len() {
+        let bytes = read_u32(input, pos);
+        let slot = &mut table[hash(bytes)];
+        let candidate = *slot as usize;
+        *slot = pos as u32 + 1;
+        if candidate != 0 {
+            let start = candidate - 1;
+            if pos - start <= MAX_OFFSET && read_u32(input, start) == bytes {
+                let mut len = MIN_MATCH;
+                while pos + len < input.len() && input[start + len] == input[pos + len] {
+                    len += 1;
+                }
+                push_sequence(&mut out, &input[anchor..pos], Some((pos - start, len)));
+                pos += len;
+                anchor = pos;
+                continue;
+            }
+        }
+        pos += 1;
+    }
+    push_sequence(&mut out, &input[anchor..], None);
+    out
+}
+
+fn read_length(input: &[u8], pos: &mut usize, nibble: usize) -> Option<usize> {
+    let mut len = nibble;
+    if nibble == 15 {
+        loop {
+            let byte = *input.get(*pos)?;
+            *pos += 1;
+            len = len.checked_add(byte as usize)?;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Some(len)
+}
+
+/// Decompresses `input` into `out`, which must come out exactly full.
+/// None if the input is malformed.
+pub fn decompress(input: &[u8], out: &mut [u8]) -> Option<()> {
+    let mut pos = 0;
+    let mut written = 0;
+    loop {
+        let token = *input.get(pos)? as usize;
+        pos += 1;
+        let literals = read_length(input, &mut pos, token >> 4)?;
+        let source = input.get(pos..pos.checked_add(literals)?)?;
+        out.get_mut(written..written + literals)?.copy_from_slice(source);
+        pos += literals;
+        written += literals;
+        if pos == input.len() {
+            return (written == out.len()).then_some(());
+        }
+
+        let offset = u16::from_le_bytes(input.get(pos..pos + 2)?.try_into().ok()?) as usize;
+        pos += 2;
+        let len = read_length(input, &mut pos, token & 0x0F)?.checked_add(MIN_MATCH)?;
+        if offset == 0 || offset > written || len > out.len() - written {
+            return None;
+        }
+        // Byte by byte: the match may overlap what it is copying
+        for i in written..written + len {
+            out[i] = out[i - offset];
+        }
+        written += len;
+    }
+}
@@ -1,3 +1,19 @@
 pub mod types;
+pub mod codec;
 pub mod pager;
+pub mod buffer;
+pub mod wal;
+pub mod lz;
+pub mod compress;
+pub mod checksum;
 pub mod btree;
+pub mod error;
+pub mod catalog;
+pub mod database;
+pub mod heap;
+pub mod mvcc;
+pub mod lock;
+pub mod backup;
+pub mod row;
+pub mod sort;
+pub mod query;
@@ -0,0 +1,203 @@
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use crate::drivers::block::BlockError;
+use super::error::DbError;
+use super::heap::{HeapFile, RecordId};
+use super::pager::Pager;
+
+// Multi-version concurrency control. Rows are never changed in a way a
+// reader could notice: every heap record starts with a version header
+// naming the transaction that wrote it (xmin) and the one that deleted or
+// replaced it (xmax). An update copies the current version out to a new
+// record, marks the copy replaced and writes the new version at the row's
+// stable RecordId, linked to the copy. A delete only sets xmax. Readers
+// walk that chain from the newest version until they reach one their
+// snapshot can see, so an open scan keeps seeing the rows as they were
+// when it started while writers carry on.
+//
+// Several sessions may write at once (see `lock`), all into the pager's
+// one uncommitted batch, so a commit can carry versions of transactions
+// that are still open. Snapshots list those as active and do not see
+// them; the page-0 header lists them as in flight so that they can be
+// reverted if they never commit.
+//
+// Index entries are only ever added: an entry (key, rid) may outlive the
+// versions it was made for, so readers check that the version they found
+// still carries the key. `Database::vacuum` drops stale entries and
+// versions no snapshot can see any more.
+
+// Version header in front of every row in the heap:
+// 0      : flags
+// 1..9   : xmin - transaction that wrote the version
+// 9..17  : xmax - transaction that deleted or replaced it (0 = none)
+// 17..23 : previous version (page u32, slot u16; page 0 = none)
+pub const VERSION_HEADER_SIZE: usize = 23;
+
+// Superseded copy, only reachable through the chain of its row
+const FLAG_OLD: u8 = 0x01;
+
+/// Header of one row version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version {
+    pub xmin: u64,
+    pub xmax: u64,
+    pub prev: Option<RecordId>,
+    pub old: bool,
+}
+
+impl Version {
+    /// Header of a version written by `xmin` with no history.
+    pub fn new(xmin: u64) -> Self {
+        Version { xmin, xmax: 0, prev: None, old: false }
+    }
+
+    /// True until a transaction deletes or replaces the version.
+    pub fn is_current(&self) -> bool {
+        self.xmax == 0
+    }
+
+    /// The header followed by the encoded row.
+    pub fn encode(&self, row: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(VERSION_HEADER_SIZE + row.len());
+        out.push(if self.old { FLAG_OLD } else { 0 });
+        out.extend_from_slice(&self.xmin.to_le_bytes());
+        out.extend_from_slice(&self.xmax.to_le_bytes());
+        let prev = self.prev.unwrap_or(RecordId { page: 0, slot: 0 });
+        out.extend_from_slice(&prev.page.to_le_bytes());
+        out.extend_from_slice(&prev.slot.to_le_bytes());
+        out.extend_from_slice(row);
+        out
+    }
+
+    /// Splits a heap record into its header and encoded row.
+    pub fn decode(record: &[u8]) -> Result<(Version, &[u8]), DbError> {
+        if record.len() < VERSION_HEADER_SIZE {
+            return Err(DbError::Decode("truncated version header"));
+        }
+        let u64_at = |at: usize| u64::from_le_bytes(record[at..at + 8].try_into().unwrap_or([0; 8]));
+        let page = u32::from_le_bytes(record[17..21].try_into().unwrap_or([0; 4]));
+        let slot = u16::from_le_bytes(record[21..23].try_into().unwrap_or([0; 2]));
+        let version = Version {
+            xmin: u64_at(1),
+            xmax: u64_at(9),
+            prev: (page != 0).then_some(RecordId { page, slot }),
+            old: record[0] & FLAG_OLD != 0,
+        };
+        Ok((version, &record[VERSION_HEADER_SIZE..]))
+    }
+}
+
+/// What a reader sees: the work of every transaction below `high`, that
+/// is, those started before the snapshot was taken, except the ones still
+/// open in other sessions at the time (`active`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub high: u64,
+    pub active: Vec<u64>,
+}
+
+impl Snapshot {
+    /// True if the snapshot includes `txn`'s work. Transaction 0 is
+    /// never seen.
+    pub fn sees(&self, txn: u64) -> bool {
+        txn != 0 && txn < self.high && !self.active.contains(&txn)
+    }
+
+    pub fn can_see(&self, version: &Version) -> bool {
+        self.sees(version.xmin) && !self.sees(version.xmax)
+    }
+}
+
+/// Versions no snapshot can see any more. A version replaced or deleted by
+/// `xmax` is garbage once every open snapshot sees `xmax`.
+pub struct Horizon {
+    snapshots: Vec<Snapshot>,
+}
+
+impl Horizon {
+    /// Called with no writer open, so every xmax on disk is committed.
+    pub fn new(snapshots: Vec<Snapshot>) -> Self {
+        Horizon { snapshots }
+    }
+
+    pub fn is_garbage(&self, version: &Version) -> bool {
+        !version.is_current() && self.snapshots.iter().all(|s| s.sees(version.xmax))
+    }
+}
+
+/// The version of a row that `snapshot` sees, as encoded row bytes, given
+/// the record stored at the row's id. Old copies yield None: they are only
+/// read through the row that owns them.
+pub fn resolve(pager: &mut Pager, heap: &HeapFile, record: Vec<u8>, snapshot: &Snapshot) -> Result<Option<Vec<u8>>, DbError> {
+    let (first, _) = Version::decode(&record)?;
+    if first.old {
+        return Ok(None);
+    }
+    let mut record = record;
+    loop {
+        let (version, row) = Version::decode(&record)?;
+        if snapshot.can_see(&version) {
+            return Ok(Some(row.to_vec()));
+        }
+        if snapshot.sees(version.xmin) {
+            // Deleted or replaced before the snapshot; older versions were too
+            return Ok(None);
+        }
+        match version.prev {
+            Some(prev) => {
+                record = heap.get(pager, prev)?.ok_or(BlockError::Corrupt { page_id: prev.page })?;
+            }
+            None => return Ok(None),
+        }
+    }
+}
+
+/// Every version of the row at `rid`, newest first, with its record id.
+/// Empty if `rid` holds no row (nothing, or an old copy).
+pub fn chain(pager: &mut Pager, heap: &HeapFile, rid: RecordId) -> Result<Vec<(RecordId, Version, Vec<u8>)>, DbError> {
+    let mut versions = Vec::new();
+    let mut next = Some(rid);
+    while let Some(at) = next {
+        let record = match heap.get(pager, at)? {
+            Some(record) => record,
+            None if at == rid => break,
+            None => return Err(BlockError::Corrupt { page_id: at.page }.into()),
+        };
+        let (version, row) = Version::decode(&record)?;
+        if at == rid && version.old {
+            break;
+        }
+        next = version.prev;
+        versions.push((at, version, row.to_vec()));
+    }
+    Ok(versions)
+}
+
+/// Undoes what the transactions in `txns` did to the row at `rid`: a
+/// version one of them wrote gives way to the version it replaced (or the
+/// row goes, if they inserted it), and a deletion is cleared. Used for
+/// transactions that reached the disk but will never commit, once their
+/// in-memory undo is gone.
+pub fn revert(pager: &mut Pager, heap: &mut HeapFile, rid: RecordId, txns: &[u64]) -> Result<(), DbError> {
+    let Some(record) = heap.get(pager, rid)? else { return Ok(()) };
+    let (version, _) = Version::decode(&record)?;
+    if version.old {
+        return Ok(());
+    }
+    if txns.contains(&version.xmin) {
+        match version.prev {
+            Some(prev) => {
+                let copy = heap.get(pager, prev)?.ok_or(BlockError::Corrupt { page_id: prev.page })?;
+                let (older, row) = Version::decode(&copy)?;
+                heap.update(pager, rid, &Version { xmax: 0, old: false, ..older }.encode(row))?;
+                heap.delete(pager, prev)?;
+            }
+            None => {
+                heap.delete(pager, rid)?;
+            }
+        }
+    } else if txns.contains(&version.xmax) {
+        heap.set_head(pager, rid, &Version { xmax: 0, ..version }.encode(&[]))?;
+    }
+    Ok(())
+}
@@ -2,49 +2,346 @@ use alloc::vec::Vec;
 use alloc::vec;
 use alloc::boxed::Box;
 use crate::drivers::block::{BlockIO, BlockError, BLOCK_SIZE};
+use super::buffer::{BufferPool, PageHandle, PoolStats, DEFAULT_POOL_FRAMES};
+use super::wal::{self, Wal};
+use super::compress::{Compression, PageStore};
+use super::checksum::crc32;
 use core::convert::TryInto;
 
 pub const MAGIC: &[u8; 4] = b"WOWO";
 
+// Every page ends in a trailer owned by the pager:
+// LSN of the last logged image (u64), the page's own id (u32), then a
+// CRC-32 of everything before the CRC (u32).
+pub const PAGE_TRAILER_SIZE: usize = 16;
+/// Bytes of each page available to page users (B-tree nodes, free list...).
+pub const PAGE_DATA_SIZE: usize = BLOCK_SIZE - PAGE_TRAILER_SIZE;
+
+// Page 0 header offsets
+const HDR_TOTAL_PAGES: usize = 4;
+const HDR_NUM_TABLES: usize = 8;
+const HDR_CATALOG_PAGE: usize = 12;
+const HDR_FREE_HEAD: usize = 16;
+const HDR_FREE_COUNT: usize = 20;
+const HDR_WAL_START: usize = 24;
+const HDR_WAL_BLOCKS: usize = 28;
+const HDR_NEXT_TXN: usize = 32; // u64
+// Transactions in flight at the last commit: count (u32), then the ids (u64)
+const HDR_IN_FLIGHT: usize = 40;
+/// Most transactions the page-0 header can list as in flight.
+pub const MAX_IN_FLIGHT: usize = 16;
+// How pages are stored (see `Compression`), fixed at format time
+const HDR_COMPRESSION: usize = HDR_IN_FLIGHT + 4 + MAX_IN_FLIGHT * 8;
+
+// A page on the free list: 0..4 next free page (0 = end), 4..8 marker
+const FREE_MARKER: &[u8; 4] = b"FREE";
+
+/// Page-level storage manager. Pages are cached in a `BufferPool` whose
+/// write-backs go through the write-ahead log, so home pages only change
+/// when `flush` commits. Opening a disk replays any committed log frames
+/// left behind by a crash. On a compressed disk the `PageStore` packs the
+/// written pages into fewer blocks.
 pub struct Pager {
-    disk: Box<dyn BlockIO>,
+    store: PageStore,
+    pool: BufferPool,
+    temp: TempSpace,
+}
+
+// Scratch pages for query processing (sort runs) are taken from the top of
+// the page ids, below the WAL, and handed out downwards. They are written
+// directly to the device: they bypass the buffer pool and the log, are
+// never recorded in the page-0 header and are simply forgotten on a crash.
+// `alloc_page` stops growing the file at the lowest scratch page in use.
+struct TempSpace {
+    floor: u32,
+    free: Vec<u32>,
+    in_use: u32,
 }
 
 impl Pager {
     pub fn new(disk: Box<dyn BlockIO>) -> Result<Self, BlockError> {
-        let mut p = Pager { disk };
-        
-        // Check if file is initialized or empty
-        // RamDisk is zeroed by default.
-        // We check if block 0 is empty? Or we just init if magic missing.
+        Self::with_pool_size(disk, DEFAULT_POOL_FRAMES)
+    }
+
+    /// Opens `disk` with a buffer pool of up to `frames` cached pages.
+    pub fn with_pool_size(disk: Box<dyn BlockIO>, frames: usize) -> Result<Self, BlockError> {
+        Self::with_options(disk, frames, Compression::None)
+    }
+
+    /// Opens `disk`, formatting it to store pages with `compression` if it
+    /// is blank. A formatted disk keeps the setting it was created with.
+    pub fn with_compression(disk: Box<dyn BlockIO>, compression: Compression) -> Result<Self, BlockError> {
+        Self::with_options(disk, DEFAULT_POOL_FRAMES, compression)
+    }
+
+    fn with_options(mut disk: Box<dyn BlockIO>, frames: usize, compression: Compression) -> Result<Self, BlockError> {
+        // Only an all-zero block 0 is a blank disk to format; anything else
+        // without the magic is not ours, and is left alone.
+        // The WAL location never changes, so the home copy of page 0 is
+        // always good enough to find the log even before recovery.
         let mut buf = [0u8; BLOCK_SIZE];
-        if p.disk.read_block(0, &mut buf).is_ok() {
-            if &buf[0..4] != MAGIC {
-                // Initialize new file
-                p.init_new_file()?;
+        disk.read_block(0, &mut buf)?;
+        if &buf[0..4] != MAGIC {
+            if buf.iter().any(|&b| b != 0) {
+                return Err(BlockError::Corrupt { page_id: 0 });
             }
-        } else {
-             // Maybe empty or error, try init
-             p.init_new_file()?;
+            Self::init_new_file(disk.as_mut(), &mut buf, compression)?;
         }
-        
-        Ok(p)
+        let header_u32 = |offset: usize| u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap_or([0; 4]));
+        let compression = Compression::from_code(header_u32(HDR_COMPRESSION)).ok_or(BlockError::Corrupt { page_id: 0 })?;
+        let wal = Wal::open(disk, header_u32(HDR_WAL_START), header_u32(HDR_WAL_BLOCKS))?;
+        let store = PageStore::open(wal, compression)?;
+
+        // A full pool must still fit in the log at commit (plus the commit
+        // record and, when compressed, the page map)
+        let (_, wal_capacity) = store.usage();
+        let frames = frames.min((wal_capacity - 1).saturating_sub(store.overhead()).max(1) as usize);
+        let temp = TempSpace { floor: store.num_blocks(), free: Vec::new(), in_use: 0 };
+        Ok(Pager { store, pool: BufferPool::new(frames), temp })
     }
 
-    fn init_new_file(&mut self) -> Result<(), BlockError> {
-        let mut buf = [0u8; BLOCK_SIZE];
+    fn init_new_file(disk: &mut dyn BlockIO, buf: &mut [u8; BLOCK_SIZE], compression: Compression) -> Result<(), BlockError> {
+        let num_blocks = disk.num_blocks();
+        let wal_blocks = wal::default_wal_blocks(num_blocks);
+        let wal_start = num_blocks - wal_blocks;
+        if wal_start < 2 {
+            return Err(BlockError::DiskFull);
+        }
+
+        buf.fill(0);
         buf[0..4].copy_from_slice(MAGIC);
         // Total pages = 1 (Page 0 itself)
         buf[4..8].copy_from_slice(&1u32.to_le_bytes()); 
         // Num Tables = 0
         buf[8..12].copy_from_slice(&0u32.to_le_bytes());
+        // Catalog Page = 0 (no tables yet)
+        buf[12..16].copy_from_slice(&0u32.to_le_bytes());
+        // Free List Head = 0 (empty), Free Page Count = 0
+        buf[16..20].copy_from_slice(&0u32.to_le_bytes());
+        buf[20..24].copy_from_slice(&0u32.to_le_bytes());
+        // WAL Region = last wal_blocks blocks of the device
+        buf[24..28].copy_from_slice(&wal_start.to_le_bytes());
+        buf[28..32].copy_from_slice(&wal_blocks.to_le_bytes());
+        // Next transaction id = 1 (0 means "no transaction" in row versions)
+        buf[32..40].copy_from_slice(&1u64.to_le_bytes());
+        // No transactions in flight (count at 40..44 stays 0)
+        buf[HDR_COMPRESSION..HDR_COMPRESSION + 4].copy_from_slice(&compression.code().to_le_bytes());
+
+        stamp_trailer(buf, 0, 0);
+        Wal::format(disk, wal_start)?;
+        if compression == Compression::Lz {
+            PageStore::format(disk, wal_start)?;
+        }
+        disk.write_block(0, buf)
+    }
+
+    /// Hands the underlying device back without flushing, e.g. to reopen the
+    /// same disk with a fresh `Pager` or to simulate a crash. Call `flush`
+    /// first to keep cached writes.
+    pub fn into_disk(self) -> Box<dyn BlockIO> {
+        self.store.into_disk()
+    }
+
+    /// Makes every change since the last flush durable as one atomic batch:
+    /// dirty pages are logged, a commit record is written, and the logged
+    /// pages are then copied to their home blocks.
+    pub fn flush(&mut self) -> Result<(), BlockError> {
+        self.pool.flush(&mut self.store)?;
+        self.store.commit()
+    }
+
+    /// Throws away every change since the last flush: cached pages are
+    /// dropped (dirty or not, since clean ones may have been read back from
+    /// the log) and the log's uncommitted frames are abandoned. Scratch
+    /// pages are not affected. No page may be pinned.
+    pub fn rollback(&mut self) {
+        self.pool.discard();
+        self.store.abort();
+    }
+
+    /// Pages that failed checksum verification since the pager was opened.
+    pub fn corruptions(&self) -> u64 {
+        self.store.corruptions()
+    }
 
-        self.disk.write_block(0, &buf)
+    /// Log slots used by the uncommitted batch / total log slots.
+    pub fn wal_usage(&self) -> (u32, u32) {
+        self.store.usage()
     }
 
-    pub fn read_page(&self, page_id: u32) -> Result<Vec<u8>, BlockError> {
-        let mut buf = vec![0u8; BLOCK_SIZE];
-        self.disk.read_block(page_id, &mut buf)?;
+    pub fn compression(&self) -> Compression {
+        self.store.compression()
+    }
+
+    /// Pages stored / blocks they take up, for a compressed disk.
+    pub fn packing_stats(&self) -> Option<(u32, u32)> {
+        self.store.packing_stats()
+    }
+
+    /// Most pages the file can grow to on this device.
+    pub fn page_capacity(&self) -> u32 {
+        self.store.num_blocks()
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        self.pool.stats()
+    }
+
+    pub fn pool_capacity(&self) -> usize {
+        self.pool.capacity()
+    }
+
+    /// Pins a page in the buffer pool, loading it from disk if needed. The
+    /// page stays cached until the handle is passed to `unpin_page`.
+    pub fn pin_page(&mut self, page_id: u32) -> Result<PageHandle, BlockError> {
+        self.pool.pin(&mut self.store, page_id, true)
+    }
+
+    pub fn unpin_page(&mut self, handle: PageHandle) {
+        self.pool.unpin(handle)
+    }
+
+    pub fn page(&self, handle: &PageHandle) -> &[u8] {
+        self.pool.page(handle)
+    }
+
+    /// Mutable view of a pinned page; the page is marked dirty.
+    pub fn page_mut(&mut self, handle: &PageHandle) -> &mut [u8] {
+        self.pool.page_mut(handle)
+    }
+
+    // Reads a u32 field of the page-0 header
+    fn header_u32(&mut self, offset: usize) -> Result<u32, BlockError> {
+        let header = self.pin_page(0)?;
+        let bytes: [u8; 4] = self.page(&header)[offset..offset + 4].try_into().unwrap_or([0; 4]);
+        self.unpin_page(header);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn set_header_u32(&mut self, offset: usize, value: u32) -> Result<(), BlockError> {
+        let header = self.pin_page(0)?;
+        self.page_mut(&header)[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        self.unpin_page(header);
+        Ok(())
+    }
+
+    /// Id the next transaction will get. It only moves forward with
+    /// committed batches, so ids of rolled-back transactions are reused.
+    pub fn next_txn_id(&mut self) -> Result<u64, BlockError> {
+        let header = self.pin_page(0)?;
+        let bytes: [u8; 8] = self.page(&header)[HDR_NEXT_TXN..HDR_NEXT_TXN + 8].try_into().unwrap_or([0; 8]);
+        self.unpin_page(header);
+        Ok(u64::from_le_bytes(bytes).max(1))
+    }
+
+    pub fn set_next_txn_id(&mut self, id: u64) -> Result<(), BlockError> {
+        let header = self.pin_page(0)?;
+        self.page_mut(&header)[HDR_NEXT_TXN..HDR_NEXT_TXN + 8].copy_from_slice(&id.to_le_bytes());
+        self.unpin_page(header);
+        Ok(())
+    }
+
+    /// Transactions whose changes went out with the last commit although
+    /// they had not committed themselves. See `Database`.
+    pub fn in_flight(&mut self) -> Result<Vec<u64>, BlockError> {
+        let header = self.pin_page(0)?;
+        let page = self.page(&header);
+        let count = u32::from_le_bytes(page[HDR_IN_FLIGHT..HDR_IN_FLIGHT + 4].try_into().unwrap_or([0; 4])) as usize;
+        let ids = (0..count.min(MAX_IN_FLIGHT)).map(|i| {
+            let at = HDR_IN_FLIGHT + 4 + i * 8;
+            u64::from_le_bytes(page[at..at + 8].try_into().unwrap_or([0; 8]))
+        }).collect();
+        self.unpin_page(header);
+        Ok(ids)
+    }
+
+    /// Replaces the in-flight list; at most `MAX_IN_FLIGHT` ids are kept.
+    pub fn set_in_flight(&mut self, ids: &[u64]) -> Result<(), BlockError> {
+        if self.in_flight()? == ids {
+            return Ok(());
+        }
+        let ids = &ids[..ids.len().min(MAX_IN_FLIGHT)];
+        let header = self.pin_page(0)?;
+        let page = self.page_mut(&header);
+        page[HDR_IN_FLIGHT..HDR_IN_FLIGHT + 4].copy_from_slice(&(ids.len() as u32).to_le_bytes());
+        for (i, id) in ids.iter().enumerate() {
+            let at = HDR_IN_FLIGHT + 4 + i * 8;
+            page[at..at + 8].copy_from_slice(&id.to_le_bytes());
+        }
+        self.unpin_page(header);
+        Ok(())
+    }
+
+    /// Replaces page 0 with the header of another database, as restored
+    /// from a backup, keeping this device's own log location. The page
+    /// storage must match.
+    pub fn restore_header(&mut self, header: &[u8]) -> Result<(), BlockError> {
+        if header.len() != BLOCK_SIZE || &header[0..4] != MAGIC {
+            return Err(BlockError::Corrupt { page_id: 0 });
+        }
+        let code = u32::from_le_bytes(header[HDR_COMPRESSION..HDR_COMPRESSION + 4].try_into().unwrap_or([0; 4]));
+        if code != self.compression().code() {
+            return Err(BlockError::Corrupt { page_id: 0 });
+        }
+        let own = self.read_page(0)?;
+        let mut page = header.to_vec();
+        page[HDR_WAL_START..HDR_WAL_BLOCKS + 4].copy_from_slice(&own[HDR_WAL_START..HDR_WAL_BLOCKS + 4]);
+        self.write_page(0, &page)
+    }
+
+    /// First page of the table catalog (0 = no catalog yet).
+    pub fn catalog_page(&mut self) -> Result<u32, BlockError> {
+        self.header_u32(HDR_CATALOG_PAGE)
+    }
+
+    pub fn set_catalog_page(&mut self, page_id: u32) -> Result<(), BlockError> {
+        self.set_header_u32(HDR_CATALOG_PAGE, page_id)
+    }
+
+    pub fn num_tables(&mut self) -> Result<u32, BlockError> {
+        self.header_u32(HDR_NUM_TABLES)
+    }
+
+    pub fn set_num_tables(&mut self, count: u32) -> Result<(), BlockError> {
+        self.set_header_u32(HDR_NUM_TABLES, count)
+    }
+
+    /// Pages handed out so far, including page 0 and pages on the free list.
+    pub fn total_pages(&mut self) -> Result<u32, BlockError> {
+        self.header_u32(HDR_TOTAL_PAGES)
+    }
+
+    pub fn free_pages(&mut self) -> Result<u32, BlockError> {
+        self.header_u32(HDR_FREE_COUNT)
+    }
+
+    /// Returns a page to the free list so `alloc_page` can hand it out again.
+    pub fn free_page(&mut self, page_id: u32) -> Result<(), BlockError> {
+        if page_id == 0 || page_id >= self.total_pages()? {
+            return Err(BlockError::OutOfBounds);
+        }
+        let page = self.pin_page(page_id)?;
+        if &self.page(&page)[4..8] == FREE_MARKER {
+            self.unpin_page(page);
+            return Err(BlockError::DoubleFree);
+        }
+
+        let head = self.header_u32(HDR_FREE_HEAD)?;
+        let buf = self.page_mut(&page);
+        buf.fill(0);
+        buf[0..4].copy_from_slice(&head.to_le_bytes());
+        buf[4..8].copy_from_slice(FREE_MARKER);
+        self.unpin_page(page);
+
+        let free_count = self.header_u32(HDR_FREE_COUNT)?;
+        self.set_header_u32(HDR_FREE_HEAD, page_id)?;
+        self.set_header_u32(HDR_FREE_COUNT, free_count + 1)
+    }
+
+    pub fn read_page(&mut self, page_id: u32) -> Result<Vec<u8>, BlockError> {
+        let handle = self.pin_page(page_id)?;
+        let buf = self.page(&handle).to_vec();
+        self.unpin_page(handle);
         Ok(buf)
     }
 
@@ -52,22 +349,37 @@ impl Pager {
         if buf.len() != BLOCK_SIZE {
             return Err(BlockError::WriteError); // Or generic invalid arg
         }
-        self.disk.write_block(page_id, buf)
+        // Whole-page overwrite: no need to read the old contents on a miss
+        let handle = self.pool.pin(&mut self.store, page_id, false)?;
+        self.page_mut(&handle).copy_from_slice(buf);
+        self.unpin_page(handle);
+        Ok(())
     }
 
     pub fn alloc_page(&mut self) -> Result<u32, BlockError> {
-        // Read Page 0 to get total pages
-        let mut page0 = self.read_page(0)?;
-        
-        let total_pages_bytes: [u8; 4] = page0[4..8].try_into().unwrap_or([0; 4]);
-        let total_pages = u32::from_le_bytes(total_pages_bytes);
-
-        let new_page_id = total_pages;
-        let new_total = total_pages + 1;
-
-        // Update total pages
-        page0[4..8].copy_from_slice(&new_total.to_le_bytes());
-        self.write_page(0, &page0)?;
+        let new_page_id = match self.header_u32(HDR_FREE_HEAD)? {
+            // Extend the file, as long as the device has room
+            0 => {
+                let total_pages = self.total_pages()?;
+                if total_pages >= self.temp.floor {
+                    return Err(BlockError::DiskFull);
+                }
+                self.set_header_u32(HDR_TOTAL_PAGES, total_pages + 1)?;
+                total_pages
+            }
+            // Reuse the head of the free list
+            head => {
+                let page = self.read_page(head)?;
+                if &page[4..8] != FREE_MARKER {
+                    return Err(BlockError::ReadError);
+                }
+                let next: [u8; 4] = page[0..4].try_into().unwrap_or([0; 4]);
+                let free_count = self.header_u32(HDR_FREE_COUNT)?;
+                self.set_header_u32(HDR_FREE_HEAD, u32::from_le_bytes(next))?;
+                self.set_header_u32(HDR_FREE_COUNT, free_count.saturating_sub(1))?;
+                head
+            }
+        };
 
         // Initialize new page
         let mut new_page = vec![0u8; BLOCK_SIZE];
@@ -84,4 +396,69 @@ impl Pager {
 
         Ok(new_page_id)
     }
+
+    /// Hands out an unlogged scratch page (see `TempSpace`). Its contents
+    /// are only valid through `read_temp_page` until `free_temp_page`.
+    pub fn alloc_temp_page(&mut self) -> Result<u32, BlockError> {
+        if let Some(page_id) = self.temp.free.pop() {
+            self.temp.in_use += 1;
+            return Ok(page_id);
+        }
+        if self.temp.floor <= self.total_pages()? {
+            return Err(BlockError::DiskFull);
+        }
+        self.temp.floor -= 1;
+        self.temp.in_use += 1;
+        Ok(self.temp.floor)
+    }
+
+    pub fn free_temp_page(&mut self, page_id: u32) {
+        self.store.forget_unlogged(page_id);
+        self.temp.in_use = self.temp.in_use.saturating_sub(1);
+        if self.temp.in_use == 0 {
+            // Give the whole scratch area back to the file
+            self.temp.floor = self.store.num_blocks();
+            self.temp.free.clear();
+        } else {
+            self.temp.free.push(page_id);
+        }
+    }
+
+    /// Scratch pages currently handed out.
+    pub fn temp_pages(&self) -> u32 {
+        self.temp.in_use
+    }
+
+    pub fn read_temp_page(&mut self, page_id: u32, buf: &mut [u8]) -> Result<(), BlockError> {
+        self.store.read_unlogged(page_id, buf)
+    }
+
+    /// Writes a scratch page; the trailer is stamped in place.
+    pub fn write_temp_page(&mut self, page_id: u32, buf: &mut [u8]) -> Result<(), BlockError> {
+        self.store.write_unlogged(page_id, buf)
+    }
+}
+
+/// Writes the pager trailer (LSN, page id and checksum) into the end of `page`.
+pub fn stamp_trailer(page: &mut [u8], lsn: u64, page_id: u32) {
+    page[PAGE_DATA_SIZE..PAGE_DATA_SIZE + 8].copy_from_slice(&lsn.to_le_bytes());
+    page[PAGE_DATA_SIZE + 8..PAGE_DATA_SIZE + 12].copy_from_slice(&page_id.to_le_bytes());
+    let crc = crc32(&page[..BLOCK_SIZE - 4]);
+    page[BLOCK_SIZE - 4..BLOCK_SIZE].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// True if `page` carries a valid checksum and was written as `page_id`.
+/// Catches torn and bit-flipped blocks as well as misdirected writes.
+pub fn verify_trailer(page: &[u8], page_id: u32) -> bool {
+    let stored = u32::from_le_bytes(page[BLOCK_SIZE - 4..BLOCK_SIZE].try_into().unwrap_or([0; 4]));
+    stored == crc32(&page[..BLOCK_SIZE - 4]) && trailer_page_id(page) == page_id
+}
+
+/// LSN of the log frame this page image was last written through.
+pub fn page_lsn(page: &[u8]) -> u64 {
+    u64::from_le_bytes(page[PAGE_DATA_SIZE..PAGE_DATA_SIZE + 8].try_into().unwrap_or([0; 8]))
+}
+
+pub fn trailer_page_id(page: &[u8]) -> u32 {
+    u32::from_le_bytes(page[PAGE_DATA_SIZE + 8..PAGE_DATA_SIZE + 12].try_into().unwrap_or([0; 4]))
 }
@@ -0,0 +1,281 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::sawitdb::error::DbError;
+use crate::sawitdb::row::Row;
+use crate::sawitdb::types::Value;
+use super::ast::{AggFunc, BinaryOp, Expr, Select, SelectItem};
+use super::eval::{self, eval};
+use super::executor::output_name;
+use super::order::output_ref;
+
+// GROUP BY evaluation. Rows are folded into one set of accumulators per
+// distinct group key; groups come out in key order. Without GROUP BY an
+// aggregate query has exactly one group, even over no rows.
+//
+// After grouping, every select item and HAVING is evaluated by replacing
+// its aggregates and grouping expressions with their per-group values.
+
+/// True if the query needs grouping: it has GROUP BY or an aggregate in
+/// its select list, HAVING or ORDER BY.
+pub fn is_grouped(select: &Select) -> bool {
+    !select.group_by.is_empty()
+        || select.having.is_some()
+        || select.items.iter().any(|item| matches!(item, SelectItem::Expr { expr, .. } if has_aggregate(expr)))
+        || select.order_by.iter().any(|item| has_aggregate(&item.expr))
+}
+
+pub fn has_aggregate(expr: &Expr) -> bool {
+    let mut found = false;
+    visit(expr, &mut |e| found |= matches!(e, Expr::Aggregate { .. }));
+    found
+}
+
+/// Accumulates rows into groups for one SELECT.
+pub struct Grouper {
+    group_by: Vec<Expr>,
+    items: Vec<SelectItem>,
+    having: Option<Expr>,
+    order_by: Vec<Expr>,
+    // Distinct aggregate calls of the select list, HAVING and ORDER BY
+    aggregates: Vec<Expr>,
+    groups: BTreeMap<Vec<Value>, Vec<Accumulator>>,
+}
+
+impl Grouper {
+    /// Checks the query shape: no aggregates in WHERE or GROUP BY, none
+    /// nested, and every column outside an aggregate is grouped on.
+    pub fn new(select: &Select) -> Result<Self, DbError> {
+        if select.filter.as_ref().is_some_and(has_aggregate) {
+            return Err(DbError::Eval(String::from("aggregates are not allowed in WHERE")));
+        }
+        if select.group_by.iter().any(has_aggregate) {
+            return Err(DbError::Eval(String::from("aggregates are not allowed in GROUP BY")));
+        }
+        let mut outputs = Vec::new();
+        for item in &select.items {
+            match item {
+                SelectItem::Wildcard => return Err(DbError::Eval(String::from("* cannot be used with GROUP BY or aggregates"))),
+                SelectItem::Expr { expr, .. } => outputs.push(expr),
+            }
+        }
+        outputs.extend(&select.having);
+        // ORDER BY may name a result column or compute its own value
+        let order_by: Vec<Expr> = select.order_by.iter().map(|o| o.expr.clone()).collect();
+        outputs.extend(order_by.iter().filter(|e| output_ref(e, &select.items).is_none()));
+
+        let mut aggregates: Vec<Expr> = Vec::new();
+        for expr in outputs {
+            check_grouped(expr, &select.group_by)?;
+            let mut error = None;
+            visit(expr, &mut |e| {
+                if let Expr::Aggregate { arg: Some(arg), .. } = e {
+                    if has_aggregate(arg) {
+                        error = Some(DbError::Eval(format!("aggregates cannot be nested: {}", e)));
+                    }
+                }
+                if matches!(e, Expr::Aggregate { .. }) && !aggregates.contains(e) {
+                    aggregates.push(e.clone());
+                }
+            });
+            if let Some(e) = error {
+                return Err(e);
+            }
+        }
+        Ok(Grouper {
+            group_by: select.group_by.clone(),
+            items: select.items.clone(),
+            having: select.having.clone(),
+            order_by,
+            aggregates,
+            groups: BTreeMap::new(),
+        })
+    }
+
+    pub fn add(&mut self, row: &Row) -> Result<(), DbError> {
+        let key = self.group_by.iter().map(|e| eval(e, row)).collect::<Result<Vec<_>, _>>()?;
+        if !self.groups.contains_key(&key) {
+            let accs = self.aggregates.iter().map(Accumulator::new).collect();
+            self.groups.insert(key.clone(), accs);
+        }
+        if let Some(accs) = self.groups.get_mut(&key) {
+            for (acc, expr) in accs.iter_mut().zip(&self.aggregates) {
+                let value = match expr {
+                    Expr::Aggregate { arg: Some(arg), .. } => eval(arg, row)?,
+                    // COUNT(*) counts the row itself
+                    _ => Value::Bool(true),
+                };
+                acc.add(value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// One output row per group that passes HAVING, with its ORDER BY
+    /// key.
+    pub fn finish(mut self) -> Result<Vec<(Vec<Value>, Row)>, DbError> {
+        if self.group_by.is_empty() && self.groups.is_empty() {
+            self.groups.insert(Vec::new(), self.aggregates.iter().map(Accumulator::new).collect());
+        }
+        let empty = Row::new();
+        let mut rows = Vec::with_capacity(self.groups.len());
+        for (key, accs) in self.groups {
+            let mut known: Vec<(&Expr, Value)> = self.group_by.iter().zip(key).collect();
+            for (expr, acc) in self.aggregates.iter().zip(accs) {
+                known.push((expr, acc.finish()));
+            }
+            if let Some(having) = &self.having {
+                if !eval::matches(Some(&substitute(having, &known)), &empty)? {
+                    continue;
+                }
+            }
+            let mut row = Row::new();
+            for item in &self.items {
+                if let SelectItem::Expr { expr, alias } = item {
+                    row.push(&output_name(expr, alias.as_deref()), eval(&substitute(expr, &known), &empty)?);
+                }
+            }
+            let mut keys = Vec::with_capacity(self.order_by.len());
+            for expr in &self.order_by {
+                keys.push(match output_ref(expr, &self.items) {
+                    Some(name) => row.get(name).cloned().unwrap_or(Value::Null),
+                    None => eval(&substitute(expr, &known), &empty)?,
+                });
+            }
+            rows.push((keys, row));
+        }
+        Ok(rows)
+    }
+}
+
+enum Accumulator {
+    Count(i64),
+    Sum(Option<Value>),
+    Avg { sum: Option<Value>, count: i64 },
+    Min(Option<Value>),
+    Max(Option<Value>),
+}
+
+impl Accumulator {
+    fn new(expr: &Expr) -> Self {
+        match expr {
+            Expr::Aggregate { func: AggFunc::Sum, .. } => Accumulator::Sum(None),
+            Expr::Aggregate { func: AggFunc::Avg, .. } => Accumulator::Avg { sum: None, count: 0 },
+            Expr::Aggregate { func: AggFunc::Min, .. } => Accumulator::Min(None),
+            Expr::Aggregate { func: AggFunc::Max, .. } => Accumulator::Max(None),
+            _ => Accumulator::Count(0),
+        }
+    }
+
+    // NULLs are ignored by every aggregate.
+    fn add(&mut self, value: Value) -> Result<(), DbError> {
+        if value.is_null() {
+            return Ok(());
+        }
+        match self {
+            Accumulator::Count(n) => *n += 1,
+            Accumulator::Sum(sum) => *sum = Some(add(sum.take(), value, "SUM")?),
+            Accumulator::Avg { sum, count } => {
+                *sum = Some(add(sum.take(), value, "AVG")?);
+                *count += 1;
+            }
+            Accumulator::Min(min) => {
+                if min.as_ref().is_none_or(|m| value < *m) {
+                    *min = Some(value);
+                }
+            }
+            Accumulator::Max(max) => {
+                if max.as_ref().is_none_or(|m| value > *m) {
+                    *max = Some(value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Aggregates over no values are NULL, except COUNT which is 0.
+    fn finish(self) -> Value {
+        match self {
+            Accumulator::Count(n) => Value::Int(n),
+            Accumulator::Sum(sum) => sum.unwrap_or(Value::Null),
+            Accumulator::Avg { sum: Some(Value::Int(sum)), count } => Value::Float(sum as f64 / count as f64),
+            Accumulator::Avg { sum: Some(Value::Float(sum)), count } => Value::Float(sum / count as f64),
+            Accumulator::Avg { .. } => Value::Null,
+            Accumulator::Min(v) | Accumulator::Max(v) => v.unwrap_or(Value::Null),
+        }
+    }
+}
+
+// Running sum: INT while every input is an INT, FLOAT from the first
+// FLOAT on.
+fn add(sum: Option<Value>, value: Value, func: &str) -> Result<Value, DbError> {
+    match (sum, value) {
+        (_, v) if !matches!(v, Value::Int(_) | Value::Float(_)) => {
+            Err(DbError::Eval(format!("{} expects numbers, not {}", func, v.value_type().map(|t| t.name()).unwrap_or("NULL"))))
+        }
+        (None, v) => Ok(v),
+        (Some(sum), v) => eval::arith(BinaryOp::Add, sum, v),
+    }
+}
+
+// Columns may only appear inside an aggregate or as part of a grouping
+// expression.
+fn check_grouped(expr: &Expr, group_by: &[Expr]) -> Result<(), DbError> {
+    if group_by.contains(expr) {
+        return Ok(());
+    }
+    match expr {
+        Expr::Literal(_) | Expr::Aggregate { .. } => Ok(()),
+        Expr::Column { .. } => Err(DbError::Eval(format!("column '{}' must appear in GROUP BY or in an aggregate", expr))),
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } => check_grouped(expr, group_by),
+        Expr::Binary { left, right, .. } | Expr::Like { expr: left, pattern: right, .. } => {
+            check_grouped(left, group_by)?;
+            check_grouped(right, group_by)
+        }
+        Expr::In { expr, list, .. } => {
+            check_grouped(expr, group_by)?;
+            list.iter().try_for_each(|e| check_grouped(e, group_by))
+        }
+    }
+}
+
+// `expr` with every sub-expression found in `known` replaced by its value.
+fn substitute(expr: &Expr, known: &[(&Expr, Value)]) -> Expr {
+    if let Some((_, v)) = known.iter().find(|(e, _)| *e == expr) {
+        return Expr::Literal(v.clone());
+    }
+    let sub = |e: &Expr| Box::new(substitute(e, known));
+    match expr {
+        Expr::Unary { op, expr } => Expr::Unary { op: *op, expr: sub(expr) },
+        Expr::Binary { op, left, right } => Expr::Binary { op: *op, left: sub(left), right: sub(right) },
+        Expr::In { expr, list, negated } => Expr::In {
+            expr: sub(expr),
+            list: list.iter().map(|e| substitute(e, known)).collect(),
+            negated: *negated,
+        },
+        Expr::Like { expr, pattern, negated } => Expr::Like { expr: sub(expr), pattern: sub(pattern), negated: *negated },
+        Expr::IsNull { expr, negated } => Expr::IsNull { expr: sub(expr), negated: *negated },
+        expr => expr.clone(),
+    }
+}
+
+// Calls `f` on `expr` and every sub-expression, parents first.
+fn visit(expr: &Expr, f: &mut dyn FnMut(&Expr)) {
+    f(expr);
+    match expr {
+        Expr::Literal(_) | Expr::Column { .. } | Expr::Aggregate { arg: None, .. } => {}
+        Expr::Aggregate { arg: Some(e), .. } | Expr::Unary { expr: e, .. } | Expr::IsNull { expr: e, .. } => visit(e, f),
+        Expr::Binary { left, right, .. } | Expr::Like { expr: left, pattern: right, .. } => {
+            visit(left, f);
+            visit(right, f);
+        }
+        Expr::In { expr, list, .. } => {
+            visit(expr, f);
+            for e in list {
+                visit(e, f);
+            }
+        }
+    }
+}
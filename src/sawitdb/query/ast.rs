@@ -0,0 +1,211 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::fmt;
+use crate::sawitdb::catalog::Column;
+use crate::sawitdb::types::Value;
+
+/// One parsed statement. SQL and SawitDB (AQL) spellings of the same
+/// command produce the same node.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// `CREATE TABLE t (a INT PRIMARY KEY, ...)` / `LAHAN t`
+    CreateTable { name: String, columns: Vec<Column>, primary_key: Option<String> },
+    /// `DROP TABLE t` / `BAKAR LAHAN t`
+    DropTable { name: String },
+    /// `SHOW TABLES` / `LIHAT LAHAN`
+    ShowTables,
+    /// `CREATE INDEX ON t (col)` / `INDEKS t PADA col`
+    CreateIndex { table: String, column: String },
+    /// `INSERT INTO t (cols) VALUES (...), ...` / `TANAM KE t (cols) BIBIT (...)`
+    Insert { table: String, columns: Vec<String>, rows: Vec<Vec<Expr>> },
+    /// `SELECT ... FROM t WHERE ...` / `PANEN ... DARI t DIMANA ...`
+    Select(Select),
+    /// `UPDATE t SET c = e WHERE ...` / `PUPUK t DENGAN c = e DIMANA ...`
+    Update { table: String, assignments: Vec<(String, Expr)>, filter: Option<Expr> },
+    /// `DELETE FROM t WHERE ...` / `GUSUR DARI t DIMANA ...`
+    Delete { table: String, filter: Option<Expr> },
+    /// `BEGIN [TRANSACTION]` / `MULAI`
+    Begin,
+    /// `COMMIT` / `SIMPAN`
+    Commit,
+    /// `ROLLBACK` / `BATAL`
+    Rollback,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub items: Vec<SelectItem>,
+    pub table: String,
+    pub alias: Option<String>,
+    pub joins: Vec<Join>,
+    pub filter: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderItem>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// `[INNER | LEFT] JOIN table [alias] ON condition`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Join {
+    pub kind: JoinKind,
+    pub table: String,
+    pub alias: Option<String>,
+    pub on: Expr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    Inner,
+    /// Keeps every row of the left side, with NULLs when nothing matches.
+    Left,
+}
+
+/// `expr [ASC | DESC]` in ORDER BY.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderItem {
+    pub expr: Expr,
+    pub desc: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    /// `*`
+    Wildcard,
+    Expr { expr: Expr, alias: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    /// Column reference, optionally qualified by table: `t.col`
+    Column { table: Option<String>, name: String },
+    Unary { op: UnaryOp, expr: Box<Expr> },
+    Binary { op: BinaryOp, left: Box<Expr>, right: Box<Expr> },
+    /// `e [NOT] IN (a, b, ...)`
+    In { expr: Box<Expr>, list: Vec<Expr>, negated: bool },
+    /// `e [NOT] LIKE pattern` (`%` any run, `_` any one character)
+    Like { expr: Box<Expr>, pattern: Box<Expr>, negated: bool },
+    /// `e IS [NOT] NULL`
+    IsNull { expr: Box<Expr>, negated: bool },
+    /// `COUNT(*)` (no argument), `COUNT(e)`, `SUM(e)`, ...
+    Aggregate { func: AggFunc, arg: Option<Box<Expr>> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggFunc {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggFunc {
+    pub fn name(self) -> &'static str {
+        match self {
+            AggFunc::Count => "COUNT",
+            AggFunc::Sum => "SUM",
+            AggFunc::Avg => "AVG",
+            AggFunc::Min => "MIN",
+            AggFunc::Max => "MAX",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        [AggFunc::Count, AggFunc::Sum, AggFunc::Avg, AggFunc::Min, AggFunc::Max]
+            .into_iter()
+            .find(|f| f.name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl BinaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+        }
+    }
+}
+
+// Renders the expression back as SQL; used to name unaliased result
+// columns. Nested operators are parenthesised rather than relying on
+// precedence.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Literal(Value::String(s)) => write!(f, "'{}'", s.replace('\'', "''")),
+            Expr::Literal(v @ Value::Timestamp(_)) => write!(f, "TIMESTAMP '{}'", v),
+            Expr::Literal(v) => write!(f, "{}", v),
+            Expr::Column { table: Some(table), name } => write!(f, "{}.{}", table, name),
+            Expr::Column { table: None, name } => f.write_str(name),
+            Expr::Unary { op: UnaryOp::Not, expr } => write!(f, "NOT {}", Nested(expr)),
+            Expr::Unary { op: UnaryOp::Neg, expr } => write!(f, "-{}", Nested(expr)),
+            Expr::Binary { op, left, right } => write!(f, "{} {} {}", Nested(left), op.symbol(), Nested(right)),
+            Expr::In { expr, list, negated } => {
+                write!(f, "{} {}IN (", Nested(expr), if *negated { "NOT " } else { "" })?;
+                for (i, item) in list.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str(")")
+            }
+            Expr::Like { expr, pattern, negated } => {
+                write!(f, "{} {}LIKE {}", Nested(expr), if *negated { "NOT " } else { "" }, Nested(pattern))
+            }
+            Expr::IsNull { expr, negated } => {
+                write!(f, "{} IS {}NULL", Nested(expr), if *negated { "NOT " } else { "" })
+            }
+            Expr::Aggregate { func, arg: Some(arg) } => write!(f, "{}({})", func.name(), arg),
+            Expr::Aggregate { func, arg: None } => write!(f, "{}(*)", func.name()),
+        }
+    }
+}
+
+// An operand: parenthesised unless it is a literal or column.
+struct Nested<'a>(&'a Expr);
+
+impl fmt::Display for Nested<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Expr::Literal(_) | Expr::Column { .. } | Expr::Aggregate { .. } => write!(f, "{}", self.0),
+            expr => write!(f, "({})", expr),
+        }
+    }
+}
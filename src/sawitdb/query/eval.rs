@@ -0,0 +1,204 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use crate::sawitdb::error::DbError;
+use crate::sawitdb::row::Row;
+use crate::sawitdb::types::Value;
+use super::ast::{BinaryOp, Expr, UnaryOp};
+
+// Expression evaluation with SQL's three-valued logic: any comparison
+// involving NULL is NULL (unknown), and AND/OR/NOT follow Kleene logic.
+// A WHERE clause keeps a row only when it evaluates to TRUE.
+
+/// Evaluates `expr` against `row`. Columns the row lacks read as NULL.
+pub fn eval(expr: &Expr, row: &Row) -> Result<Value, DbError> {
+    match expr {
+        Expr::Literal(v) => Ok(v.clone()),
+        Expr::Column { table, name } => Ok(column(row, table.as_deref(), name)),
+        Expr::Unary { op: UnaryOp::Not, expr } => Ok(match truth(&eval(expr, row)?, "NOT")? {
+            Some(b) => Value::Bool(!b),
+            None => Value::Null,
+        }),
+        Expr::Unary { op: UnaryOp::Neg, expr } => match eval(expr, row)? {
+            Value::Null => Ok(Value::Null),
+            Value::Int(v) => v.checked_neg().map(Value::Int).ok_or_else(overflow),
+            Value::Float(v) => Ok(Value::Float(-v)),
+            v => Err(DbError::Eval(format!("cannot negate {}", type_name(&v)))),
+        },
+        Expr::Binary { op: BinaryOp::And, left, right } => {
+            // FALSE wins over NULL, so the right side is needed unless left is FALSE
+            let l = truth(&eval(left, row)?, "AND")?;
+            if l == Some(false) {
+                return Ok(Value::Bool(false));
+            }
+            Ok(match (l, truth(&eval(right, row)?, "AND")?) {
+                (_, Some(false)) => Value::Bool(false),
+                (Some(true), Some(true)) => Value::Bool(true),
+                _ => Value::Null,
+            })
+        }
+        Expr::Binary { op: BinaryOp::Or, left, right } => {
+            let l = truth(&eval(left, row)?, "OR")?;
+            if l == Some(true) {
+                return Ok(Value::Bool(true));
+            }
+            Ok(match (l, truth(&eval(right, row)?, "OR")?) {
+                (_, Some(true)) => Value::Bool(true),
+                (Some(false), Some(false)) => Value::Bool(false),
+                _ => Value::Null,
+            })
+        }
+        Expr::Binary { op, left, right } => binary(*op, eval(left, row)?, eval(right, row)?),
+        Expr::In { expr, list, negated } => {
+            let v = eval(expr, row)?;
+            if v.is_null() {
+                return Ok(Value::Null);
+            }
+            let mut saw_null = false;
+            for item in list {
+                let item = eval(item, row)?;
+                if item.is_null() {
+                    saw_null = true;
+                } else if item == v {
+                    return Ok(Value::Bool(!negated));
+                }
+            }
+            // `x IN (1, NULL)` is unknown, not false, when x is not 1
+            Ok(if saw_null { Value::Null } else { Value::Bool(*negated) })
+        }
+        Expr::Like { expr, pattern, negated } => {
+            let (v, p) = (eval(expr, row)?, eval(pattern, row)?);
+            let pattern = match p {
+                Value::Null => return Ok(Value::Null),
+                Value::String(p) => p,
+                p => return Err(DbError::Eval(format!("LIKE pattern must be a STRING, not {}", type_name(&p)))),
+            };
+            let matched = match v {
+                Value::Null => return Ok(Value::Null),
+                Value::String(s) => like(&s, &pattern),
+                v => like(&v.to_string(), &pattern),
+            };
+            Ok(Value::Bool(matched != *negated))
+        }
+        Expr::IsNull { expr, negated } => Ok(Value::Bool(eval(expr, row)?.is_null() != *negated)),
+        Expr::Aggregate { .. } => Err(DbError::Eval(format!("{} is not allowed here", expr))),
+    }
+}
+
+/// True if `filter` holds for `row`; no filter matches every row.
+pub fn matches(filter: Option<&Expr>, row: &Row) -> Result<bool, DbError> {
+    match filter {
+        Some(expr) => Ok(truth(&eval(expr, row)?, "WHERE")? == Some(true)),
+        None => Ok(true),
+    }
+}
+
+/// Looks a column up as `table.name` first (joined rows carry qualified
+/// names), then by its bare name.
+pub fn column(row: &Row, table: Option<&str>, name: &str) -> Value {
+    if let Some(table) = table {
+        if let Some(v) = row.get(&format!("{}.{}", table, name)) {
+            return v.clone();
+        }
+    }
+    row.get(name).cloned().unwrap_or(Value::Null)
+}
+
+// Boolean view of a value: None for NULL, an error for non-booleans.
+fn truth(v: &Value, context: &str) -> Result<Option<bool>, DbError> {
+    match v {
+        Value::Bool(b) => Ok(Some(*b)),
+        Value::Null => Ok(None),
+        v => Err(DbError::Eval(format!("{} expects a BOOL, not {}", context, type_name(v)))),
+    }
+}
+
+fn binary(op: BinaryOp, l: Value, r: Value) -> Result<Value, DbError> {
+    if l.is_null() || r.is_null() {
+        return Ok(Value::Null);
+    }
+    let ord = l.cmp(&r);
+    let cmp = |f: fn(Ordering) -> bool| Ok(Value::Bool(f(ord)));
+    match op {
+        BinaryOp::Eq => cmp(Ordering::is_eq),
+        BinaryOp::Ne => cmp(Ordering::is_ne),
+        BinaryOp::Lt => cmp(Ordering::is_lt),
+        BinaryOp::Le => cmp(Ordering::is_le),
+        BinaryOp::Gt => cmp(Ordering::is_gt),
+        BinaryOp::Ge => cmp(Ordering::is_ge),
+        BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => arith(op, l, r),
+        BinaryOp::And | BinaryOp::Or => unreachable!("logical operators are evaluated lazily"),
+    }
+}
+
+/// Arithmetic on numbers: INT op INT stays INT (overflow is an error),
+/// anything involving a FLOAT is computed in floating point.
+pub fn arith(op: BinaryOp, l: Value, r: Value) -> Result<Value, DbError> {
+    match (&l, &r) {
+        (Value::Int(a), Value::Int(b)) => {
+            let (a, b) = (*a, *b);
+            let result = match op {
+                BinaryOp::Add => a.checked_add(b),
+                BinaryOp::Sub => a.checked_sub(b),
+                BinaryOp::Mul => a.checked_mul(b),
+                _ if b == 0 => return Err(DbError::Eval(String::from("division by zero"))),
+                _ => a.checked_div(b),
+            };
+            result.map(Value::Int).ok_or_else(overflow)
+        }
+        (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) => {
+            let (a, b) = (as_f64(&l), as_f64(&r));
+            Ok(Value::Float(match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Sub => a - b,
+                BinaryOp::Mul => a * b,
+                _ if b == 0.0 => return Err(DbError::Eval(String::from("division by zero"))),
+                _ => a / b,
+            }))
+        }
+        _ => Err(DbError::Eval(format!("cannot apply '{}' to {} and {}", op.symbol(), type_name(&l), type_name(&r)))),
+    }
+}
+
+fn as_f64(v: &Value) -> f64 {
+    match v {
+        Value::Int(i) => *i as f64,
+        Value::Float(f) => *f,
+        _ => 0.0,
+    }
+}
+
+fn overflow() -> DbError {
+    DbError::Eval(String::from("integer overflow"))
+}
+
+fn type_name(v: &Value) -> &'static str {
+    v.value_type().map(|t| t.name()).unwrap_or("NULL")
+}
+
+/// SQL LIKE: `%` matches any run of characters, `_` exactly one.
+pub fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    // Position of the last `%` and the text position it is trying
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '_' || pattern[p] == text[t]) {
+            t += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '%' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((sp, st)) = star {
+            // Let the last `%` swallow one more character
+            p = sp + 1;
+            t = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '%')
+}
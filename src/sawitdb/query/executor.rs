@@ -0,0 +1,463 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::sawitdb::btree::RangeCursor;
+use crate::sawitdb::catalog::{Column, TableInfo};
+use crate::sawitdb::database::{self, Database, AUTO_KEY};
+use crate::sawitdb::error::DbError;
+use crate::sawitdb::heap::{HeapCursor, HeapFile};
+use crate::sawitdb::lock::{LockMode, LockTarget};
+use crate::sawitdb::mvcc::{self, Snapshot};
+use crate::sawitdb::row::Row;
+use crate::sawitdb::types::{Value, ValueType};
+use super::aggregate::{self, Grouper};
+use super::ast::{Expr, Select, SelectItem, Statement};
+use super::eval::{self, eval};
+use super::join::{self, JoinSource};
+use super::order::{self, SortedRows, Sorter};
+use super::plan::{self, Access, KeyRange};
+
+/// Result of executing one statement.
+pub enum Output {
+    /// Rows to be pulled with `RowStream::next_row`.
+    Rows(Box<RowStream>),
+    /// Number of rows inserted, updated or deleted.
+    Affected(usize),
+    Done,
+}
+
+/// Parses and executes a single statement.
+pub fn run(db: &mut Database, src: &str) -> Result<Output, DbError> {
+    execute(db, &super::parse(src)?)
+}
+
+pub fn execute(db: &mut Database, stmt: &Statement) -> Result<Output, DbError> {
+    match stmt {
+        Statement::CreateTable { name, columns, primary_key } => {
+            let (key, columns) = match primary_key {
+                Some(key) => (key.as_str(), columns.clone()),
+                // Schemaless, or no key declared: rows are numbered
+                None if columns.is_empty() || columns.iter().any(|c| c.name == AUTO_KEY) => (AUTO_KEY, columns.clone()),
+                None => {
+                    let mut with_id = vec![Column::new(AUTO_KEY, ValueType::Int)];
+                    with_id.extend(columns.iter().cloned());
+                    (AUTO_KEY, with_id)
+                }
+            };
+            db.create_table(name, key, columns)?;
+            Ok(Output::Done)
+        }
+        Statement::DropTable { name } => {
+            db.drop_table(name)?;
+            Ok(Output::Done)
+        }
+        Statement::ShowTables => {
+            let rows = db.tables().iter()
+                .map(|t| Row::new().with("table", Value::String(t.name.clone())).with("key", Value::String(t.key_field.clone())))
+                .collect();
+            Ok(Output::Rows(Box::new(RowStream::from_rows(rows))))
+        }
+        Statement::CreateIndex { table, column } => {
+            db.create_index(table, column)?;
+            Ok(Output::Done)
+        }
+        Statement::Insert { table, columns, rows } => insert(db, table, columns, rows),
+        Statement::Select(select) => Ok(Output::Rows(Box::new(RowStream::select(db, select)?))),
+        Statement::Update { table, assignments, filter } => {
+            let info = db.table(table)?.clone();
+            for (column, expr) in assignments {
+                check_column(&info, None, column)?;
+                check_expr(&info, expr)?;
+            }
+            let matched = RowStream::open(db, &info, filter.as_ref(), Vec::new())?.collect_rows(db)?;
+            for row in &matched {
+                let mut updated = row.clone();
+                for (column, expr) in assignments {
+                    updated.set(column, eval(expr, row)?);
+                }
+                db.update(table, row.key(&info.key_field)?, &updated)?;
+            }
+            Ok(Output::Affected(matched.len()))
+        }
+        Statement::Delete { table, filter } => {
+            let info = db.table(table)?.clone();
+            let matched = RowStream::open(db, &info, filter.as_ref(), Vec::new())?.collect_rows(db)?;
+            for row in &matched {
+                db.delete(table, row.key(&info.key_field)?)?;
+            }
+            Ok(Output::Affected(matched.len()))
+        }
+        Statement::Begin => {
+            db.begin()?;
+            Ok(Output::Done)
+        }
+        Statement::Commit => {
+            db.commit()?;
+            Ok(Output::Done)
+        }
+        Statement::Rollback => {
+            db.rollback()?;
+            Ok(Output::Done)
+        }
+    }
+}
+
+fn insert(db: &mut Database, table: &str, columns: &[String], rows: &[Vec<Expr>]) -> Result<Output, DbError> {
+    let info = db.table(table)?.clone();
+    let names: Vec<String> = if columns.is_empty() {
+        if info.schema.is_empty() {
+            return Err(DbError::Eval(format!("INSERT into '{}' needs a column list", table)));
+        }
+        // Without a column list, an omitted auto key is filled in
+        let mut names: Vec<String> = info.schema.iter().map(|c| c.name.clone()).collect();
+        if info.key_field == AUTO_KEY && rows.iter().all(|r| r.len() + 1 == names.len()) {
+            names.retain(|n| n != AUTO_KEY);
+        }
+        names
+    } else {
+        columns.to_vec()
+    };
+    for name in &names {
+        check_column(&info, None, name)?;
+    }
+
+    let empty = Row::new();
+    for values in rows {
+        if values.len() != names.len() {
+            return Err(DbError::Eval(format!("expected {} values, got {}", names.len(), values.len())));
+        }
+        let mut row = Row::new();
+        for (name, expr) in names.iter().zip(values) {
+            if has_column(expr) {
+                return Err(DbError::Eval(String::from("INSERT values must be constants")));
+            }
+            row.set(name, eval(expr, &empty)?);
+        }
+        db.insert(table, &row)?;
+    }
+    Ok(Output::Affected(rows.len()))
+}
+
+// Table sources read the row versions their snapshot sees. Index entries
+// may be left over from other versions, so a row read through an index
+// must still carry the entry's key in `column`.
+enum Source {
+    Rows(vec::IntoIter<Row>),
+    Heap { cursor: HeapCursor, snapshot: Arc<Snapshot> },
+    Index {
+        root: u32,
+        column: String,
+        ranges: vec::IntoIter<KeyRange>,
+        cursor: Option<Box<RangeCursor>>,
+        snapshot: Arc<Snapshot>,
+    },
+    Sorted(SortedRows),
+    Join(Box<JoinSource>),
+}
+
+/// Result rows of a query, produced one at a time. The stream does not
+/// borrow the database, so the caller passes it to every `next_row`.
+pub struct RowStream {
+    info: Option<TableInfo>,
+    source: Source,
+    filter: Option<Expr>,
+    items: Vec<SelectItem>,
+    // OFFSET rows still to drop and LIMIT rows still to return
+    skip: usize,
+    remaining: Option<usize>,
+}
+
+impl RowStream {
+    fn from_rows(rows: Vec<Row>) -> Self {
+        Self::from_source(Source::Rows(rows.into_iter()))
+    }
+
+    fn from_source(source: Source) -> Self {
+        RowStream { info: None, source, filter: None, items: Vec::new(), skip: 0, remaining: None }
+    }
+
+    fn select(db: &mut Database, select: &Select) -> Result<Self, DbError> {
+        if !select.joins.is_empty() {
+            let resolved = join::resolve(db, select)?;
+            return Self::select_rows(db, &resolved);
+        }
+        let info = db.table(&select.table)?.clone();
+        for item in &select.items {
+            if let SelectItem::Expr { expr, .. } = item {
+                check_expr(&info, expr)?;
+            }
+        }
+        for item in &select.order_by {
+            if order::output_ref(&item.expr, &select.items).is_none() {
+                check_expr(&info, &item.expr)?;
+            }
+        }
+        for expr in select.group_by.iter().chain(&select.having) {
+            check_expr(&info, expr)?;
+        }
+        Self::select_rows(db, select)
+    }
+
+    // Runs a checked (or, with joins, resolved) SELECT.
+    fn select_rows(db: &mut Database, select: &Select) -> Result<Self, DbError> {
+        let keep = select.limit.map(|limit| limit.saturating_add(select.offset.unwrap_or(0)));
+        let desc: Vec<bool> = select.order_by.iter().map(|o| o.desc).collect();
+
+        let mut stream = if aggregate::is_grouped(select) {
+            // Grouped results are only known once every row has been read
+            let mut grouper = Grouper::new(select)?;
+            let mut rows = Self::input(db, select, Vec::new())?;
+            while let Some(row) = rows.next_row(db)? {
+                grouper.add(&row)?;
+            }
+            let groups = grouper.finish()?;
+            if select.order_by.is_empty() {
+                Self::from_rows(groups.into_iter().map(|(_, row)| row).collect())
+            } else {
+                let mut sorter = Sorter::new(desc, keep);
+                for (keys, row) in groups {
+                    sorter.push(db.pager(), keys, row)?;
+                }
+                Self::from_source(Source::Sorted(sorter.finish(db.pager())?))
+            }
+        } else if select.order_by.is_empty() {
+            Self::input(db, select, select.items.clone())?
+        } else {
+            let mut rows = Self::input(db, select, Vec::new())?;
+            let mut sorter = Sorter::new(desc, keep);
+            while let Some(row) = rows.next_row(db)? {
+                let out = project(&select.items, &row)?;
+                let mut keys = Vec::with_capacity(select.order_by.len());
+                for item in &select.order_by {
+                    keys.push(match order::output_ref(&item.expr, &select.items) {
+                        Some(name) => eval::column(&out, None, name),
+                        None => eval(&item.expr, &row)?,
+                    });
+                }
+                sorter.push(db.pager(), keys, out)?;
+            }
+            Self::from_source(Source::Sorted(sorter.finish(db.pager())?))
+        };
+        stream.skip = select.offset.unwrap_or(0);
+        stream.remaining = select.limit;
+        Ok(stream)
+    }
+
+    // The rows a SELECT reads, filtered by its WHERE clause and projected
+    // onto `items` (all fields if empty).
+    fn input(db: &mut Database, select: &Select, items: Vec<SelectItem>) -> Result<Self, DbError> {
+        if select.joins.is_empty() {
+            let info = db.table(&select.table)?.clone();
+            return Self::open(db, &info, select.filter.as_ref(), items);
+        }
+        let mut stream = join::open(db, select)?;
+        stream.filter = select.filter.clone();
+        stream.items = items;
+        Ok(stream)
+    }
+
+    // Rows of `info` matching `filter`, projected onto `items` (all
+    // fields if empty).
+    fn open(db: &mut Database, info: &TableInfo, filter: Option<&Expr>, items: Vec<SelectItem>) -> Result<Self, DbError> {
+        if let Some(filter) = filter {
+            check_expr(info, filter)?;
+            if aggregate::has_aggregate(filter) {
+                return Err(DbError::Eval(String::from("aggregates are not allowed in WHERE")));
+            }
+        }
+        let snapshot = db.snapshot()?;
+        let mut stream = Self::open_as(db, info, &info.name, filter, &snapshot)?;
+        stream.items = items;
+        Ok(stream)
+    }
+
+    /// Rows of `info`, referred to as `alias` in `filter`, matching
+    /// `filter`, as `snapshot` sees them. The filter is not checked
+    /// against the schema.
+    pub(super) fn open_as(db: &mut Database, info: &TableInfo, alias: &str, filter: Option<&Expr>, snapshot: &Arc<Snapshot>)
+        -> Result<Self, DbError> {
+        db.lock(LockTarget::Table(info.name.clone()), LockMode::Shared)?;
+        let snapshot = snapshot.clone();
+        let source = match plan::plan_as(info, alias, filter) {
+            Access::Scan => Source::Heap { cursor: db.heap(&info.name)?.cursor(), snapshot },
+            Access::Index { column, ranges } => {
+                let (root, column) = match column {
+                    None => (info.root_page, info.key_field.clone()),
+                    Some(column) => match info.index_on(&column) {
+                        Some(index) => (index.root_page, column),
+                        None => return Err(DbError::NoSuchColumn(column)),
+                    },
+                };
+                Source::Index { root, column, ranges: ranges.into_iter(), cursor: None, snapshot }
+            }
+        };
+        Ok(RowStream { info: Some(info.clone()), source, filter: filter.cloned(), items: Vec::new(), skip: 0, remaining: None })
+    }
+
+    pub(super) fn joined(source: JoinSource) -> Self {
+        Self::from_source(Source::Join(Box::new(source)))
+    }
+
+    /// The next result row, or None at the end.
+    pub fn next_row(&mut self, db: &mut Database) -> Result<Option<Row>, DbError> {
+        loop {
+            if self.remaining == Some(0) {
+                self.close(db)?;
+                return Ok(None);
+            }
+            let row = match self.fetch(db)? {
+                Some(row) => row,
+                None => {
+                    self.close(db)?;
+                    return Ok(None);
+                }
+            };
+            if !eval::matches(self.filter.as_ref(), &row)? {
+                continue;
+            }
+            if self.skip > 0 {
+                self.skip -= 1;
+                continue;
+            }
+            if let Some(n) = &mut self.remaining {
+                *n -= 1;
+            }
+            return project(&self.items, &row).map(Some);
+        }
+    }
+
+    /// Ends the stream early, freeing any scratch pages a sort still holds.
+    pub fn close(&mut self, db: &mut Database) -> Result<(), DbError> {
+        match core::mem::replace(&mut self.source, Source::Rows(Vec::new().into_iter())) {
+            Source::Sorted(rows) => rows.release(db.pager()),
+            Source::Join(mut join) => join.close(db),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drains the stream.
+    pub fn collect_rows(mut self, db: &mut Database) -> Result<Vec<Row>, DbError> {
+        let mut rows = Vec::new();
+        while let Some(row) = self.next_row(db)? {
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    // Next candidate row from the source, before filtering.
+    fn fetch(&mut self, db: &mut Database) -> Result<Option<Row>, DbError> {
+        match &mut self.source {
+            Source::Rows(rows) => Ok(rows.next()),
+            Source::Sorted(rows) => rows.next_row(db.pager()),
+            Source::Join(join) => join.next_row(db),
+            Source::Heap { cursor, snapshot } => loop {
+                let Some(info) = &self.info else { return Ok(None) };
+                let Some(record) = cursor.next_record(db.pager()) else { return Ok(None) };
+                let (_, record) = record?;
+                let heap = HeapFile::at(info.heap_first, info.heap_last);
+                if let Some(data) = mvcc::resolve(db.pager(), &heap, record, snapshot)? {
+                    return Ok(Some(Row::decode(&data, &info.schema)?));
+                }
+            },
+            Source::Index { root, column, ranges, cursor, snapshot } => loop {
+                if cursor.is_none() {
+                    match ranges.next() {
+                        Some(range) => *cursor = Some(Box::new(RangeCursor::new(*root, range))),
+                        None => return Ok(None),
+                    }
+                }
+                let entry = cursor.as_mut().and_then(|c| c.next_entry(db.pager()));
+                match entry {
+                    Some(entry) => {
+                        let (key, value) = entry?;
+                        let rid = database::record_id(&value)?;
+                        if let Some(info) = &self.info {
+                            if let Some(row) = db.fetch(info, rid, snapshot)? {
+                                if row.get(column).unwrap_or(&Value::Null) == &key {
+                                    return Ok(Some(row));
+                                }
+                            }
+                        }
+                    }
+                    None => *cursor = None,
+                }
+            },
+        }
+    }
+
+}
+
+// `row` projected onto `items`; no items keeps the row as it is.
+fn project(items: &[SelectItem], row: &Row) -> Result<Row, DbError> {
+    if items.is_empty() {
+        return Ok(row.clone());
+    }
+    let mut out = Row::new();
+    for item in items {
+        match item {
+            SelectItem::Wildcard => {
+                for (name, value) in row.fields() {
+                    out.push(name, value.clone());
+                }
+            }
+            SelectItem::Expr { expr, alias } => {
+                out.push(&output_name(expr, alias.as_deref()), eval(expr, row)?);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Name of a result column: its alias, the column name, or the
+/// expression's text.
+pub fn output_name(expr: &Expr, alias: Option<&str>) -> String {
+    match (alias, expr) {
+        (Some(alias), _) => String::from(alias),
+        (None, Expr::Column { name, .. }) => name.clone(),
+        (None, expr) => expr.to_string(),
+    }
+}
+
+// Rejects references to columns the table cannot have. Schemaless tables
+// accept any column name.
+fn check_expr(info: &TableInfo, expr: &Expr) -> Result<(), DbError> {
+    match expr {
+        Expr::Literal(_) | Expr::Aggregate { arg: None, .. } => Ok(()),
+        Expr::Column { table, name } => check_column(info, table.as_deref(), name),
+        Expr::Aggregate { arg: Some(expr), .. } => check_expr(info, expr),
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } => check_expr(info, expr),
+        Expr::Binary { left, right, .. } | Expr::Like { expr: left, pattern: right, .. } => {
+            check_expr(info, left)?;
+            check_expr(info, right)
+        }
+        Expr::In { expr, list, .. } => {
+            check_expr(info, expr)?;
+            list.iter().try_for_each(|e| check_expr(info, e))
+        }
+    }
+}
+
+fn check_column(info: &TableInfo, table: Option<&str>, name: &str) -> Result<(), DbError> {
+    if table.is_some_and(|t| t != info.name) {
+        return Err(DbError::NoSuchColumn(format!("{}.{}", table.unwrap_or(""), name)));
+    }
+    if info.schema.is_empty() || info.schema.iter().any(|c| c.name == name) {
+        Ok(())
+    } else {
+        Err(DbError::NoSuchColumn(String::from(name)))
+    }
+}
+
+fn has_column(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(_) | Expr::Aggregate { arg: None, .. } => false,
+        Expr::Column { .. } => true,
+        Expr::Aggregate { arg: Some(expr), .. } => has_column(expr),
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } => has_column(expr),
+        Expr::Binary { left, right, .. } | Expr::Like { expr: left, pattern: right, .. } => has_column(left) || has_column(right),
+        Expr::In { expr, list, .. } => has_column(expr) || list.iter().any(has_column),
+    }
+}
@@ -0,0 +1,428 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::hash::{Hash, Hasher};
+use crate::sawitdb::catalog::TableInfo;
+use crate::sawitdb::database::Database;
+use crate::sawitdb::error::DbError;
+use crate::sawitdb::mvcc::Snapshot;
+use crate::sawitdb::row::Row;
+use crate::sawitdb::types::Value;
+use super::aggregate;
+use super::ast::{BinaryOp, Expr, JoinKind, OrderItem, Select, SelectItem};
+use super::eval::{self, eval};
+use super::executor::RowStream;
+use super::order::output_ref;
+use super::plan::split_and;
+
+// Joins. Every table of the query is known by its alias (its name when it
+// has none), and joined rows carry fields qualified by it: `u.name`.
+// `resolve` qualifies each column reference of the query up front, so
+// evaluation never has to guess which table a bare name belongs to.
+//
+// Joins run left to right, each over the rows produced so far. For every
+// left row the matching rows of the joined table are found:
+// - by index nested loop when the ON clause equates the table's key or an
+//   indexed column with an expression over the earlier tables: the
+//   right rows are looked up through the index, one probe per left row;
+// - by hash join when it equates some other expression over the joined
+//   table: that table is read once into a hash table on the expression;
+// - otherwise by rescanning the joined table for every left row.
+// The whole ON clause is checked on each combined row either way. WHERE
+// conjuncts on the FROM table alone are applied while reading it.
+
+/// `select` with every column reference qualified by its table's alias,
+/// except ORDER BY references to result columns.
+pub fn resolve(db: &Database, select: &Select) -> Result<Select, DbError> {
+    let mut scopes = vec![Scope::new(db, &select.table, select.alias.as_deref())?];
+    let mut joins = Vec::with_capacity(select.joins.len());
+    for join in &select.joins {
+        let scope = Scope::new(db, &join.table, join.alias.as_deref())?;
+        if scopes.iter().any(|s| s.alias == scope.alias) {
+            return Err(DbError::Eval(format!("table name '{}' is used twice; give one of them an alias", scope.alias)));
+        }
+        scopes.push(scope);
+        // ON may refer to this table and the ones before it
+        let mut join = join.clone();
+        join.on = qualify_expr(&join.on, &scopes)?;
+        if aggregate::has_aggregate(&join.on) {
+            return Err(DbError::Eval(String::from("aggregates are not allowed in ON")));
+        }
+        joins.push(join);
+    }
+
+    let resolve = |e: &Expr| qualify_expr(e, &scopes);
+    let mut items = Vec::with_capacity(select.items.len());
+    for item in &select.items {
+        items.push(match item {
+            SelectItem::Wildcard => SelectItem::Wildcard,
+            SelectItem::Expr { expr, alias } => SelectItem::Expr { expr: resolve(expr)?, alias: alias.clone() },
+        });
+    }
+    let filter = select.filter.as_ref().map(resolve).transpose()?;
+    if filter.as_ref().is_some_and(aggregate::has_aggregate) {
+        return Err(DbError::Eval(String::from("aggregates are not allowed in WHERE")));
+    }
+    let mut order_by = Vec::with_capacity(select.order_by.len());
+    for item in &select.order_by {
+        let expr = match output_ref(&item.expr, &select.items) {
+            Some(_) => item.expr.clone(),
+            None => resolve(&item.expr)?,
+        };
+        order_by.push(OrderItem { expr, desc: item.desc });
+    }
+    Ok(Select {
+        items,
+        table: select.table.clone(),
+        alias: select.alias.clone(),
+        joins,
+        filter,
+        group_by: select.group_by.iter().map(resolve).collect::<Result<_, _>>()?,
+        having: select.having.as_ref().map(resolve).transpose()?,
+        order_by,
+        limit: select.limit,
+        offset: select.offset,
+    })
+}
+
+/// The joined rows of a resolved `select`, before WHERE.
+pub fn open(db: &mut Database, select: &Select) -> Result<RowStream, DbError> {
+    let base = db.table(&select.table)?.clone();
+    let base_alias = select.alias.clone().unwrap_or_else(|| base.name.clone());
+
+    // WHERE conjuncts that only read the FROM table can narrow its scan
+    let mut pushed = None;
+    if let Some(filter) = &select.filter {
+        let mut conjuncts = Vec::new();
+        split_and(filter, &mut conjuncts);
+        for conjunct in conjuncts {
+            if tables(conjunct).iter().all(|t| *t == base_alias) {
+                pushed = Some(match pushed {
+                    None => conjunct.clone(),
+                    Some(left) => and(left, conjunct.clone()),
+                });
+            }
+        }
+    }
+    // Every table of the query is read from the same snapshot
+    let snapshot = db.snapshot()?;
+    let mut stream = RowStream::open_as(db, &base, &base_alias, pushed.as_ref(), &snapshot)?;
+    let mut left_alias = Some(base_alias);
+    for join in &select.joins {
+        let info = db.table(&join.table)?.clone();
+        let alias = join.alias.clone().unwrap_or_else(|| info.name.clone());
+        let probe = Probe::choose(db, &info, &alias, &join.on, &snapshot)?;
+        let source = JoinSource {
+            left: Box::new(stream),
+            left_alias: left_alias.take(),
+            kind: join.kind,
+            info,
+            alias,
+            on: join.on.clone(),
+            probe,
+            snapshot: snapshot.clone(),
+            current: None,
+        };
+        stream = RowStream::joined(source);
+    }
+    Ok(stream)
+}
+
+// A table of the query under the name it is referred to by.
+struct Scope {
+    alias: String,
+    info: TableInfo,
+}
+
+impl Scope {
+    fn new(db: &Database, table: &str, alias: Option<&str>) -> Result<Self, DbError> {
+        let info = db.table(table)?.clone();
+        Ok(Scope { alias: String::from(alias.unwrap_or(table)), info })
+    }
+
+    // Schemaless tables may have any column.
+    fn has(&self, name: &str) -> bool {
+        self.info.schema.is_empty() || self.info.schema.iter().any(|c| c.name == name)
+    }
+}
+
+fn qualify_expr(expr: &Expr, scopes: &[Scope]) -> Result<Expr, DbError> {
+    let sub = |e: &Expr| qualify_expr(e, scopes).map(Box::new);
+    Ok(match expr {
+        Expr::Literal(_) | Expr::Aggregate { arg: None, .. } => expr.clone(),
+        Expr::Column { table, name } => Expr::Column { table: Some(owner(scopes, table.as_deref(), name)?), name: name.clone() },
+        Expr::Aggregate { func, arg: Some(arg) } => Expr::Aggregate { func: *func, arg: Some(sub(arg)?) },
+        Expr::Unary { op, expr } => Expr::Unary { op: *op, expr: sub(expr)? },
+        Expr::Binary { op, left, right } => Expr::Binary { op: *op, left: sub(left)?, right: sub(right)? },
+        Expr::In { expr, list, negated } => Expr::In {
+            expr: sub(expr)?,
+            list: list.iter().map(|e| qualify_expr(e, scopes)).collect::<Result<_, _>>()?,
+            negated: *negated,
+        },
+        Expr::Like { expr, pattern, negated } => Expr::Like { expr: sub(expr)?, pattern: sub(pattern)?, negated: *negated },
+        Expr::IsNull { expr, negated } => Expr::IsNull { expr: sub(expr)?, negated: *negated },
+    })
+}
+
+// Alias of the table a column reference belongs to. A bare name must
+// belong to exactly one table; tables declaring it win over schemaless
+// ones.
+fn owner(scopes: &[Scope], table: Option<&str>, name: &str) -> Result<String, DbError> {
+    if let Some(table) = table {
+        return match scopes.iter().find(|s| s.alias == table) {
+            Some(scope) if scope.has(name) => Ok(scope.alias.clone()),
+            _ => Err(DbError::NoSuchColumn(format!("{}.{}", table, name))),
+        };
+    }
+    let declared: Vec<&Scope> = scopes.iter().filter(|s| s.info.schema.iter().any(|c| c.name == name)).collect();
+    let candidates = if declared.is_empty() {
+        scopes.iter().filter(|s| s.info.schema.is_empty()).collect()
+    } else {
+        declared
+    };
+    match candidates.as_slice() {
+        [scope] => Ok(scope.alias.clone()),
+        [] => Err(DbError::NoSuchColumn(String::from(name))),
+        _ => Err(DbError::Eval(format!("column '{}' is ambiguous", name))),
+    }
+}
+
+// Aliases a resolved expression reads from.
+fn tables(expr: &Expr) -> Vec<&str> {
+    let mut out = Vec::new();
+    collect_tables(expr, &mut out);
+    out
+}
+
+fn collect_tables<'a>(expr: &'a Expr, out: &mut Vec<&'a str>) {
+    match expr {
+        Expr::Literal(_) | Expr::Aggregate { arg: None, .. } => {}
+        Expr::Column { table, .. } => out.extend(table.as_deref()),
+        Expr::Aggregate { arg: Some(e), .. } | Expr::Unary { expr: e, .. } | Expr::IsNull { expr: e, .. } => collect_tables(e, out),
+        Expr::Binary { left, right, .. } | Expr::Like { expr: left, pattern: right, .. } => {
+            collect_tables(left, out);
+            collect_tables(right, out);
+        }
+        Expr::In { expr, list, .. } => {
+            collect_tables(expr, out);
+            for e in list {
+                collect_tables(e, out);
+            }
+        }
+    }
+}
+
+fn and(left: Expr, right: Expr) -> Expr {
+    Expr::Binary { op: BinaryOp::And, left: Box::new(left), right: Box::new(right) }
+}
+
+// `row` with its fields renamed to `alias.name`.
+fn qualify(row: &Row, alias: &str) -> Row {
+    let mut out = Row::new();
+    for (name, value) in row.fields() {
+        out.push(&format!("{}.{}", alias, name), value.clone());
+    }
+    out
+}
+
+/// How the rows of a joined table matching one left row are found.
+enum Probe {
+    /// Index (or key) lookup of `column` = `key`, evaluated on the left row.
+    Lookup { column: String, key: Expr },
+    /// Rows bucketed by their join key; `key` is evaluated on the left row.
+    Hash { table: HashTable, key: Expr },
+    /// Every row of the table.
+    Scan,
+}
+
+impl Probe {
+    fn choose(db: &mut Database, info: &TableInfo, alias: &str, on: &Expr, snapshot: &Arc<Snapshot>) -> Result<Self, DbError> {
+        let mut conjuncts = Vec::new();
+        split_and(on, &mut conjuncts);
+        // `right = left` pairs: `right` reads only the joined table,
+        // `left` only earlier ones
+        let mut keys = Vec::new();
+        for conjunct in conjuncts {
+            if let Expr::Binary { op: BinaryOp::Eq, left, right } = conjunct {
+                for (r, l) in [(left, right), (right, left)] {
+                    let r_tables = tables(r);
+                    if !r_tables.is_empty() && r_tables.iter().all(|t| *t == alias) && !tables(l).contains(&alias) {
+                        keys.push((&**r, &**l));
+                    }
+                }
+            }
+        }
+
+        let indexed = keys.iter().find(|(r, _)| {
+            matches!(r, Expr::Column { name, .. } if *name == info.key_field || info.index_on(name).is_some())
+        });
+        if let Some((Expr::Column { name, .. }, key)) = indexed {
+            return Ok(Probe::Lookup { column: name.clone(), key: (*key).clone() });
+        }
+        let Some((right_key, key)) = keys.first() else {
+            return Ok(Probe::Scan);
+        };
+        let mut entries = Vec::new();
+        let mut rows = RowStream::open_as(db, info, alias, None, snapshot)?;
+        while let Some(row) = rows.next_row(db)? {
+            let row = qualify(&row, alias);
+            let k = eval(right_key, &row)?;
+            // NULL never equals anything
+            if !k.is_null() {
+                entries.push((k, row));
+            }
+        }
+        Ok(Probe::Hash { table: HashTable::build(entries), key: (*key).clone() })
+    }
+}
+
+/// One join step: the rows of `left` combined with their matches in a
+/// joined table.
+pub struct JoinSource {
+    left: Box<RowStream>,
+    // Set when the left rows come straight from the FROM table and still
+    // need qualifying
+    left_alias: Option<String>,
+    kind: JoinKind,
+    info: TableInfo,
+    alias: String,
+    on: Expr,
+    probe: Probe,
+    snapshot: Arc<Snapshot>,
+    // The left row being joined, its candidate matches and whether any of
+    // them passed ON yet
+    current: Option<(Row, Candidates, bool)>,
+}
+
+impl JoinSource {
+    pub fn next_row(&mut self, db: &mut Database) -> Result<Option<Row>, DbError> {
+        loop {
+            let Some((left, candidates, matched)) = &mut self.current else {
+                let Some(mut row) = self.left.next_row(db)? else {
+                    return Ok(None);
+                };
+                if let Some(alias) = &self.left_alias {
+                    row = qualify(&row, alias);
+                }
+                let candidates = self.candidates(db, &row)?;
+                self.current = Some((row, candidates, false));
+                continue;
+            };
+            match candidates.next_row(db, &self.alias)? {
+                Some(right) => {
+                    let mut row = left.clone();
+                    for (name, value) in right.fields() {
+                        row.push(name, value.clone());
+                    }
+                    if eval::matches(Some(&self.on), &row)? {
+                        *matched = true;
+                        return Ok(Some(row));
+                    }
+                }
+                None => {
+                    let unmatched = !*matched && self.kind == JoinKind::Left;
+                    let Some((mut row, _, _)) = self.current.take() else { continue };
+                    if unmatched {
+                        for column in &self.info.schema {
+                            row.push(&format!("{}.{}", self.alias, column.name), Value::Null);
+                        }
+                        return Ok(Some(row));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn close(&mut self, db: &mut Database) -> Result<(), DbError> {
+        if let Some((_, Candidates::Stream(stream), _)) = &mut self.current {
+            stream.close(db)?;
+        }
+        self.current = None;
+        self.left.close(db)
+    }
+
+    fn candidates(&self, db: &mut Database, left: &Row) -> Result<Candidates, DbError> {
+        match &self.probe {
+            Probe::Lookup { column, key } => {
+                let key = eval(key, left)?;
+                if key.is_null() {
+                    return Ok(Candidates::Rows(Vec::new().into_iter()));
+                }
+                let filter = Expr::Binary {
+                    op: BinaryOp::Eq,
+                    left: Box::new(Expr::Column { table: None, name: column.clone() }),
+                    right: Box::new(Expr::Literal(key)),
+                };
+                Ok(Candidates::Stream(Box::new(RowStream::open_as(db, &self.info, &self.info.name, Some(&filter), &self.snapshot)?)))
+            }
+            Probe::Hash { table, key } => {
+                let key = eval(key, left)?;
+                Ok(Candidates::Rows(table.get(&key).cloned().collect::<Vec<_>>().into_iter()))
+            }
+            Probe::Scan => Ok(Candidates::Stream(Box::new(RowStream::open_as(db, &self.info, &self.alias, None, &self.snapshot)?))),
+        }
+    }
+}
+
+// Right rows for one left row: read from the table (unqualified) or taken
+// from the hash table (already qualified).
+enum Candidates {
+    Stream(Box<RowStream>),
+    Rows(vec::IntoIter<Row>),
+}
+
+impl Candidates {
+    fn next_row(&mut self, db: &mut Database, alias: &str) -> Result<Option<Row>, DbError> {
+        match self {
+            Candidates::Stream(stream) => Ok(stream.next_row(db)?.map(|row| qualify(&row, alias))),
+            Candidates::Rows(rows) => Ok(rows.next()),
+        }
+    }
+}
+
+// Chained hash table from join key to rows. `Value`'s hash agrees with its
+// equality, so `1` finds rows keyed `1.0`.
+struct HashTable {
+    buckets: Vec<Vec<(Value, Row)>>,
+}
+
+impl HashTable {
+    fn build(entries: Vec<(Value, Row)>) -> Self {
+        let mut buckets: Vec<Vec<(Value, Row)>> = (0..entries.len().next_power_of_two()).map(|_| Vec::new()).collect();
+        let mask = buckets.len() - 1;
+        for (key, row) in entries {
+            buckets[hash(&key) as usize & mask].push((key, row));
+        }
+        HashTable { buckets }
+    }
+
+    fn get<'a>(&'a self, key: &'a Value) -> impl Iterator<Item = &'a Row> + 'a {
+        let bucket = &self.buckets[hash(key) as usize & (self.buckets.len() - 1)];
+        bucket.iter().filter(move |(k, _)| !key.is_null() && k == key).map(|(_, row)| row)
+    }
+}
+
+fn hash(value: &Value) -> u64 {
+    let mut hasher = Fnv1a(0xcbf2_9ce4_8422_2325);
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// FNV-1a, 64-bit.
+struct Fnv1a(u64);
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,182 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::format;
+use super::ParseError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Ident(String),  // Bare word; keywords are matched on these case-insensitively
+    Quoted(String), // "double quoted" identifier
+    Str(String),    // 'single quoted' string literal
+    Int(i64),
+    Float(f64),
+    LParen,
+    RParen,
+    Comma,
+    Dot,
+    Semicolon,
+    Star,
+    Plus,
+    Minus,
+    Slash,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eof,
+}
+
+/// A token and the byte offset where it starts in the query text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub pos: usize,
+}
+
+impl Token {
+    /// The token as it should appear in an error message.
+    pub fn describe(&self) -> String {
+        match &self.kind {
+            TokenKind::Ident(s) => s.clone(),
+            TokenKind::Quoted(s) => format!("\"{}\"", s),
+            TokenKind::Str(s) => format!("'{}'", s),
+            TokenKind::Int(v) => format!("{}", v),
+            TokenKind::Float(v) => format!("{}", v),
+            TokenKind::Eof => String::from("end of input"),
+            kind => String::from(symbol(kind)),
+        }
+    }
+}
+
+fn symbol(kind: &TokenKind) -> &'static str {
+    match kind {
+        TokenKind::LParen => "(",
+        TokenKind::RParen => ")",
+        TokenKind::Comma => ",",
+        TokenKind::Dot => ".",
+        TokenKind::Semicolon => ";",
+        TokenKind::Star => "*",
+        TokenKind::Plus => "+",
+        TokenKind::Minus => "-",
+        TokenKind::Slash => "/",
+        TokenKind::Eq => "=",
+        TokenKind::Ne => "!=",
+        TokenKind::Lt => "<",
+        TokenKind::Le => "<=",
+        TokenKind::Gt => ">",
+        TokenKind::Ge => ">=",
+        _ => "?",
+    }
+}
+
+/// Splits a query into tokens, ending with an `Eof` token. `--` starts a
+/// comment that runs to the end of the line.
+pub fn tokenize(src: &str) -> Result<Vec<Token>, ParseError> {
+    let bytes = src.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == b'-' && bytes.get(i + 1) == Some(&b'-') {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+
+        let kind = if c.is_ascii_alphabetic() || c == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            TokenKind::Ident(String::from(&src[start..i]))
+        } else if c.is_ascii_digit() {
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            let mut is_float = false;
+            if i + 1 < bytes.len() && bytes[i] == b'.' && bytes[i + 1].is_ascii_digit() {
+                is_float = true;
+                i += 1;
+                while i < bytes.len() && bytes[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                let mut j = i + 1;
+                if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+                    j += 1;
+                }
+                if j < bytes.len() && bytes[j].is_ascii_digit() {
+                    is_float = true;
+                    i = j;
+                    while i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text = &src[start..i];
+            if is_float {
+                TokenKind::Float(text.parse().map_err(|_| ParseError::new("bad number", start, text))?)
+            } else {
+                TokenKind::Int(text.parse().map_err(|_| ParseError::new("integer out of range", start, text))?)
+            }
+        } else if c == b'\'' || c == b'"' {
+            // Quotes are escaped by doubling them: 'it''s'
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match src[i..].find(c as char) {
+                    Some(end) => {
+                        text.push_str(&src[i..i + end]);
+                        i += end + 1;
+                        if bytes.get(i) == Some(&c) {
+                            text.push(c as char);
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    None => return Err(ParseError::new("unterminated quote", start, &src[start..])),
+                }
+            }
+            if c == b'\'' { TokenKind::Str(text) } else { TokenKind::Quoted(text) }
+        } else {
+            let two = bytes.get(i + 1).copied();
+            let (kind, len) = match (c, two) {
+                (b'!', Some(b'=')) | (b'<', Some(b'>')) => (TokenKind::Ne, 2),
+                (b'<', Some(b'=')) => (TokenKind::Le, 2),
+                (b'>', Some(b'=')) => (TokenKind::Ge, 2),
+                (b'=', Some(b'=')) => (TokenKind::Eq, 2),
+                (b'(', _) => (TokenKind::LParen, 1),
+                (b')', _) => (TokenKind::RParen, 1),
+                (b',', _) => (TokenKind::Comma, 1),
+                (b'.', _) => (TokenKind::Dot, 1),
+                (b';', _) => (TokenKind::Semicolon, 1),
+                (b'*', _) => (TokenKind::Star, 1),
+                (b'+', _) => (TokenKind::Plus, 1),
+                (b'-', _) => (TokenKind::Minus, 1),
+                (b'/', _) => (TokenKind::Slash, 1),
+                (b'=', _) => (TokenKind::Eq, 1),
+                (b'<', _) => (TokenKind::Lt, 1),
+                (b'>', _) => (TokenKind::Gt, 1),
+                _ => {
+                    let ch = src[start..].chars().next().unwrap();
+                    return Err(ParseError::new("unexpected character", start, &src[start..start + ch.len_utf8()]));
+                }
+            };
+            i += len;
+            kind
+        };
+        tokens.push(Token { kind, pos: start });
+    }
+    tokens.push(Token { kind: TokenKind::Eof, pos: src.len() });
+    Ok(tokens)
+}
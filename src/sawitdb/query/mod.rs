@@ -0,0 +1,71 @@
+// SawitDB query language. Both plain SQL and the SawitDB (AQL) keywords
+// used by the Go and Node.js engines are accepted:
+//
+// | SQL                          | SawitDB                         |
+// |------------------------------|---------------------------------|
+// | `CREATE TABLE t`             | `LAHAN t`                       |
+// | `DROP TABLE t`               | `BAKAR LAHAN t`                 |
+// | `SHOW TABLES`                | `LIHAT LAHAN`                   |
+// | `CREATE INDEX ON t (c)`      | `INDEKS t PADA c`               |
+// | `INSERT INTO t (..) VALUES`  | `TANAM KE t (..) BIBIT`         |
+// | `SELECT .. FROM t WHERE`     | `PANEN .. DARI t DIMANA`        |
+// | `UPDATE t SET .. WHERE`      | `PUPUK t DENGAN .. DIMANA`      |
+// | `DELETE FROM t WHERE`        | `GUSUR DARI t DIMANA`           |
+// | `BEGIN`                      | `MULAI`                         |
+// | `COMMIT`                     | `SIMPAN`                        |
+// | `ROLLBACK`                   | `BATAL`                         |
+//
+// Aggregates (`COUNT`, `SUM`, `AVG`, `MIN`, `MAX`), `GROUP BY`, `HAVING`,
+// `ORDER BY`, `LIMIT`/`OFFSET` and `[INNER | LEFT] JOIN ... ON` are
+// spelled the same way in both.
+
+use alloc::string::String;
+use alloc::fmt;
+
+pub mod aggregate;
+pub mod ast;
+pub mod eval;
+pub mod executor;
+pub mod join;
+pub mod lexer;
+pub mod order;
+pub mod parser;
+pub mod plan;
+
+pub use executor::{execute, run, Output, RowStream};
+pub use parser::{parse, parse_script};
+
+/// Syntax error with the byte offset of the offending token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub pos: usize,
+    pub near: String,
+}
+
+impl ParseError {
+    pub fn new(message: &str, pos: usize, near: &str) -> Self {
+        ParseError { message: String::from(message), pos, near: String::from(near) }
+    }
+
+    /// The line of `src` containing the error with a `^` under the
+    /// offending token, for printing below the message.
+    pub fn caret(&self, src: &str) -> String {
+        let pos = self.pos.min(src.len());
+        let line_start = src[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = src[pos..].find('\n').map(|i| pos + i).unwrap_or(src.len());
+        let mut out = String::from(&src[line_start..line_end]);
+        out.push('\n');
+        for _ in src[line_start..pos].chars() {
+            out.push(' ');
+        }
+        out.push('^');
+        out
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {} (near {})", self.message, self.pos, self.near)
+    }
+}
@@ -0,0 +1,157 @@
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use crate::sawitdb::error::DbError;
+use crate::sawitdb::pager::Pager;
+use crate::sawitdb::row::Row;
+use crate::sawitdb::sort::{ExternalSorter, SortedRecords};
+use crate::sawitdb::types::Value;
+use super::ast::{Expr, SelectItem};
+use super::executor::output_name;
+
+// ORDER BY with LIMIT/OFFSET. When the rows that can reach the output
+// (OFFSET + LIMIT) are few, a bounded heap keeps only the best of them;
+// otherwise rows go through the external sorter, which spills to scratch
+// pages instead of holding the whole result on the kernel heap.
+
+/// Largest OFFSET + LIMIT handled with the in-memory top-N heap.
+pub const TOP_N_MAX: usize = 128;
+
+/// If `expr` names a result column (`ORDER BY alias`), that column's name.
+/// Such keys are read from the output row rather than the table row.
+pub fn output_ref<'a>(expr: &'a Expr, items: &[SelectItem]) -> Option<&'a str> {
+    match expr {
+        Expr::Column { table: None, name } => items.iter().any(|item| {
+            matches!(item, SelectItem::Expr { expr, alias } if output_name(expr, alias.as_deref()) == *name)
+        }).then_some(name.as_str()),
+        _ => None,
+    }
+}
+
+/// Sorted output, read with `next_row`.
+pub enum SortedRows {
+    Memory(alloc::vec::IntoIter<Row>),
+    Spilled { records: SortedRecords, keys: usize },
+}
+
+impl SortedRows {
+    pub fn next_row(&mut self, pager: &mut Pager) -> Result<Option<Row>, DbError> {
+        match self {
+            SortedRows::Memory(rows) => Ok(rows.next()),
+            SortedRows::Spilled { records, keys } => match records.next_record(pager) {
+                Some(record) => Ok(Some(decode_row(record?, *keys)?)),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Frees any scratch pages still held.
+    pub fn release(self, pager: &mut Pager) -> Result<(), DbError> {
+        match self {
+            SortedRows::Memory(_) => Ok(()),
+            SortedRows::Spilled { records, .. } => records.release(pager),
+        }
+    }
+}
+
+/// Collects (sort key, row) pairs and returns the rows in key order.
+pub enum Sorter {
+    TopN { heap: BinaryHeap<TopEntry>, keep: usize, seq: u64, desc: Vec<bool> },
+    External { sorter: ExternalSorter, keys: usize },
+}
+
+impl Sorter {
+    /// `keep` is OFFSET + LIMIT when there is a LIMIT.
+    pub fn new(desc: Vec<bool>, keep: Option<usize>) -> Self {
+        match keep {
+            Some(keep) if keep <= TOP_N_MAX => Sorter::TopN { heap: BinaryHeap::new(), keep, seq: 0, desc },
+            _ => Sorter::External { keys: desc.len(), sorter: ExternalSorter::new(desc) },
+        }
+    }
+
+    pub fn push(&mut self, pager: &mut Pager, keys: Vec<Value>, row: Row) -> Result<(), DbError> {
+        match self {
+            Sorter::TopN { heap, keep, seq, desc } => {
+                let key = keys.into_iter().zip(desc.iter()).map(|(v, &desc)| SortKey { value: v, desc }).collect();
+                heap.push(TopEntry { key, seq: *seq, row });
+                *seq += 1;
+                // The heap's maximum is the worst row kept
+                if heap.len() > *keep {
+                    heap.pop();
+                }
+                Ok(())
+            }
+            Sorter::External { sorter, .. } => {
+                let mut record = keys;
+                for (name, value) in row.fields() {
+                    record.push(Value::String(name.clone()));
+                    record.push(value.clone());
+                }
+                sorter.push(pager, record)
+            }
+        }
+    }
+
+    pub fn finish(self, pager: &mut Pager) -> Result<SortedRows, DbError> {
+        match self {
+            Sorter::TopN { heap, .. } => {
+                let rows: Vec<Row> = heap.into_sorted_vec().into_iter().map(|e| e.row).collect();
+                Ok(SortedRows::Memory(rows.into_iter()))
+            }
+            Sorter::External { sorter, keys } => Ok(SortedRows::Spilled { records: sorter.finish(pager)?, keys }),
+        }
+    }
+}
+
+// Spilled records are the sort keys followed by name/value pairs.
+fn decode_row(record: Vec<Value>, keys: usize) -> Result<Row, DbError> {
+    let mut row = Row::new();
+    let mut fields = record.into_iter().skip(keys);
+    while let Some(name) = fields.next() {
+        match (name, fields.next()) {
+            (Value::String(name), Some(value)) => row.push(&name, value),
+            _ => return Err(DbError::Decode("bad row in sort run")),
+        }
+    }
+    Ok(row)
+}
+
+// One ORDER BY value with its direction.
+pub struct SortKey {
+    value: Value,
+    desc: bool,
+}
+
+/// Heap entry ordered by key, then by arrival so equal keys keep their
+/// input order.
+pub struct TopEntry {
+    key: Vec<SortKey>,
+    seq: u64,
+    row: Row,
+}
+
+impl Ord for TopEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for (a, b) in self.key.iter().zip(&other.key) {
+            let ord = a.value.cmp(&b.value);
+            if ord != Ordering::Equal {
+                return if a.desc { ord.reverse() } else { ord };
+            }
+        }
+        self.seq.cmp(&other.seq)
+    }
+}
+
+impl PartialOrd for TopEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for TopEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TopEntry {}
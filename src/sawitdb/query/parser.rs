@@ -0,0 +1,656 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::sawitdb::catalog::Column;
+use crate::sawitdb::types::{self, Value, ValueType};
+use super::ast::{AggFunc, BinaryOp, Expr, Join, JoinKind, OrderItem, Select, SelectItem, Statement, UnaryOp};
+use super::lexer::{tokenize, Token, TokenKind};
+use super::ParseError;
+
+/// Parses a single statement (an optional trailing `;` is allowed).
+pub fn parse(src: &str) -> Result<Statement, ParseError> {
+    let mut parser = Parser { tokens: tokenize(src)?, idx: 0, depth: 0 };
+    let stmt = parser.statement()?;
+    parser.eat(&TokenKind::Semicolon);
+    parser.expect_eof()?;
+    Ok(stmt)
+}
+
+/// Parses `;`-separated statements. Empty statements are skipped.
+pub fn parse_script(src: &str) -> Result<Vec<Statement>, ParseError> {
+    let mut parser = Parser { tokens: tokenize(src)?, idx: 0, depth: 0 };
+    let mut stmts = Vec::new();
+    loop {
+        while parser.eat(&TokenKind::Semicolon) {}
+        if parser.peek().kind == TokenKind::Eof {
+            return Ok(stmts);
+        }
+        stmts.push(parser.statement()?);
+        if !parser.eat(&TokenKind::Semicolon) {
+            parser.expect_eof()?;
+        }
+    }
+}
+
+// SQL words that cannot be used as bare identifiers. The SawitDB keywords
+// stay usable as names (a plantation table may well have a `bibit` or
+// `lahan` column); they are only recognised where a keyword is expected.
+const RESERVED: &[&str] = &[
+    "SELECT", "FROM", "WHERE", "INSERT", "INTO", "VALUES", "UPDATE", "SET", "DELETE", "CREATE",
+    "DROP", "TABLE", "INDEX", "ON", "SHOW", "AND", "OR", "NOT", "IN", "LIKE", "IS", "NULL",
+    "TRUE", "FALSE", "AS", "GROUP", "BY", "HAVING", "ORDER", "LIMIT", "OFFSET",
+    "JOIN", "INNER", "LEFT", "OUTER",
+];
+
+// SawitDB keywords that may directly follow a table name, so they are not
+// taken as its alias.
+const AFTER_TABLE: &[&str] = &["DIMANA"];
+
+// Deepest expression accepted, counting nested parentheses, NOT and
+// unary minus, and each operator in a chain such as `a + b + c`. Parsing
+// and every later walk over an expression recurse once per level.
+const MAX_DEPTH: usize = 64;
+
+struct Parser {
+    tokens: Vec<Token>,
+    idx: usize,
+    // Expression levels open at the current token
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.idx]
+    }
+
+    fn peek_at(&self, ahead: usize) -> &Token {
+        &self.tokens[(self.idx + ahead).min(self.tokens.len() - 1)]
+    }
+
+    fn advance(&mut self) -> Token {
+        let tok = self.tokens[self.idx].clone();
+        if tok.kind != TokenKind::Eof {
+            self.idx += 1;
+        }
+        tok
+    }
+
+    fn error(&self, message: String) -> ParseError {
+        let tok = self.peek();
+        ParseError::new(&message, tok.pos, &tok.describe())
+    }
+
+    fn expected(&self, what: &str) -> ParseError {
+        self.error(format!("expected {}", what))
+    }
+
+    // Opens one more expression level; the caller resets `depth` when done.
+    fn nest(&mut self) -> Result<(), ParseError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(String::from("expression nested too deeply")));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if &self.peek().kind == kind {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, kind: &TokenKind, what: &str) -> Result<(), ParseError> {
+        if self.eat(kind) {
+            Ok(())
+        } else {
+            Err(self.expected(what))
+        }
+    }
+
+    fn expect_eof(&self) -> Result<(), ParseError> {
+        if self.peek().kind == TokenKind::Eof {
+            Ok(())
+        } else {
+            Err(self.expected("end of statement"))
+        }
+    }
+
+    fn is_keyword(&self, ahead: usize, word: &str) -> bool {
+        matches!(&self.peek_at(ahead).kind, TokenKind::Ident(s) if s.eq_ignore_ascii_case(word))
+    }
+
+    /// Consumes the keyword if it is next (any of the given spellings).
+    fn eat_keyword(&mut self, words: &[&str]) -> bool {
+        if words.iter().any(|w| self.is_keyword(0, w)) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    fn expect_keyword(&mut self, words: &[&str]) -> Result<(), ParseError> {
+        if self.eat_keyword(words) {
+            Ok(())
+        } else {
+            Err(self.expected(&words.join(" or ")))
+        }
+    }
+
+    /// A table or column name: a non-reserved word or a "quoted" name.
+    fn ident(&mut self, what: &str) -> Result<String, ParseError> {
+        match &self.peek().kind {
+            TokenKind::Ident(s) if !RESERVED.iter().any(|r| s.eq_ignore_ascii_case(r)) => {
+                let s = s.clone();
+                self.advance();
+                Ok(s)
+            }
+            TokenKind::Quoted(s) => {
+                let s = s.clone();
+                self.advance();
+                Ok(s)
+            }
+            _ => Err(self.expected(what)),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, ParseError> {
+        if self.eat_keyword(&["SELECT", "PANEN"]) {
+            return self.select();
+        }
+        if self.eat_keyword(&["INSERT"]) {
+            self.expect_keyword(&["INTO"])?;
+            return self.insert();
+        }
+        if self.eat_keyword(&["TANAM"]) {
+            self.expect_keyword(&["KE"])?;
+            return self.insert();
+        }
+        if self.eat_keyword(&["UPDATE", "PUPUK"]) {
+            return self.update();
+        }
+        if self.eat_keyword(&["DELETE", "GUSUR"]) {
+            self.expect_keyword(&["FROM", "DARI"])?;
+            let table = self.ident("table name")?;
+            let filter = self.filter()?;
+            return Ok(Statement::Delete { table, filter });
+        }
+        if self.eat_keyword(&["CREATE"]) {
+            if self.eat_keyword(&["TABLE"]) {
+                return self.create_table();
+            }
+            if self.eat_keyword(&["INDEX"]) {
+                // Index name is optional and not used
+                if !self.is_keyword(0, "ON") {
+                    self.ident("index name or ON")?;
+                }
+                self.expect_keyword(&["ON"])?;
+                let table = self.ident("table name")?;
+                self.expect(&TokenKind::LParen, "(")?;
+                let column = self.ident("column name")?;
+                self.expect(&TokenKind::RParen, ")")?;
+                return Ok(Statement::CreateIndex { table, column });
+            }
+            return Err(self.expected("TABLE or INDEX"));
+        }
+        if self.eat_keyword(&["LAHAN"]) {
+            return self.create_table();
+        }
+        if self.eat_keyword(&["INDEKS"]) {
+            let table = self.ident("table name")?;
+            self.expect_keyword(&["PADA"])?;
+            let column = self.ident("column name")?;
+            return Ok(Statement::CreateIndex { table, column });
+        }
+        if self.eat_keyword(&["DROP"]) {
+            self.expect_keyword(&["TABLE"])?;
+            return Ok(Statement::DropTable { name: self.ident("table name")? });
+        }
+        if self.eat_keyword(&["BAKAR"]) {
+            self.expect_keyword(&["LAHAN"])?;
+            return Ok(Statement::DropTable { name: self.ident("table name")? });
+        }
+        if self.eat_keyword(&["SHOW"]) {
+            self.expect_keyword(&["TABLES"])?;
+            return Ok(Statement::ShowTables);
+        }
+        if self.eat_keyword(&["LIHAT"]) {
+            self.expect_keyword(&["LAHAN"])?;
+            return Ok(Statement::ShowTables);
+        }
+        if self.eat_keyword(&["BEGIN"]) {
+            self.eat_keyword(&["TRANSACTION"]);
+            return Ok(Statement::Begin);
+        }
+        if self.eat_keyword(&["MULAI"]) {
+            return Ok(Statement::Begin);
+        }
+        if self.eat_keyword(&["COMMIT", "SIMPAN"]) {
+            return Ok(Statement::Commit);
+        }
+        if self.eat_keyword(&["ROLLBACK", "BATAL"]) {
+            return Ok(Statement::Rollback);
+        }
+        Err(self.expected("a statement"))
+    }
+
+    fn select(&mut self) -> Result<Statement, ParseError> {
+        let mut items = Vec::new();
+        loop {
+            if self.eat(&TokenKind::Star) {
+                items.push(SelectItem::Wildcard);
+            } else {
+                let expr = self.expr()?;
+                let alias = if self.eat_keyword(&["AS"]) { Some(self.ident("alias")?) } else { None };
+                items.push(SelectItem::Expr { expr, alias });
+            }
+            if !self.eat(&TokenKind::Comma) {
+                break;
+            }
+        }
+        self.expect_keyword(&["FROM", "DARI"])?;
+        let table = self.ident("table name")?;
+        let alias = self.table_alias()?;
+        let mut joins = Vec::new();
+        loop {
+            let kind = if self.eat_keyword(&["JOIN"]) {
+                JoinKind::Inner
+            } else if self.eat_keyword(&["INNER"]) {
+                self.expect_keyword(&["JOIN"])?;
+                JoinKind::Inner
+            } else if self.eat_keyword(&["LEFT"]) {
+                self.eat_keyword(&["OUTER"]);
+                self.expect_keyword(&["JOIN"])?;
+                JoinKind::Left
+            } else {
+                break;
+            };
+            let table = self.ident("table name")?;
+            let alias = self.table_alias()?;
+            self.expect_keyword(&["ON"])?;
+            joins.push(Join { kind, table, alias, on: self.expr()? });
+        }
+        let filter = self.filter()?;
+        let mut group_by = Vec::new();
+        if self.eat_keyword(&["GROUP"]) {
+            self.expect_keyword(&["BY"])?;
+            loop {
+                group_by.push(self.expr()?);
+                if !self.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+        let having = if self.eat_keyword(&["HAVING"]) { Some(self.expr()?) } else { None };
+        let mut order_by = Vec::new();
+        if self.eat_keyword(&["ORDER"]) {
+            self.expect_keyword(&["BY"])?;
+            loop {
+                let expr = self.expr()?;
+                let desc = self.eat_keyword(&["DESC"]);
+                if !desc {
+                    self.eat_keyword(&["ASC"]);
+                }
+                order_by.push(OrderItem { expr, desc });
+                if !self.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+        let limit = if self.eat_keyword(&["LIMIT"]) { Some(self.count("row count after LIMIT")?) } else { None };
+        let offset = if self.eat_keyword(&["OFFSET"]) { Some(self.count("row count after OFFSET")?) } else { None };
+        Ok(Statement::Select(Select { items, table, alias, joins, filter, group_by, having, order_by, limit, offset }))
+    }
+
+    // `[AS] alias` after a table name in FROM / JOIN.
+    fn table_alias(&mut self) -> Result<Option<String>, ParseError> {
+        if self.eat_keyword(&["AS"]) {
+            return Ok(Some(self.ident("alias")?));
+        }
+        let is_alias = match &self.peek().kind {
+            TokenKind::Ident(s) => !RESERVED.iter().chain(AFTER_TABLE).any(|r| s.eq_ignore_ascii_case(r)),
+            TokenKind::Quoted(_) => true,
+            _ => false,
+        };
+        if is_alias { Ok(Some(self.ident("alias")?)) } else { Ok(None) }
+    }
+
+    // A non-negative integer literal.
+    fn count(&mut self, what: &str) -> Result<usize, ParseError> {
+        match self.peek().kind {
+            TokenKind::Int(n) if n >= 0 => {
+                self.advance();
+                Ok(n as usize)
+            }
+            _ => Err(self.expected(what)),
+        }
+    }
+
+    fn filter(&mut self) -> Result<Option<Expr>, ParseError> {
+        if self.eat_keyword(&["WHERE", "DIMANA"]) {
+            return Ok(Some(self.expr()?));
+        }
+        Ok(None)
+    }
+
+    fn insert(&mut self) -> Result<Statement, ParseError> {
+        let table = self.ident("table name")?;
+        let mut columns = Vec::new();
+        if self.eat(&TokenKind::LParen) {
+            columns = self.ident_list("column name")?;
+            self.expect(&TokenKind::RParen, ")")?;
+        }
+        self.expect_keyword(&["VALUES", "BIBIT"])?;
+        let mut rows = Vec::new();
+        loop {
+            self.expect(&TokenKind::LParen, "(")?;
+            let mut row = Vec::new();
+            loop {
+                row.push(self.expr()?);
+                if !self.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+            self.expect(&TokenKind::RParen, ")")?;
+            rows.push(row);
+            if !self.eat(&TokenKind::Comma) {
+                break;
+            }
+        }
+        Ok(Statement::Insert { table, columns, rows })
+    }
+
+    fn update(&mut self) -> Result<Statement, ParseError> {
+        let table = self.ident("table name")?;
+        self.expect_keyword(&["SET", "DENGAN"])?;
+        let mut assignments = Vec::new();
+        loop {
+            let column = self.ident("column name")?;
+            self.expect(&TokenKind::Eq, "=")?;
+            assignments.push((column, self.expr()?));
+            if !self.eat(&TokenKind::Comma) {
+                break;
+            }
+        }
+        let filter = self.filter()?;
+        Ok(Statement::Update { table, assignments, filter })
+    }
+
+    /// `name [( coldef, ... )]` where coldef is `name TYPE [PRIMARY KEY]`
+    /// or a table-level `PRIMARY KEY (name)`.
+    fn create_table(&mut self) -> Result<Statement, ParseError> {
+        let name = self.ident("table name")?;
+        let mut columns = Vec::new();
+        let mut primary_key = None;
+        if self.eat(&TokenKind::LParen) {
+            loop {
+                if self.eat_keyword(&["PRIMARY"]) {
+                    self.expect_keyword(&["KEY"])?;
+                    self.expect(&TokenKind::LParen, "(")?;
+                    primary_key = Some(self.ident("column name")?);
+                    self.expect(&TokenKind::RParen, ")")?;
+                } else {
+                    let col = self.ident("column name")?;
+                    let ty = match &self.peek().kind {
+                        TokenKind::Ident(t) => ValueType::parse(t),
+                        _ => None,
+                    }.ok_or_else(|| self.expected("column type"))?;
+                    self.advance();
+                    if self.eat_keyword(&["PRIMARY"]) {
+                        self.expect_keyword(&["KEY"])?;
+                        primary_key = Some(col.clone());
+                    }
+                    columns.push(Column { name: col, ty });
+                }
+                if !self.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+            self.expect(&TokenKind::RParen, ")")?;
+        }
+        Ok(Statement::CreateTable { name, columns, primary_key })
+    }
+
+    fn ident_list(&mut self, what: &str) -> Result<Vec<String>, ParseError> {
+        let mut names = Vec::new();
+        loop {
+            names.push(self.ident(what)?);
+            if !self.eat(&TokenKind::Comma) {
+                return Ok(names);
+            }
+        }
+    }
+
+    // Precedence, loosest first: OR, AND, NOT, comparisons (and IN, LIKE,
+    // IS NULL), + -, * /, unary minus.
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        self.nest()?;
+        let mut left = self.and_expr()?;
+        while self.eat_keyword(&["OR"]) {
+            self.nest()?;
+            let right = self.and_expr()?;
+            left = binary(BinaryOp::Or, left, right);
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut left = self.not_expr()?;
+        while self.eat_keyword(&["AND"]) {
+            self.nest()?;
+            let right = self.not_expr()?;
+            left = binary(BinaryOp::And, left, right);
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn not_expr(&mut self) -> Result<Expr, ParseError> {
+        if self.eat_keyword(&["NOT"]) {
+            let depth = self.depth;
+            self.nest()?;
+            let expr = self.not_expr()?;
+            self.depth = depth;
+            return Ok(Expr::Unary { op: UnaryOp::Not, expr: Box::new(expr) });
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        let left = self.additive()?;
+        let op = match self.peek().kind {
+            TokenKind::Eq => Some(BinaryOp::Eq),
+            TokenKind::Ne => Some(BinaryOp::Ne),
+            TokenKind::Lt => Some(BinaryOp::Lt),
+            TokenKind::Le => Some(BinaryOp::Le),
+            TokenKind::Gt => Some(BinaryOp::Gt),
+            TokenKind::Ge => Some(BinaryOp::Ge),
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.advance();
+            let right = self.additive()?;
+            return Ok(binary(op, left, right));
+        }
+
+        if self.eat_keyword(&["IS"]) {
+            let negated = self.eat_keyword(&["NOT"]);
+            self.expect_keyword(&["NULL"])?;
+            return Ok(Expr::IsNull { expr: Box::new(left), negated });
+        }
+        // NOT here only as part of NOT IN / NOT LIKE
+        let negated = if self.is_keyword(0, "NOT") && (self.is_keyword(1, "IN") || self.is_keyword(1, "LIKE")) {
+            self.advance();
+            true
+        } else {
+            false
+        };
+        if self.eat_keyword(&["IN"]) {
+            self.expect(&TokenKind::LParen, "(")?;
+            let mut list = Vec::new();
+            loop {
+                list.push(self.expr()?);
+                if !self.eat(&TokenKind::Comma) {
+                    break;
+                }
+            }
+            self.expect(&TokenKind::RParen, ")")?;
+            return Ok(Expr::In { expr: Box::new(left), list, negated });
+        }
+        if self.eat_keyword(&["LIKE"]) {
+            let pattern = self.additive()?;
+            return Ok(Expr::Like { expr: Box::new(left), pattern: Box::new(pattern), negated });
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinaryOp::Add,
+                TokenKind::Minus => BinaryOp::Sub,
+                _ => {
+                    self.depth = depth;
+                    return Ok(left);
+                }
+            };
+            self.advance();
+            self.nest()?;
+            let right = self.multiplicative()?;
+            left = binary(op, left, right);
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ParseError> {
+        let depth = self.depth;
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Star => BinaryOp::Mul,
+                TokenKind::Slash => BinaryOp::Div,
+                _ => {
+                    self.depth = depth;
+                    return Ok(left);
+                }
+            };
+            self.advance();
+            self.nest()?;
+            let right = self.unary()?;
+            left = binary(op, left, right);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.eat(&TokenKind::Minus) {
+            let depth = self.depth;
+            self.nest()?;
+            let expr = self.unary()?;
+            self.depth = depth;
+            // Fold negative number literals right away
+            return Ok(match expr {
+                Expr::Literal(Value::Int(v)) => Expr::Literal(Value::Int(v.wrapping_neg())),
+                Expr::Literal(Value::Float(v)) => Expr::Literal(Value::Float(-v)),
+                expr => Expr::Unary { op: UnaryOp::Neg, expr: Box::new(expr) },
+            });
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let tok = self.peek().clone();
+        match &tok.kind {
+            TokenKind::Int(v) => {
+                self.advance();
+                Ok(Expr::Literal(Value::Int(*v)))
+            }
+            TokenKind::Float(v) => {
+                self.advance();
+                Ok(Expr::Literal(Value::Float(*v)))
+            }
+            TokenKind::Str(s) => {
+                self.advance();
+                Ok(Expr::Literal(Value::String(s.clone())))
+            }
+            TokenKind::LParen => {
+                self.advance();
+                let expr = self.expr()?;
+                self.expect(&TokenKind::RParen, ")")?;
+                Ok(expr)
+            }
+            TokenKind::Ident(word) => {
+                let upper = word.to_ascii_uppercase();
+                match upper.as_str() {
+                    "NULL" => {
+                        self.advance();
+                        return Ok(Expr::Literal(Value::Null));
+                    }
+                    "TRUE" | "FALSE" => {
+                        self.advance();
+                        return Ok(Expr::Literal(Value::Bool(upper == "TRUE")));
+                    }
+                    _ => {}
+                }
+                // Aggregates; the names stay usable as column names
+                if let (Some(func), TokenKind::LParen) = (AggFunc::parse(word), &self.peek_at(1).kind) {
+                    self.idx += 2;
+                    let arg = if func == AggFunc::Count && self.eat(&TokenKind::Star) {
+                        None
+                    } else {
+                        Some(Box::new(self.expr()?))
+                    };
+                    self.expect(&TokenKind::RParen, ")")?;
+                    return Ok(Expr::Aggregate { func, arg });
+                }
+                // Typed literals: X'cafe' and TIMESTAMP '2024-01-31 08:00:00'
+                if let TokenKind::Str(text) = &self.peek_at(1).kind {
+                    if upper == "X" && self.peek_at(1).pos == tok.pos + 1 {
+                        let bytes = parse_hex(text).ok_or_else(|| {
+                            ParseError::new("bad hex literal", tok.pos, &format!("X'{}'", text))
+                        })?;
+                        self.idx += 2;
+                        return Ok(Expr::Literal(Value::Bytes(bytes)));
+                    }
+                    if upper == "TIMESTAMP" {
+                        let pos = self.peek_at(1).pos;
+                        let micros = types::parse_timestamp(text)
+                            .ok_or_else(|| ParseError::new("bad timestamp", pos, &format!("'{}'", text)))?;
+                        self.idx += 2;
+                        return Ok(Expr::Literal(Value::Timestamp(micros)));
+                    }
+                }
+                self.column()
+            }
+            TokenKind::Quoted(_) => self.column(),
+            _ => Err(self.expected("an expression")),
+        }
+    }
+
+    fn column(&mut self) -> Result<Expr, ParseError> {
+        let name = self.ident("column name")?;
+        if self.eat(&TokenKind::Dot) {
+            let column = self.ident("column name")?;
+            return Ok(Expr::Column { table: Some(name), name: column });
+        }
+        Ok(Expr::Column { table: None, name })
+    }
+}
+
+fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+    Expr::Binary { op, left: Box::new(left), right: Box::new(right) }
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,178 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::ops::Bound;
+use crate::sawitdb::catalog::TableInfo;
+use crate::sawitdb::types::Value;
+use super::ast::{BinaryOp, Expr};
+
+/// A key interval as passed to `BTreeIndex::range`.
+pub type KeyRange = (Bound<Value>, Bound<Value>);
+
+/// How a query reaches its rows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Access {
+    /// Read every row of the heap.
+    Scan,
+    /// Read the rows under `ranges` (sorted, disjoint) of the primary key
+    /// (`column: None`) or of the secondary index on `column`.
+    Index { column: Option<String>, ranges: Vec<KeyRange> },
+}
+
+// Only `col <op> literal` conjuncts of the top-level AND chain are used to
+// pick an index; the executor re-checks the whole WHERE clause on every
+// row it fetches, so a plan only has to cover the matching rows.
+//
+// Candidates are ranked: the primary key, then a secondary index with an
+// equality or IN predicate, then a secondary index with a range.
+
+/// Chooses how to read `info` for the given WHERE clause.
+pub fn plan(info: &TableInfo, filter: Option<&Expr>) -> Access {
+    plan_as(info, &info.name, filter)
+}
+
+/// `plan` for a table referred to as `alias` in the query.
+pub fn plan_as(info: &TableInfo, alias: &str, filter: Option<&Expr>) -> Access {
+    let mut conjuncts = Vec::new();
+    if let Some(filter) = filter {
+        split_and(filter, &mut conjuncts);
+    }
+
+    if let Some(ranges) = ranges_for(alias, &info.key_field, &conjuncts) {
+        return Access::Index { column: None, ranges };
+    }
+    let mut best: Option<(bool, &str, Vec<KeyRange>)> = None;
+    for index in &info.indexes {
+        if let Some(ranges) = ranges_for(alias, &index.column, &conjuncts) {
+            let points = ranges.iter().all(is_point);
+            if best.as_ref().is_none_or(|(best_points, _, _)| points && !best_points) {
+                best = Some((points, &index.column, ranges));
+            }
+        }
+    }
+    match best {
+        Some((_, column, ranges)) => Access::Index { column: Some(String::from(column)), ranges },
+        None => Access::Scan,
+    }
+}
+
+/// The conjuncts of a top-level AND chain.
+pub fn split_and<'a>(expr: &'a Expr, out: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::Binary { op: BinaryOp::And, left, right } => {
+            split_and(left, out);
+            split_and(right, out);
+        }
+        expr => out.push(expr),
+    }
+}
+
+// Intersection of every constraint the conjuncts place on `column`, or
+// None if there is none.
+fn ranges_for(table: &str, column: &str, conjuncts: &[&Expr]) -> Option<Vec<KeyRange>> {
+    let mut ranges = vec![(Bound::Unbounded, Bound::Unbounded)];
+    let mut constrained = false;
+    for expr in conjuncts {
+        if let Some(allowed) = constraint(table, column, expr) {
+            constrained = true;
+            ranges = intersect(&ranges, &allowed);
+        }
+    }
+    if constrained { Some(ranges) } else { None }
+}
+
+// The key ranges a single conjunct allows, if it constrains `column`.
+fn constraint(table: &str, column: &str, expr: &Expr) -> Option<Vec<KeyRange>> {
+    let is_col = |e: &Expr| matches!(e, Expr::Column { table: t, name } if name == column && t.as_deref().is_none_or(|t| t == table));
+    match expr {
+        Expr::Binary { op, left, right } => {
+            let (op, value) = match (&**left, &**right) {
+                (l, Expr::Literal(v)) if is_col(l) => (*op, v),
+                (Expr::Literal(v), r) if is_col(r) => (flip(*op)?, v),
+                _ => return None,
+            };
+            if value.is_null() {
+                // Comparing with NULL never yields TRUE
+                return Some(Vec::new());
+            }
+            let v = || value.clone();
+            Some(vec![match op {
+                BinaryOp::Eq => (Bound::Included(v()), Bound::Included(v())),
+                BinaryOp::Lt => (Bound::Unbounded, Bound::Excluded(v())),
+                BinaryOp::Le => (Bound::Unbounded, Bound::Included(v())),
+                BinaryOp::Gt => (Bound::Excluded(v()), Bound::Unbounded),
+                BinaryOp::Ge => (Bound::Included(v()), Bound::Unbounded),
+                _ => return None,
+            }])
+        }
+        Expr::In { expr, list, negated: false } if is_col(expr) => {
+            let mut values = Vec::with_capacity(list.len());
+            for item in list {
+                match item {
+                    Expr::Literal(v) if !v.is_null() => values.push(v.clone()),
+                    Expr::Literal(_) => {}
+                    _ => return None,
+                }
+            }
+            values.sort();
+            values.dedup();
+            Some(values.into_iter().map(|v| (Bound::Included(v.clone()), Bound::Included(v))).collect())
+        }
+        _ => None,
+    }
+}
+
+// `lit op col` as `col op' lit`.
+fn flip(op: BinaryOp) -> Option<BinaryOp> {
+    Some(match op {
+        BinaryOp::Eq => BinaryOp::Eq,
+        BinaryOp::Lt => BinaryOp::Gt,
+        BinaryOp::Le => BinaryOp::Ge,
+        BinaryOp::Gt => BinaryOp::Lt,
+        BinaryOp::Ge => BinaryOp::Le,
+        _ => return None,
+    })
+}
+
+// Both inputs are sorted and disjoint, so the pairwise intersections come
+// out sorted and disjoint as well.
+fn intersect(a: &[KeyRange], b: &[KeyRange]) -> Vec<KeyRange> {
+    let mut out = Vec::new();
+    for (a_lo, a_hi) in a {
+        for (b_lo, b_hi) in b {
+            let lo = tighter(a_lo, b_lo, Ordering::Greater);
+            let hi = tighter(a_hi, b_hi, Ordering::Less);
+            if !is_empty(&lo, &hi) {
+                out.push((lo, hi));
+            }
+        }
+    }
+    out
+}
+
+// The more restrictive of two bounds: the larger lower bound (`want` =
+// Greater) or the smaller upper bound (`want` = Less).
+fn tighter(a: &Bound<Value>, b: &Bound<Value>, want: Ordering) -> Bound<Value> {
+    match (a, b) {
+        (Bound::Unbounded, other) | (other, Bound::Unbounded) => other.clone(),
+        (Bound::Included(x) | Bound::Excluded(x), Bound::Included(y) | Bound::Excluded(y)) => match x.cmp(y) {
+            Ordering::Equal if matches!(a, Bound::Excluded(_)) => a.clone(),
+            Ordering::Equal => b.clone(),
+            ord if ord == want => a.clone(),
+            _ => b.clone(),
+        },
+    }
+}
+
+fn is_empty(lo: &Bound<Value>, hi: &Bound<Value>) -> bool {
+    match (lo, hi) {
+        (Bound::Included(l), Bound::Included(h)) => l > h,
+        (Bound::Included(l) | Bound::Excluded(l), Bound::Included(h) | Bound::Excluded(h)) => l >= h,
+        _ => false,
+    }
+}
+
+fn is_point(range: &KeyRange) -> bool {
+    matches!(range, (Bound::Included(l), Bound::Included(h)) if l == h)
+}
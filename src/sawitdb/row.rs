@@ -0,0 +1,135 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::fmt;
+use super::types::{Value, ValueType};
+use super::catalog::Column;
+use super::codec;
+use super::error::DbError;
+
+/// A table row: named fields in order. Rows read back from a table with a
+/// schema have one field per column, in column order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Row {
+    fields: Vec<(String, Value)>,
+}
+
+impl Row {
+    pub fn new() -> Self {
+        Row { fields: Vec::new() }
+    }
+
+    /// Builder form of `set`.
+    pub fn with(mut self, name: &str, value: Value) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    /// Sets a field, replacing any previous value under the same name.
+    pub fn set(&mut self, name: &str, value: Value) {
+        match self.fields.iter_mut().find(|(n, _)| n == name) {
+            Some(field) => field.1 = value,
+            None => self.fields.push((String::from(name), value)),
+        }
+    }
+
+    /// Appends a field even if one with the same name exists, as query
+    /// results may repeat a column.
+    pub fn push(&mut self, name: &str, value: Value) {
+        self.fields.push((String::from(name), value));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.fields.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    pub fn fields(&self) -> &[(String, Value)] {
+        &self.fields
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The row's value for `key_field`, which must be present and not NULL.
+    pub fn key(&self, key_field: &str) -> Result<&Value, DbError> {
+        match self.get(key_field) {
+            Some(v) if !v.is_null() => Ok(v),
+            _ => Err(DbError::MissingKey(String::from(key_field))),
+        }
+    }
+
+    /// Checks the row against `schema` and returns its values in column
+    /// order. Unknown fields and mistyped values are rejected, missing
+    /// columns become NULL and INT values are widened for FLOAT columns.
+    pub fn conform(&self, schema: &[Column]) -> Result<Vec<Value>, DbError> {
+        if let Some((name, _)) = self.fields.iter().find(|(n, _)| !schema.iter().any(|c| &c.name == n)) {
+            return Err(DbError::NoSuchColumn(name.clone()));
+        }
+        schema.iter().map(|col| {
+            match (self.get(&col.name), col.ty) {
+                (None, _) | (Some(Value::Null), _) => Ok(Value::Null),
+                (Some(Value::Int(v)), ValueType::Float) => Ok(Value::Float(*v as f64)),
+                (Some(v), ty) if v.value_type() == Some(ty) => Ok(v.clone()),
+                (Some(v), ty) => Err(DbError::TypeMismatch {
+                    column: col.name.clone(),
+                    expected: ty,
+                    found: v.value_type(),
+                }),
+            }
+        }).collect()
+    }
+
+    /// Encodes the row for a table with `schema`: just the values in column
+    /// order, or name/value pairs when the table has no schema.
+    pub fn encode(&self, schema: &[Column]) -> Result<Vec<u8>, DbError> {
+        if schema.is_empty() {
+            let pairs: Vec<Value> = self.fields.iter()
+                .flat_map(|(n, v)| [Value::String(n.clone()), v.clone()])
+                .collect();
+            return Ok(codec::encode(&pairs));
+        }
+        Ok(codec::encode(&self.conform(schema)?))
+    }
+
+    pub fn decode(buf: &[u8], schema: &[Column]) -> Result<Self, DbError> {
+        let values = codec::decode(buf)?;
+        if schema.is_empty() {
+            if values.len() % 2 != 0 {
+                return Err(DbError::Decode("unpaired row field"));
+            }
+            let mut row = Row::new();
+            for pair in values.chunks(2) {
+                match &pair[0] {
+                    Value::String(name) => row.fields.push((name.clone(), pair[1].clone())),
+                    _ => return Err(DbError::Decode("bad field name")),
+                }
+            }
+            return Ok(row);
+        }
+        if values.len() != schema.len() {
+            return Err(DbError::Decode("row does not match schema"));
+        }
+        let fields = schema.iter().zip(values).map(|(c, v)| (c.name.clone(), v)).collect();
+        Ok(Row { fields })
+    }
+}
+
+impl fmt::Display for Row {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("{")?;
+        for (i, (name, value)) in self.fields.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            match value {
+                Value::String(s) => write!(f, "{}: \"{}\"", name, s)?,
+                _ => write!(f, "{}: {}", name, value)?,
+            }
+        }
+        f.write_str("}")
+    }
+}
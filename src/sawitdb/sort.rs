@@ -0,0 +1,326 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use crate::drivers::block::BLOCK_SIZE;
+use super::codec;
+use super::error::DbError;
+use super::pager::{Pager, PAGE_DATA_SIZE};
+use super::types::Value;
+
+// External merge sort for records of `Value`s, ordered by their leading
+// values. Records are buffered until `SORT_MEMORY` bytes (encoded size)
+// are held, then sorted and spilled as a run to a chain of scratch pages
+// (`Pager::alloc_temp_page`). Reading merges up to `MERGE_FAN_IN` runs at
+// a time, each needing one page buffer; with more runs, earlier passes
+// merge neighbouring runs into longer ones first.
+//
+// The sort is stable: runs hold consecutive input, and on equal keys the
+// merge takes from the earlier run.
+//
+// Run page layout:
+// 0..4 : next page of the run (0 = last)
+// 4..6 : bytes used on this page
+// 6..  : the run's byte stream - varint length + `codec::encode` record
+
+/// Bytes of records held in memory before a run is spilled.
+pub const SORT_MEMORY: usize = 64 * 1024;
+/// Runs merged at once (one page buffer each).
+pub const MERGE_FAN_IN: usize = 8;
+
+const RUN_HEADER: usize = 6;
+const RUN_CAPACITY: usize = PAGE_DATA_SIZE - RUN_HEADER;
+
+/// Orders records by their first `desc.len()` values; `desc[i]` reverses
+/// the i-th. NULLs sort first ascending, last descending.
+pub fn compare(a: &[Value], b: &[Value], desc: &[bool]) -> Ordering {
+    for (i, &desc) in desc.iter().enumerate() {
+        let ord = a.get(i).cmp(&b.get(i));
+        if ord != Ordering::Equal {
+            return if desc { ord.reverse() } else { ord };
+        }
+    }
+    Ordering::Equal
+}
+
+pub struct ExternalSorter {
+    desc: Vec<bool>,
+    memory: usize,
+    buffer: Vec<Vec<Value>>,
+    buffered: usize,
+    runs: Vec<u32>,
+}
+
+impl ExternalSorter {
+    pub fn new(desc: Vec<bool>) -> Self {
+        Self::with_memory(desc, SORT_MEMORY)
+    }
+
+    pub fn with_memory(desc: Vec<bool>, memory: usize) -> Self {
+        ExternalSorter { desc, memory, buffer: Vec::new(), buffered: 0, runs: Vec::new() }
+    }
+
+    pub fn push(&mut self, pager: &mut Pager, record: Vec<Value>) -> Result<(), DbError> {
+        self.buffered += record.iter().map(codec::value_len).sum::<usize>() + 16;
+        self.buffer.push(record);
+        if self.buffered >= self.memory {
+            self.spill(pager)?;
+        }
+        Ok(())
+    }
+
+    /// Runs written to scratch pages so far.
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Sorts what is left and returns the records in order. Small inputs
+    /// never touch the disk.
+    pub fn finish(mut self, pager: &mut Pager) -> Result<SortedRecords, DbError> {
+        if self.runs.is_empty() {
+            let desc = self.desc;
+            self.buffer.sort_by(|a, b| compare(a, b, &desc));
+            return Ok(SortedRecords::Memory(self.buffer.into_iter()));
+        }
+        if !self.buffer.is_empty() {
+            self.spill(pager)?;
+        }
+        let mut runs = self.runs;
+        while runs.len() > MERGE_FAN_IN {
+            let mut merged = Vec::with_capacity(runs.len().div_ceil(MERGE_FAN_IN));
+            for group in runs.chunks(MERGE_FAN_IN) {
+                let mut merge = Merge::open(pager, group, self.desc.clone())?;
+                let mut out = RunWriter::new(pager)?;
+                while let Some(record) = merge.next_record(pager) {
+                    out.write(pager, &record?)?;
+                }
+                merged.push(out.finish(pager)?);
+            }
+            runs = merged;
+        }
+        Ok(SortedRecords::Merge(Merge::open(pager, &runs, self.desc)?))
+    }
+
+    /// Frees any spilled runs without reading them.
+    pub fn discard(self, pager: &mut Pager) -> Result<(), DbError> {
+        for run in self.runs {
+            RunReader::open(pager, run)?.release(pager)?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self, pager: &mut Pager) -> Result<(), DbError> {
+        let desc = &self.desc;
+        self.buffer.sort_by(|a, b| compare(a, b, desc));
+        let mut out = RunWriter::new(pager)?;
+        for record in self.buffer.drain(..) {
+            out.write(pager, &record)?;
+        }
+        self.runs.push(out.finish(pager)?);
+        self.buffered = 0;
+        Ok(())
+    }
+}
+
+/// Output of `ExternalSorter::finish`.
+pub enum SortedRecords {
+    Memory(vec::IntoIter<Vec<Value>>),
+    Merge(Merge),
+}
+
+impl SortedRecords {
+    pub fn next_record(&mut self, pager: &mut Pager) -> Option<Result<Vec<Value>, DbError>> {
+        match self {
+            SortedRecords::Memory(records) => records.next().map(Ok),
+            SortedRecords::Merge(merge) => merge.next_record(pager),
+        }
+    }
+
+    /// Frees the scratch pages of records not read yet. Reading to the end
+    /// frees them as well.
+    pub fn release(self, pager: &mut Pager) -> Result<(), DbError> {
+        if let SortedRecords::Merge(merge) = self {
+            for (reader, _) in merge.inputs {
+                reader.release(pager)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// K-way merge over sorted runs.
+pub struct Merge {
+    desc: Vec<bool>,
+    // Each run with its next record
+    inputs: Vec<(RunReader, Option<Vec<Value>>)>,
+}
+
+impl Merge {
+    fn open(pager: &mut Pager, runs: &[u32], desc: Vec<bool>) -> Result<Self, DbError> {
+        let mut inputs = Vec::with_capacity(runs.len());
+        for &run in runs {
+            let mut reader = RunReader::open(pager, run)?;
+            let head = reader.read_record(pager)?;
+            inputs.push((reader, head));
+        }
+        Ok(Merge { desc, inputs })
+    }
+
+    pub fn next_record(&mut self, pager: &mut Pager) -> Option<Result<Vec<Value>, DbError>> {
+        let mut best: Option<usize> = None;
+        for (i, (_, head)) in self.inputs.iter().enumerate() {
+            if let Some(record) = head {
+                // Strictly smaller only: ties go to the earlier run
+                let better = match best.and_then(|b| self.inputs[b].1.as_ref()) {
+                    Some(current) => compare(record, current, &self.desc) == Ordering::Less,
+                    None => true,
+                };
+                if better {
+                    best = Some(i);
+                }
+            }
+        }
+        let (reader, head) = &mut self.inputs[best?];
+        let next = match reader.read_record(pager) {
+            Ok(next) => next,
+            Err(e) => return Some(Err(e)),
+        };
+        core::mem::replace(head, next).map(Ok)
+    }
+}
+
+// Appends a byte stream to a new chain of scratch pages.
+struct RunWriter {
+    first: u32,
+    page_id: u32,
+    buf: Vec<u8>,
+    used: usize,
+}
+
+impl RunWriter {
+    fn new(pager: &mut Pager) -> Result<Self, DbError> {
+        let page_id = pager.alloc_temp_page()?;
+        Ok(RunWriter { first: page_id, page_id, buf: vec![0u8; BLOCK_SIZE], used: 0 })
+    }
+
+    fn write(&mut self, pager: &mut Pager, record: &[Value]) -> Result<(), DbError> {
+        let data = codec::encode(record);
+        let mut bytes = Vec::with_capacity(data.len() + 4);
+        codec::write_varint(&mut bytes, data.len() as u64);
+        bytes.extend_from_slice(&data);
+
+        let mut rest = bytes.as_slice();
+        while !rest.is_empty() {
+            if self.used == RUN_CAPACITY {
+                let next = pager.alloc_temp_page()?;
+                self.buf[0..4].copy_from_slice(&next.to_le_bytes());
+                self.write_page(pager)?;
+                self.page_id = next;
+                self.used = 0;
+            }
+            let n = rest.len().min(RUN_CAPACITY - self.used);
+            let at = RUN_HEADER + self.used;
+            self.buf[at..at + n].copy_from_slice(&rest[..n]);
+            self.used += n;
+            rest = &rest[n..];
+        }
+        Ok(())
+    }
+
+    /// Writes the last page and returns the run's first page.
+    fn finish(mut self, pager: &mut Pager) -> Result<u32, DbError> {
+        self.buf[0..4].copy_from_slice(&0u32.to_le_bytes());
+        self.write_page(pager)?;
+        Ok(self.first)
+    }
+
+    fn write_page(&mut self, pager: &mut Pager) -> Result<(), DbError> {
+        self.buf[4..6].copy_from_slice(&(self.used as u16).to_le_bytes());
+        pager.write_temp_page(self.page_id, &mut self.buf)?;
+        self.buf.fill(0);
+        Ok(())
+    }
+}
+
+// Reads a run back, freeing each page once it has been consumed.
+struct RunReader {
+    page_id: u32,
+    buf: Vec<u8>,
+    pos: usize,
+    used: usize,
+}
+
+impl RunReader {
+    fn open(pager: &mut Pager, first: u32) -> Result<Self, DbError> {
+        let mut reader = RunReader { page_id: first, buf: vec![0u8; BLOCK_SIZE], pos: 0, used: 0 };
+        reader.load(pager)?;
+        Ok(reader)
+    }
+
+    fn load(&mut self, pager: &mut Pager) -> Result<(), DbError> {
+        pager.read_temp_page(self.page_id, &mut self.buf)?;
+        self.used = u16::from_le_bytes([self.buf[4], self.buf[5]]) as usize;
+        self.pos = 0;
+        Ok(())
+    }
+
+    fn next_page(&self) -> u32 {
+        u32::from_le_bytes([self.buf[0], self.buf[1], self.buf[2], self.buf[3]])
+    }
+
+    // Next byte of the stream, None at the end of the run.
+    fn byte(&mut self, pager: &mut Pager) -> Result<Option<u8>, DbError> {
+        while self.pos == self.used {
+            if self.page_id == 0 {
+                return Ok(None);
+            }
+            let next = self.next_page();
+            pager.free_temp_page(self.page_id);
+            self.page_id = next;
+            if next == 0 {
+                return Ok(None);
+            }
+            self.load(pager)?;
+        }
+        let b = self.buf[RUN_HEADER + self.pos];
+        self.pos += 1;
+        Ok(Some(b))
+    }
+
+    fn read_record(&mut self, pager: &mut Pager) -> Result<Option<Vec<Value>>, DbError> {
+        let mut len = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = match self.byte(pager)? {
+                Some(b) => b,
+                None if shift == 0 => return Ok(None),
+                None => return Err(DbError::Decode("truncated sort run")),
+            };
+            if shift > 63 {
+                return Err(DbError::Decode("bad length in sort run"));
+            }
+            len |= ((b & 0x7f) as u64) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                break;
+            }
+        }
+        let mut data = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            data.push(self.byte(pager)?.ok_or(DbError::Decode("truncated sort run"))?);
+        }
+        codec::decode(&data).map(Some)
+    }
+
+    // Frees the pages not consumed yet.
+    fn release(mut self, pager: &mut Pager) -> Result<(), DbError> {
+        while self.page_id != 0 {
+            let next = self.next_page();
+            pager.free_temp_page(self.page_id);
+            self.page_id = next;
+            if next != 0 {
+                self.load(pager)?;
+            }
+        }
+        Ok(())
+    }
+}
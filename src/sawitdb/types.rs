@@ -1,40 +1,333 @@
 use alloc::string::String;
+use alloc::vec::Vec;
 use alloc::fmt;
 use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+use super::codec;
 
-#[derive(Debug, Clone, PartialEq)]
+/// A single SQL value.
+///
+/// Values of every variant are totally ordered, so any mix of them can be
+/// used as index keys. Variants are ranked first:
+///
+/// `Null < Bool < Int/Float < Timestamp < String < Bytes`
+///
+/// `Int` and `Float` share a rank and compare by exact numeric value, so
+/// `Int(1) == Float(1.0)` and `-0.0 == 0.0`. NaN equals itself and sorts
+/// above every other number. Within the other ranks the natural order
+/// applies (`false < true`, strings and bytes lexicographically).
+#[derive(Debug, Clone)]
 pub enum Value {
+    Null,
+    Bool(bool),
     Int(i64),
     Float(f64),
     String(String),
+    Bytes(Vec<u8>),
+    Timestamp(i64), // Microseconds since the Unix epoch (UTC)
+}
+
+/// Column type as stored in table schemas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    Bool,
+    Int,
+    Float,
+    String,
+    Bytes,
+    Timestamp,
+}
+
+impl ValueType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueType::Bool => "BOOL",
+            ValueType::Int => "INT",
+            ValueType::Float => "FLOAT",
+            ValueType::String => "STRING",
+            ValueType::Bytes => "BYTES",
+            ValueType::Timestamp => "TIMESTAMP",
+        }
+    }
+
+    /// Parses a type name as typed in the shell (case-insensitive).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "BOOL" | "BOOLEAN" => Some(ValueType::Bool),
+            "INT" | "INTEGER" => Some(ValueType::Int),
+            "FLOAT" | "REAL" | "DOUBLE" => Some(ValueType::Float),
+            "STRING" | "TEXT" | "VARCHAR" => Some(ValueType::String),
+            "BYTES" | "BLOB" => Some(ValueType::Bytes),
+            "TIMESTAMP" | "DATETIME" => Some(ValueType::Timestamp),
+            _ => None,
+        }
+    }
+
+    pub fn tag(&self) -> u8 {
+        match self {
+            ValueType::Bool => TAG_BOOL,
+            ValueType::Int => TAG_INT,
+            ValueType::Float => TAG_FLOAT,
+            ValueType::String => TAG_STRING,
+            ValueType::Bytes => TAG_BYTES,
+            ValueType::Timestamp => TAG_TIMESTAMP,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            TAG_BOOL => Some(ValueType::Bool),
+            TAG_INT => Some(ValueType::Int),
+            TAG_FLOAT => Some(ValueType::Float),
+            TAG_STRING => Some(ValueType::String),
+            TAG_BYTES => Some(ValueType::Bytes),
+            TAG_TIMESTAMP => Some(ValueType::Timestamp),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// On-page tags, one byte in front of every encoded value
+pub(crate) const TAG_INT: u8 = 1;
+pub(crate) const TAG_FLOAT: u8 = 2;
+pub(crate) const TAG_STRING: u8 = 3;
+pub(crate) const TAG_NULL: u8 = 4;
+pub(crate) const TAG_BOOL: u8 = 5;
+pub(crate) const TAG_BYTES: u8 = 6;
+pub(crate) const TAG_TIMESTAMP: u8 = 7;
+
+impl Value {
+    /// Type of this value; `Null` belongs to every column type.
+    pub fn value_type(&self) -> Option<ValueType> {
+        match self {
+            Value::Null => None,
+            Value::Bool(_) => Some(ValueType::Bool),
+            Value::Int(_) => Some(ValueType::Int),
+            Value::Float(_) => Some(ValueType::Float),
+            Value::String(_) => Some(ValueType::String),
+            Value::Bytes(_) => Some(ValueType::Bytes),
+            Value::Timestamp(_) => Some(ValueType::Timestamp),
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Position of the variant in the cross-type order.
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Int(_) | Value::Float(_) => 2,
+            Value::Timestamp(_) => 3,
+            Value::String(_) => 4,
+            Value::Bytes(_) => 5,
+        }
+    }
+
+    /// Number of bytes `encode_into` appends for this value.
+    pub fn encoded_len(&self) -> usize {
+        codec::value_len(self)
+    }
+
+    /// Appends the on-page form of this value (see `codec::encode_value`).
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        codec::encode_value(self, out)
+    }
+
+    /// Decodes a value written by `encode_into`, returning it together with
+    /// the number of bytes consumed. `None` means the bytes are malformed.
+    pub fn decode_from(buf: &[u8]) -> Option<(Value, usize)> {
+        let mut pos = 0;
+        let value = codec::decode_value(buf, &mut pos).ok()?;
+        Some((value, pos))
+    }
+}
+
+/// Orders floats with NaN equal to itself and above every other number.
+fn cmp_f64(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+    }
+}
+
+/// Compares an integer with a float exactly (no rounding of large ints).
+fn cmp_int_f64(a: i64, b: f64) -> Ordering {
+    const TWO_POW_63: f64 = 9_223_372_036_854_775_808.0;
+    if b.is_nan() || b >= TWO_POW_63 {
+        return Ordering::Less;
+    }
+    if b < -TWO_POW_63 {
+        return Ordering::Greater;
+    }
+    // `b as i64` truncates toward zero and is exact in this range
+    let whole = b as i64;
+    a.cmp(&whole).then_with(|| cmp_f64(whole as f64, b))
+}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Float(a), Value::Float(b)) => cmp_f64(*a, *b),
+            (Value::Int(a), Value::Float(b)) => cmp_int_f64(*a, *b),
+            (Value::Float(a), Value::Int(b)) => cmp_int_f64(*b, *a).reverse(),
+            (Value::Timestamp(a), Value::Timestamp(b)) => a.cmp(b),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Bytes(a), Value::Bytes(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
 }
 
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-         match (self, other) {
-            (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
-            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
-            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
-            
-            // Cross-type comparisons (matching Go implementation behavior)
-            (Value::Int(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
-            (Value::Float(a), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
-            
-            // String vs Number -> Unordered (or define rule? Go code implies strict type check or return 0)
-            // For now, let's treat different types as incomparable (None), or define an order.
-            // Go implementation defaults to 0 if types don't match (except int/float mix).
-            _ => None 
-        }
+        Some(self.cmp(other))
+    }
+}
+
+// Equality follows the total order, so `Int(1) == Float(1.0)`
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
     }
 }
 
+impl Eq for Value {}
+
+// Hashing agrees with equality: a float equal to an integer hashes as that
+// integer (so `Int(1)` and `Float(1.0)`, or `0.0` and `-0.0`, collide), and
+// every NaN hashes alike.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        const TWO_POW_63: f64 = 9_223_372_036_854_775_808.0;
+        self.rank().hash(state);
+        match self {
+            Value::Null => {}
+            Value::Bool(v) => v.hash(state),
+            Value::Int(v) => v.hash(state),
+            Value::Float(v) if v.is_nan() => state.write_u8(0xff),
+            Value::Float(v) if *v >= -TWO_POW_63 && *v < TWO_POW_63 && (*v as i64) as f64 == *v => (*v as i64).hash(state),
+            Value::Float(v) => v.to_bits().hash(state),
+            Value::Timestamp(v) => v.hash(state),
+            Value::String(v) => v.hash(state),
+            Value::Bytes(v) => v.hash(state),
+        }
+    }
+}
 
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Value::Null => f.write_str("NULL"),
+            Value::Bool(v) => write!(f, "{}", v),
             Value::Int(v) => write!(f, "{}", v),
             Value::Float(v) => write!(f, "{}", v),
             Value::String(v) => write!(f, "{}", v),
+            Value::Bytes(b) => {
+                f.write_str("x'")?;
+                for byte in b {
+                    write!(f, "{:02x}", byte)?;
+                }
+                f.write_str("'")
+            }
+            Value::Timestamp(micros) => fmt_timestamp(f, *micros),
+        }
+    }
+}
+
+/// Parses `YYYY-MM-DD[ HH:MM:SS[.ffffff]]` (UTC, `T` also accepted as the
+/// separator) into microseconds since the epoch.
+pub fn parse_timestamp(text: &str) -> Option<i64> {
+    fn num(s: &str, digits: usize) -> Option<i64> {
+        if s.len() != digits || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    }
+    let (date, time) = match text.find([' ', 'T']) {
+        Some(i) => (&text[..i], Some(&text[i + 1..])),
+        None => (text, None),
+    };
+    let mut parts = date.splitn(3, '-');
+    let year = num(parts.next()?, 4)?;
+    let month = num(parts.next()?, 2)?;
+    let day = num(parts.next()?, 2)?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let (mut secs, mut frac) = (0, 0);
+    if let Some(time) = time {
+        let (hms, fraction) = match time.split_once('.') {
+            Some((hms, f)) if (1..=6).contains(&f.len()) => (hms, Some(f)),
+            Some(_) => return None,
+            None => (time, None),
+        };
+        let mut parts = hms.splitn(3, ':');
+        let (h, m, s) = (num(parts.next()?, 2)?, num(parts.next()?, 2)?, num(parts.next()?, 2)?);
+        if h > 23 || m > 59 || s > 59 {
+            return None;
+        }
+        secs = h * 3600 + m * 60 + s;
+        if let Some(f) = fraction {
+            frac = num(f, f.len())? * 10i64.pow(6 - f.len() as u32);
         }
     }
+
+    // Day count from civil date (Howard Hinnant's days_from_civil)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    Some((days * 86_400 + secs) * 1_000_000 + frac)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Writes `YYYY-MM-DD HH:MM:SS[.ffffff]` (UTC) for microseconds since the epoch.
+fn fmt_timestamp(f: &mut fmt::Formatter<'_>, micros: i64) -> fmt::Result {
+    let secs = micros.div_euclid(1_000_000);
+    let frac = micros.rem_euclid(1_000_000);
+    let days = secs.div_euclid(86_400);
+    let tod = secs.rem_euclid(86_400);
+
+    // Civil date from day count (Howard Hinnant's days_from_civil inverse)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, tod / 3600, tod / 60 % 60, tod % 60)?;
+    if frac != 0 {
+        write!(f, ".{:06}", frac)?;
+    }
+    Ok(())
 }
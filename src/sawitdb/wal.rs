@@ -0,0 +1,278 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use alloc::vec;
+use crate::drivers::block::{BlockIO, BlockError, BLOCK_SIZE};
+use super::pager::{stamp_trailer, verify_trailer, page_lsn, trailer_page_id};
+use core::cell::Cell;
+use core::convert::TryInto;
+
+// WAL region layout (at the end of the device):
+// start        : Header block, copy 0 - 0..4 magic, 4..12 checkpoint LSN,
+//                pager trailer (with the checkpoint LSN)
+// start + 1    : Header block, copy 1
+// start + 2 .. : Frame slots. A frame is a full page image whose trailer
+//                carries its LSN and home page id. A commit record is a
+//                frame with COMMIT_MARKER at 0..4 and page id COMMIT_PAGE.
+//
+// Frames of one log generation have contiguous LSNs starting above the
+// checkpoint LSN, so recovery stops at the first slot that breaks the run
+// or fails its checksum (a torn frame).
+//
+// Checkpoints alternate between the two header copies, so a torn header
+// write leaves the other, older one. Recovery from an older checkpoint
+// just redoes frames whose pages already reached home.
+const WAL_MAGIC: &[u8; 4] = b"WLOG";
+const COMMIT_MARKER: &[u8; 4] = b"WCMT";
+const COMMIT_PAGE: u32 = u32::MAX;
+const HEADER_COPIES: u32 = 2;
+
+/// Write-ahead log wrapped around the data disk. It implements `BlockIO`
+/// for the buffer pool: writes append page images to the log instead of
+/// touching their home block, reads see the newest logged image. Nothing
+/// reaches its home location until `commit` has made the batch durable.
+/// Every block read through it is checked against its trailer checksum.
+pub struct Wal {
+    disk: Box<dyn BlockIO>,
+    start: u32,
+    capacity: u32,
+    next_slot: u32,
+    next_lsn: u64,
+    frames: BTreeMap<u32, (u32, u64)>, // page id -> (slot, lsn) of its latest frame
+    header: u32, // Header copy holding the newest checkpoint
+    corruptions: Cell<u64>,
+}
+
+/// Picks the WAL size for a fresh device of `num_blocks` blocks: a
+/// quarter of it, at least seven slots besides the header copies, and
+/// never more than half.
+pub fn default_wal_blocks(num_blocks: u32) -> u32 {
+    (num_blocks / 4).clamp(9, 1024).min(num_blocks / 2)
+}
+
+impl Wal {
+    /// Writes both copies of an empty log header at `start`.
+    pub fn format(disk: &mut dyn BlockIO, start: u32) -> Result<(), BlockError> {
+        for copy in 0..HEADER_COPIES {
+            write_header(disk, start + copy, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Opens the log in blocks `start..start + blocks`, replaying committed
+    /// frames into their home pages and discarding everything after the last
+    /// commit record.
+    pub fn open(disk: Box<dyn BlockIO>, start: u32, blocks: u32) -> Result<Self, BlockError> {
+        if blocks <= HEADER_COPIES || start.checked_add(blocks) != Some(disk.num_blocks()) {
+            return Err(BlockError::OutOfBounds);
+        }
+        let mut wal = Wal {
+            disk,
+            start,
+            capacity: blocks - HEADER_COPIES,
+            next_slot: 0,
+            next_lsn: 1,
+            frames: BTreeMap::new(),
+            header: 0,
+            corruptions: Cell::new(0),
+        };
+        wal.recover()?;
+        Ok(wal)
+    }
+
+    pub fn into_disk(self) -> Box<dyn BlockIO> {
+        self.disk
+    }
+
+    pub fn corruptions(&self) -> u64 {
+        self.corruptions.get()
+    }
+
+    /// Reads a block straight from the device, bypassing the log. Only for
+    /// scratch blocks that are never logged (see `Pager::alloc_temp_page`).
+    pub fn read_unlogged(&self, block_id: u32, buf: &mut [u8]) -> Result<(), BlockError> {
+        if block_id >= self.start {
+            return Err(BlockError::OutOfBounds);
+        }
+        self.disk.read_block(block_id, buf)?;
+        if !verify_trailer(buf, block_id) {
+            self.corruptions.set(self.corruptions.get() + 1);
+            return Err(BlockError::Corrupt { page_id: block_id });
+        }
+        Ok(())
+    }
+
+    /// Writes a scratch block straight to the device. The contents do not
+    /// survive a crash in any meaningful way and are never replayed.
+    pub fn write_unlogged(&mut self, block_id: u32, buf: &mut [u8]) -> Result<(), BlockError> {
+        if block_id >= self.start || buf.len() != BLOCK_SIZE {
+            return Err(BlockError::OutOfBounds);
+        }
+        stamp_trailer(buf, 0, block_id);
+        self.disk.write_block(block_id, buf)
+    }
+
+    /// True if `block_id` has a frame in the uncommitted batch.
+    pub fn is_logged(&self, block_id: u32) -> bool {
+        self.frames.contains_key(&block_id)
+    }
+
+    /// Frame slots in use / available in the current log generation.
+    pub fn usage(&self) -> (u32, u32) {
+        (self.next_slot, self.capacity)
+    }
+
+    fn recover(&mut self) -> Result<(), BlockError> {
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        let mut newest = None;
+        for copy in 0..HEADER_COPIES {
+            let block = self.start + copy;
+            if self.disk.read_block(block, &mut buf).is_err() || &buf[0..4] != WAL_MAGIC || !verify_trailer(&buf, block) {
+                self.corruptions.set(self.corruptions.get() + 1);
+                continue;
+            }
+            let lsn = u64::from_le_bytes(buf[4..12].try_into().unwrap_or([0; 8]));
+            if newest.is_none_or(|(_, newest_lsn)| lsn > newest_lsn) {
+                newest = Some((copy, lsn));
+            }
+        }
+        let (header, checkpoint_lsn) = newest.ok_or(BlockError::Corrupt { page_id: self.start })?;
+        self.header = header;
+
+        let mut committed: Vec<(u32, u32)> = Vec::new(); // (page id, slot)
+        let mut pending: Vec<(u32, u32)> = Vec::new();
+        let mut last_lsn = checkpoint_lsn;
+        for slot in 0..self.capacity {
+            if self.disk.read_block(self.slot_block(slot), &mut buf).is_err() {
+                break;
+            }
+            let lsn = page_lsn(&buf);
+            let in_run = if slot == 0 { lsn > checkpoint_lsn } else { lsn == last_lsn + 1 };
+            if !in_run || !verify_trailer(&buf, trailer_page_id(&buf)) {
+                break;
+            }
+            last_lsn = lsn;
+            if trailer_page_id(&buf) == COMMIT_PAGE && &buf[0..4] == COMMIT_MARKER {
+                committed.append(&mut pending);
+            } else {
+                pending.push((trailer_page_id(&buf), slot));
+            }
+        }
+
+        // Redo committed frames in log order so the newest image wins
+        for (page_id, slot) in committed {
+            self.disk.read_block(self.slot_block(slot), &mut buf)?;
+            self.disk.write_block(page_id, &buf)?;
+        }
+        // Move the checkpoint past every LSN seen, including discarded frames,
+        // so stale slots can never join a later log run
+        if last_lsn != checkpoint_lsn {
+            self.checkpoint(last_lsn)?;
+        }
+        self.next_lsn = last_lsn + 1;
+        Ok(())
+    }
+
+    /// Appends a commit record and checkpoints every logged page back to its
+    /// home block. Once the commit record is on disk the batch survives a
+    /// crash; the checkpoint only shortens the next recovery.
+    pub fn commit(&mut self) -> Result<(), BlockError> {
+        if self.frames.is_empty() {
+            return Ok(());
+        }
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        buf[0..4].copy_from_slice(COMMIT_MARKER);
+        let commit_lsn = self.append(COMMIT_PAGE, &mut buf)?;
+
+        for (&page_id, &(slot, _)) in self.frames.iter() {
+            self.disk.read_block(self.slot_block(slot), &mut buf)?;
+            self.disk.write_block(page_id, &buf)?;
+        }
+        self.checkpoint(commit_lsn)?;
+        self.frames.clear();
+        self.next_slot = 0;
+        Ok(())
+    }
+
+    /// Drops the uncommitted batch: reads see the home blocks again and
+    /// its slots are reused. The abandoned frames have no commit record
+    /// after them, so recovery never replays them, and the next batch
+    /// continues with higher LSNs.
+    pub fn abort(&mut self) {
+        self.frames.clear();
+        self.next_slot = 0;
+    }
+
+    fn slot_block(&self, slot: u32) -> u32 {
+        self.start + HEADER_COPIES + slot
+    }
+
+    // Records `lsn` as the checkpoint in the older header copy.
+    fn checkpoint(&mut self, lsn: u64) -> Result<(), BlockError> {
+        let copy = (self.header + 1) % HEADER_COPIES;
+        write_header(self.disk.as_mut(), self.start + copy, lsn)?;
+        self.header = copy;
+        Ok(())
+    }
+
+    /// Stamps `buf` with the next LSN and writes it to the next free slot.
+    fn append(&mut self, page_id: u32, buf: &mut [u8]) -> Result<u64, BlockError> {
+        if self.next_slot >= self.capacity {
+            return Err(BlockError::LogFull);
+        }
+        let lsn = self.next_lsn;
+        stamp_trailer(buf, lsn, page_id);
+        self.disk.write_block(self.slot_block(self.next_slot), buf)?;
+        self.next_lsn += 1;
+        self.next_slot += 1;
+        Ok(lsn)
+    }
+}
+
+impl BlockIO for Wal {
+    fn read_block(&self, block_id: u32, buf: &mut [u8]) -> Result<(), BlockError> {
+        match self.frames.get(&block_id) {
+            Some(&(slot, _)) => self.disk.read_block(self.slot_block(slot), buf)?,
+            None => self.disk.read_block(block_id, buf)?,
+        }
+        if !verify_trailer(buf, block_id) {
+            self.corruptions.set(self.corruptions.get() + 1);
+            return Err(BlockError::Corrupt { page_id: block_id });
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self, block_id: u32, buf: &[u8]) -> Result<(), BlockError> {
+        if block_id >= self.start || buf.len() != BLOCK_SIZE {
+            return Err(BlockError::OutOfBounds);
+        }
+        let mut frame = buf.to_vec();
+        match self.frames.get(&block_id) {
+            // Not committed yet, so the page's existing frame can be rewritten
+            // in place (keeping its LSN) instead of using another slot
+            Some(&(slot, lsn)) => {
+                stamp_trailer(&mut frame, lsn, block_id);
+                self.disk.write_block(self.slot_block(slot), &frame)
+            }
+            None => {
+                let slot = self.next_slot;
+                let lsn = self.append(block_id, &mut frame)?;
+                self.frames.insert(block_id, (slot, lsn));
+                Ok(())
+            }
+        }
+    }
+
+    /// Blocks available for data pages (everything below the log).
+    fn num_blocks(&self) -> u32 {
+        self.start
+    }
+}
+
+fn write_header(disk: &mut dyn BlockIO, block: u32, checkpoint_lsn: u64) -> Result<(), BlockError> {
+    let mut buf = vec![0u8; BLOCK_SIZE];
+    buf[0..4].copy_from_slice(WAL_MAGIC);
+    buf[4..12].copy_from_slice(&checkpoint_lsn.to_le_bytes());
+    stamp_trailer(&mut buf, checkpoint_lsn, block);
+    disk.write_block(block, &buf)
+}
@@ -0,0 +1,109 @@
+use crate::sawitdb::compress::Compression;
+use crate::sawitdb::database::Database;
+use crate::sawitdb::error::DbError;
+use crate::sawitdb::lock::SessionId;
+use crate::drivers::block::BlockIO;
+use alloc::boxed::Box;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use spin::Mutex;
+use lazy_static::lazy_static;
+
+// The database shared by the shell and network tasks
+lazy_static! {
+    pub static ref DATABASE: Mutex<Option<Database>> = Mutex::new(None);
+}
+
+/// Opens the shared database on `disk`, formatting it with `compression`
+/// if it is blank.
+pub fn init(disk: Box<dyn BlockIO>, compression: Compression) -> Result<(), DbError> {
+    let db = Database::with_compression(disk, compression)?;
+    *DATABASE.lock() = Some(db);
+    Ok(())
+}
+
+/// Replaces the shared database with the one on `disk`, e.g. a restored
+/// backup. The caller must have closed its own session; fails with
+/// `InUse` while any other is open.
+pub fn replace(disk: Box<dyn BlockIO>) -> Result<(), DbError> {
+    let db = Database::open(disk)?;
+    let mut guard = DATABASE.lock();
+    if guard.as_ref().is_some_and(|old| old.session_count() > 1) {
+        return Err(DbError::InUse);
+    }
+    *guard = Some(db);
+    Ok(())
+}
+
+/// One client's session on the shared database, closed (and its open
+/// transaction rolled back) when dropped. `None` from `open` if the
+/// database is not initialized.
+pub struct Session {
+    id: SessionId,
+}
+
+impl Session {
+    pub fn open() -> Option<Result<Session, DbError>> {
+        let mut guard = DATABASE.lock();
+        let db = guard.as_mut()?;
+        Some(db.open_session().map(|id| Session { id }))
+    }
+
+    /// Runs `f` on the database in this session. `f` should end its
+    /// statement with `autocommit`. A statement that has to wait for a lock
+    /// held by another session is undone, and `f` runs again once the lock
+    /// is granted; meanwhile other tasks run. The mutex is only held while
+    /// `f` runs, so it must not block.
+    pub async fn run<T, F>(&self, mut f: F) -> Result<T, DbError>
+    where
+        F: FnMut(&mut Database) -> Result<T, DbError>,
+    {
+        loop {
+            let result = {
+                let mut guard = DATABASE.lock();
+                // Sessions only exist once the database is open, and it stays open
+                let db = guard.as_mut().expect("session without a database");
+                db.enter(self.id);
+                f(db)
+            };
+            match result {
+                Err(DbError::LockWait) => LockWait { session: self.id }.await?,
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(db) = DATABASE.lock().as_mut() {
+            let _ = db.close_session(self.id);
+        }
+    }
+}
+
+// Resolves once the lock `session` waits for is granted, re-queueing the
+// task on every poll until then. A deadlock found while waiting rolls the
+// session's transaction back.
+struct LockWait {
+    session: SessionId,
+}
+
+impl Future for LockWait {
+    type Output = Result<(), DbError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut guard = DATABASE.lock();
+        let Some(db) = guard.as_mut() else { return Poll::Ready(Ok(())) };
+        db.enter(self.session);
+        match db.lock_granted() {
+            Ok(true) => Poll::Ready(Ok(())),
+            Ok(false) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(db.autocommit(Err(e))),
+        }
+    }
+}
@@ -2,6 +2,7 @@ pub mod simple_executor;
 pub mod keyboard;
 pub mod shell;
 pub mod net;
+pub mod db;
 
 use core::{future::Future, pin::Pin};
 use alloc::boxed::Box;
@@ -3,11 +3,21 @@ use smoltcp::time::Instant;
 use smoltcp::socket::tcp::{Socket as TcpSocket, SocketBuffer as TcpSocketBuffer};
 use smoltcp::iface::SocketHandle;
 use alloc::vec;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
 use crate::serial_println;
+use crate::sawitdb::error::DbError;
+use crate::sawitdb::query::{self, Output};
+use crate::sawitdb::backup::{Backup, Restore};
+use super::db::Session;
 use core::sync::atomic::{AtomicU64, Ordering};
 
 static TIME: AtomicU64 = AtomicU64::new(0);
 
+// Longest query line a remote client may send, newline included
+const MAX_LINE: usize = 4096;
+
 // Simple monotonic timer since we don't have high precision hardware timer hooked up to smoltcp yet
 fn current_time() -> Instant {
     Instant::from_millis(TIME.fetch_add(10, Ordering::Relaxed) as i64)
@@ -45,9 +55,10 @@ pub async fn poll_task() {
 }
 
 
-// Improved Logic with Handle
-// use smoltcp::iface::SocketHandle; // Already imported at top? Or assume it uses the top one.
-
+// Remote SQL sessions on port 8023: each line received is run as one
+// query against the database the shell uses, and the result is sent back.
+// A connection gets its own session, with its own transactions and locks;
+// closing the connection rolls back whatever it left open.
 pub async fn server_task() {
     let handle: SocketHandle;
     {
@@ -57,42 +68,232 @@ pub async fn server_task() {
         let socket = TcpSocket::new(tcp_rx_buffer, tcp_tx_buffer);
         handle = sockets.add(socket);
     }
-    
-    let _is_open = false;
+
+    let mut session: Option<Session> = None;
+    // Bytes received but not yet a complete line; a character may be
+    // split across reads, so lines are only decoded once complete
+    let mut pending: Vec<u8> = Vec::new();
+    // Set once a line has outgrown `MAX_LINE`: the rest of it is dropped
+    let mut overlong = false;
 
     loop {
+        let mut greeting = None;
         {
             let mut sockets = SOCKETS.lock();
             let socket = sockets.get_mut::<TcpSocket>(handle);
-            
+
             if !socket.is_open() {
-                // Listen
+                // Previous client gone: drop its session before listening again
+                session = None;
+                pending.clear();
+                overlong = false;
                 socket.listen(8023).ok(); // Port 8023
                 crate::serial_println!("[Net] Listening on :8023");
             }
-            
-            if socket.can_send() && socket.can_recv() {
-                // Simple Echo/Shell
-                // Read input
+
+            if socket.is_active() && session.is_none() {
+                match Session::open() {
+                    Some(Ok(opened)) => {
+                        session = Some(opened);
+                        greeting = Some(String::from("SawitRemote> "));
+                    }
+                    Some(Err(e)) => {
+                        socket.send_slice(format!("SawitDB unavailable: {}\n", e).as_bytes()).ok();
+                        socket.close();
+                    }
+                    None => {
+                        socket.send_slice(b"SawitDB unavailable\n").ok();
+                        socket.close();
+                    }
+                }
+            }
+
+            if socket.can_recv() {
                 let mut data = [0u8; 128];
-                match socket.recv_slice(&mut data) {
-                    Ok(size) if size > 0 => {
-                         let s = core::str::from_utf8(&data[..size]).unwrap_or("?");
-                         crate::serial_println!("[Remote] {}", s);
-                         // Echo + response (Mock Shell)
-                         use alloc::format;
-                         let response = format!("SawitRemote> You said: {}\n", s.trim());
-                         socket.send_slice(response.as_bytes()).ok();
-                         
-                         // Here we could bridge to shell commands
-                         // ...
+                if let Ok(size) = socket.recv_slice(&mut data) {
+                    pending.extend_from_slice(&data[..size]);
+                }
+            }
+        }
+        if let Some(greeting) = greeting {
+            send(handle, greeting.as_bytes()).await;
+        }
+
+        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+            let bytes: Vec<u8> = pending.drain(..=end).collect();
+            if overlong {
+                // Tail of a line already answered with an error
+                overlong = false;
+                continue;
+            }
+            let line = String::from_utf8_lossy(&bytes);
+            let line = line.trim();
+            let Some(ref session) = session else { break };
+            let mut response = String::new();
+            if !line.is_empty() {
+                crate::serial_println!("[Remote] {}", line);
+                response = remote_query(session, line).await;
+            }
+            response.push_str("SawitRemote> ");
+            send(handle, response.as_bytes()).await;
+        }
+        if pending.len() > MAX_LINE {
+            pending.clear();
+            if !overlong {
+                overlong = true;
+                send(handle, b"Error: line too long\nSawitRemote> ").await;
+            }
+        }
+        YieldNow::default().await;
+    }
+}
+
+// Runs one remote query; the reply text mirrors the shell's output.
+async fn remote_query(session: &Session, src: &str) -> String {
+    let result = session.run(|db| {
+        let result = query::run(db, src).and_then(|output| match output {
+            Output::Rows(rows) => {
+                let rows = rows.collect_rows(db)?;
+                let mut text = String::new();
+                for row in &rows {
+                    text.push_str(&format!("  {}\n", row));
+                }
+                text.push_str(&format!("({} rows)\n", rows.len()));
+                Ok(text)
+            }
+            Output::Affected(n) => Ok(format!("{} rows affected.\n", n)),
+            Output::Done => Ok(String::from("OK.\n")),
+        });
+        db.autocommit(result)
+    }).await;
+    match result {
+        Ok(text) => text,
+        Err(DbError::Syntax(e)) => format!("Error: {}\n{}\n", e, e.caret(src)),
+        Err(e) => format!("Error: {}\n", e),
+    }
+}
+
+// Backups on port 8024: a client that connects is sent a consistent
+// snapshot of the database (see `Backup`), then the connection closes.
+// Writers wait while the pages are copied; readers do not.
+pub async fn backup_task() {
+    let handle: SocketHandle;
+    {
+        let mut sockets = SOCKETS.lock();
+        let tcp_rx_buffer = TcpSocketBuffer::new(vec![0; 256]);
+        let tcp_tx_buffer = TcpSocketBuffer::new(vec![0; 8192]);
+        handle = sockets.add(TcpSocket::new(tcp_rx_buffer, tcp_tx_buffer));
+    }
+
+    loop {
+        let connected = {
+            let mut sockets = SOCKETS.lock();
+            let socket = sockets.get_mut::<TcpSocket>(handle);
+            if !socket.is_open() {
+                socket.listen(8024).ok();
+                crate::serial_println!("[Net] Backups on :8024");
+            }
+            socket.may_send()
+        };
+        if connected {
+            let reply = match Session::open() {
+                Some(Ok(session)) => {
+                    match send_backup(&session, handle).await {
+                        Ok(pages) => {
+                            crate::serial_println!("[Backup] Sent {} pages", pages);
+                        }
+                        // Part of the stream may be out already: cut the
+                        // connection rather than append text to it
+                        Err(e) => {
+                            crate::serial_println!("[Backup] Failed: {}", e);
+                            SOCKETS.lock().get_mut::<TcpSocket>(handle).abort();
+                        }
                     }
-                    _ => {}
+                    None
                 }
+                Some(Err(e)) => Some(format!("SawitDB unavailable: {}\n", e)),
+                None => Some(String::from("SawitDB unavailable\n")),
+            };
+            if let Some(reply) = reply {
+                crate::serial_println!("[Backup] {}", reply.trim());
+                send(handle, reply.as_bytes()).await;
+            }
+            SOCKETS.lock().get_mut::<TcpSocket>(handle).close();
+        }
+        YieldNow::default().await;
+    }
+}
+
+// Streams one backup to the connected client. Closing `session` ends
+// the backup too, should the client go away midway.
+async fn send_backup(session: &Session, handle: SocketHandle) -> Result<u32, DbError> {
+    let mut backup = session.run(Backup::start).await?;
+    while let Some(chunk) = session.run(|db| backup.next_chunk(db)).await? {
+        if !send(handle, &chunk).await {
+            return Err(DbError::Decode("backup receiver disconnected"));
+        }
+    }
+    Ok(backup.manifest().pages)
+}
+
+/// Waits for one client on `port` and loads the backup stream it sends
+/// into `restore`, until the stream is complete, the client leaves or
+/// `cancelled` returns true (it is asked on every poll).
+pub async fn receive_backup(port: u16, restore: &mut Restore, mut cancelled: impl FnMut() -> bool) -> Result<(), DbError> {
+    let handle: SocketHandle;
+    {
+        let mut sockets = SOCKETS.lock();
+        let tcp_rx_buffer = TcpSocketBuffer::new(vec![0; 8192]);
+        let tcp_tx_buffer = TcpSocketBuffer::new(vec![0; 256]);
+        let mut socket = TcpSocket::new(tcp_rx_buffer, tcp_tx_buffer);
+        socket.listen(port).map_err(|_| DbError::Decode("cannot listen for a backup"))?;
+        handle = sockets.add(socket);
+    }
+
+    let mut result = Ok(());
+    let mut connected = false;
+    while result.is_ok() && !restore.is_complete() {
+        if cancelled() {
+            result = Err(DbError::Cancelled);
+            break;
+        }
+        {
+            let mut sockets = SOCKETS.lock();
+            let socket = sockets.get_mut::<TcpSocket>(handle);
+            connected |= socket.is_active();
+            let mut data = [0u8; 1024];
+            match socket.recv_slice(&mut data) {
+                Ok(size) => result = restore.feed(&data[..size]),
+                // Nothing more will come
+                Err(_) if connected => result = Err(DbError::Decode("backup stream incomplete")),
+                Err(_) => {}
+            }
+        }
+        YieldNow::default().await;
+    }
+
+    let mut sockets = SOCKETS.lock();
+    sockets.get_mut::<TcpSocket>(handle).abort();
+    sockets.remove(handle);
+    result
+}
+
+// Queues `bytes` on the socket as room frees up in its send buffer; false
+// if the connection can no longer send.
+async fn send(handle: SocketHandle, bytes: &[u8]) -> bool {
+    let mut sent = 0;
+    while sent < bytes.len() {
+        {
+            let mut sockets = SOCKETS.lock();
+            let socket = sockets.get_mut::<TcpSocket>(handle);
+            if !socket.may_send() {
+                return false;
             }
+            sent += socket.send_slice(&bytes[sent..]).unwrap_or(0);
         }
         YieldNow::default().await;
     }
+    true
 }
 
 
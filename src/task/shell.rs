@@ -1,21 +1,62 @@
 use crate::{print, println};
 use futures_util::stream::StreamExt;
+use futures_util::FutureExt;
 use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use alloc::boxed::Box;
 use alloc::string::String;
+use crate::drivers::block::{BlockError, BlockIO, RamDisk, BLOCK_SIZE};
+use crate::drivers::crypt::{EncryptedDisk, KDF_ITERATIONS, SALT_SIZE};
+use crate::drivers::sha256::Sha256;
+use crate::sawitdb::backup::Restore;
+use crate::sawitdb::compress::Compression;
 
-pub async fn shell_task() {
+type Keys = Keyboard<layouts::Us104Key, ScancodeSet1>;
+
+/// Runs the shell. The database on `disk` is encrypted: it is unlocked
+/// (or formatted, if blank) with a passphrase typed here before anything
+/// else can use it.
+pub async fn shell_task(disk: Box<dyn BlockIO>) {
     let mut scancodes = super::keyboard::ScancodeStream::new();
     let mut keyboard = Keyboard::new(layouts::Us104Key, ScancodeSet1,
         HandleControl::Ignore);
+    // A backup of the database always fits a RamDisk of the same size,
+    // so restores use one; the heap must hold both while they run
+    let disk_size = disk.num_blocks() as usize * BLOCK_SIZE;
+
+    match unlock_disk(disk, &mut scancodes, &mut keyboard).await {
+        // One compressed database holds every table (index pages, catalog
+        // and WAL), shared by the shell and remote sessions
+        Ok(disk) => {
+            if let Err(e) = super::db::init(Box::new(disk), Compression::Lz) {
+                println!("SawitDB unavailable: {}", e);
+            }
+        }
+        Err(e) => println!("SawitDB unavailable: {:?}", e),
+    }
     
     let mut line_buffer = String::new();
     let mut in_management_mode = false;
     
     // SawitDB State
-    use crate::sawitdb::btree::BTreeIndex;
-    use crate::sawitdb::types::Value;
-    // We only support one table for now in this simple shell
-    let mut active_table: Option<BTreeIndex> = None;
+    use crate::sawitdb::catalog::Column;
+    use crate::sawitdb::row::Row;
+    use crate::sawitdb::types::{Value, ValueType};
+    use alloc::vec;
+    use super::db::Session;
+    // The shell's session on the database it shares with remote clients
+    let mut db = match Session::open() {
+        Some(Ok(session)) => Some(session),
+        Some(Err(e)) => {
+            println!("SawitDB unavailable: {}", e);
+            None
+        }
+        None => {
+            println!("SawitDB unavailable: not initialized");
+            None
+        }
+    };
+    // Table that put/get operate on
+    let mut active_table: Option<String> = None;
 
     print!("Sawit> ");
 
@@ -59,53 +100,254 @@ pub async fn shell_task() {
                                             "db_init" => {
                                                 if parts.len() < 2 {
                                                     println!("Usage: db_init <table_name>");
-                                                } else {
+                                                } else if let Some(ref db) = db {
                                                     let name = String::from(parts[1]);
-                                                    active_table = Some(BTreeIndex::new(4, name.clone(), String::from("id")));
-                                                    println!("Table '{}' initialized.", name);
+                                                    let schema = vec![Column::new("id", ValueType::Int), Column::new("val", ValueType::String)];
+                                                    let created = db.run(|db| {
+                                                        let created = db.create_table(&name, "id", schema.clone());
+                                                        db.autocommit(created)
+                                                    }).await;
+                                                    match created {
+                                                        Ok(()) => {
+                                                            println!("Table '{}' initialized.", name);
+                                                            active_table = Some(name);
+                                                        }
+                                                        Err(e) => println!("Error: {}", e),
+                                                    }
+                                                }
+                                            },
+                                            "use" => {
+                                                if parts.len() < 2 {
+                                                    println!("Usage: use <table_name>");
+                                                } else if let Some(ref db) = db {
+                                                    match db.run(|db| db.table(parts[1]).map(|info| info.name.clone())).await {
+                                                        Ok(name) => {
+                                                            println!("Using table '{}'.", name);
+                                                            active_table = Some(name);
+                                                        }
+                                                        Err(e) => println!("Error: {}", e),
+                                                    }
+                                                }
+                                            },
+                                            "tables" => {
+                                                if let Some(ref db) = db {
+                                                    let tables = db.run(|db| Ok(db.tables().to_vec())).await.unwrap_or_default();
+                                                    if tables.is_empty() {
+                                                        println!("No tables.");
+                                                    }
+                                                    for info in &tables {
+                                                        print!("  {} (key: {})", info.name, info.key_field);
+                                                        for col in &info.schema {
+                                                            print!(" {}:{}", col.name, col.ty);
+                                                        }
+                                                        println!();
+                                                    }
+                                                }
+                                            },
+                                            "drop" if parts.len() <= 2 => {
+                                                if parts.len() < 2 {
+                                                    println!("Usage: drop <table_name>");
+                                                } else if let Some(ref db) = db {
+                                                    let dropped = db.run(|db| {
+                                                        let dropped = db.drop_table(parts[1]);
+                                                        db.autocommit(dropped)
+                                                    }).await;
+                                                    match dropped {
+                                                        Ok(()) => {
+                                                            println!("Table '{}' dropped.", parts[1]);
+                                                            if active_table.as_deref() == Some(parts[1]) {
+                                                                active_table = None;
+                                                            }
+                                                        }
+                                                        Err(e) => println!("Error: {}", e),
+                                                    }
                                                 }
                                             },
                                             "put" => {
-                                                // put <key_int> <val_str>
-                                                if let Some(ref mut table) = active_table {
+                                                // put <key_int> <val_str> - fills the key field and the first other column
+                                                if let (Some(ref db), Some(ref table)) = (&db, &active_table) {
                                                     if parts.len() < 3 {
                                                         println!("Usage: put <key_int> <val_string>");
-                                                    } else {
-                                                        if let Ok(k) = parts[1].parse::<i64>() {
-                                                            let val_str = String::from(parts[2]); // Take first word as val
-                                                            // Re-join rest if needed? For now simple single word
-                                                            table.insert(Value::Int(k), Value::String(val_str));
-                                                            println!("Inserted.");
-                                                        } else {
-                                                            println!("Error: Key must be integer");
+                                                    } else if let Ok(k) = parts[1].parse::<i64>() {
+                                                        // Each put is committed on its own, unless a transaction is open
+                                                        let result = db.run(|db| {
+                                                            let result = db.table(table).map(|info| {
+                                                                let val_col = info.schema.iter().find(|c| c.name != info.key_field)
+                                                                    .map(|c| c.name.clone()).unwrap_or_else(|| String::from("val"));
+                                                                Row::new().with(&info.key_field, Value::Int(k)).with(&val_col, Value::String(parts[2..].join(" ")))
+                                                            }).and_then(|row| db.insert(table, &row));
+                                                            db.autocommit(result)
+                                                        }).await;
+                                                        match result {
+                                                            Ok(_) => println!("Inserted."),
+                                                            Err(e) => println!("Error: {}", e),
                                                         }
+                                                    } else {
+                                                        println!("Error: Key must be integer");
                                                     }
                                                 } else {
                                                     println!("Error: No table active. Run 'db_init <table>'");
                                                 }
                                             },
                                             "get" => {
-                                                if let Some(ref table) = active_table {
+                                                if let (Some(ref db), Some(ref table)) = (&db, &active_table) {
                                                     if parts.len() < 2 {
                                                         println!("Usage: get <key_int>");
+                                                    } else if let Ok(k) = parts[1].parse::<i64>() {
+                                                        let found = db.run(|db| {
+                                                            let found = db.get(table, &Value::Int(k));
+                                                            db.autocommit(found)
+                                                        }).await;
+                                                        match found {
+                                                            Ok(Some(row)) => println!("Found: {}", row),
+                                                            Ok(None) => println!("Not Found."),
+                                                            Err(e) => println!("Error: {}", e),
+                                                        }
                                                     } else {
-                                                        if let Ok(k) = parts[1].parse::<i64>() {
-                                                             let results = table.search(&Value::Int(k));
-                                                             if results.is_empty() {
-                                                                 println!("Not Found.");
-                                                             } else {
-                                                                 // Print first result
-                                                                 println!("Found: {:?}", results[0]);
-                                                             }
-                                                        } else {
-                                                            println!("Error: Key must be integer");
+                                                        println!("Error: Key must be integer");
+                                                    }
+                                                } else {
+                                                    println!("Error: No table active.");
+                                                }
+                                            },
+                                            "del" => {
+                                                if let (Some(ref db), Some(ref table)) = (&db, &active_table) {
+                                                    if parts.len() < 2 {
+                                                        println!("Usage: del <key_int>");
+                                                    } else if let Ok(k) = parts[1].parse::<i64>() {
+                                                        let result = db.run(|db| {
+                                                            let result = db.delete(table, &Value::Int(k));
+                                                            db.autocommit(result)
+                                                        }).await;
+                                                        match result {
+                                                            Ok(true) => println!("Deleted."),
+                                                            Ok(false) => println!("Not Found."),
+                                                            Err(e) => println!("Error: {}", e),
                                                         }
+                                                    } else {
+                                                        println!("Error: Key must be integer");
+                                                    }
+                                                } else {
+                                                    println!("Error: No table active.");
+                                                }
+                                            },
+                                            "scan" => {
+                                                // scan [lo hi] - inclusive key range, whole table without bounds
+                                                if let (Some(ref db), Some(ref table)) = (&db, &active_table) {
+                                                    let bounds = match (parts.get(1), parts.get(2)) {
+                                                        (None, _) => Some((i64::MIN, i64::MAX)),
+                                                        (Some(lo), Some(hi)) => lo.parse::<i64>().ok().zip(hi.parse::<i64>().ok()),
+                                                        _ => None,
+                                                    };
+                                                    match bounds {
+                                                        Some((lo, hi)) => match db.run(|db| {
+                                                            let rows = db.range(table, Value::Int(lo)..=Value::Int(hi));
+                                                            db.autocommit(rows)
+                                                        }).await {
+                                                            Ok(rows) => {
+                                                                for row in rows {
+                                                                    println!("  {}", row);
+                                                                }
+                                                            }
+                                                            Err(e) => println!("Error: {}", e),
+                                                        },
+                                                        None => println!("Usage: scan [<lo_int> <hi_int>]"),
                                                     }
                                                 } else {
                                                     println!("Error: No table active.");
                                                 }
                                             },
-                                            _ => println!("Unknown command '{}'. Type 'help' for menu.", parts[0]),
+                                            "dbstats" => {
+                                                if let Some(ref db) = db {
+                                                    let stats = db.run(|db| {
+                                                        let pager = db.pager();
+                                                        Ok((
+                                                            pager.total_pages().unwrap_or(0),
+                                                            pager.free_pages().unwrap_or(0),
+                                                            pager.pool_stats(),
+                                                            pager.pool_capacity(),
+                                                            pager.wal_usage(),
+                                                            pager.corruptions(),
+                                                            pager.packing_stats(),
+                                                        ))
+                                                    }).await;
+                                                    if let Ok((total, free, stats, frames, (used, slots), corrupt, packing)) = stats {
+                                                        println!("Pages: {} allocated, {} free", total, free);
+                                                        println!("Buffer Pool ({} frames):", frames);
+                                                        println!("  Hits:       {}", stats.hits);
+                                                        println!("  Misses:     {}", stats.misses);
+                                                        println!("  Hit Ratio:  {}%", stats.hit_ratio());
+                                                        println!("  Evictions:  {}", stats.evictions);
+                                                        println!("  Writebacks: {}", stats.writebacks);
+                                                        println!("WAL: {}/{} slots in use", used, slots);
+                                                        match packing {
+                                                            Some((pages, blocks)) => println!("Compression: LZ, {} pages packed into {} blocks", pages, blocks),
+                                                            None => println!("Compression: off"),
+                                                        }
+                                                        println!("Corrupt Pages Detected: {}", corrupt);
+                                                    }
+                                                }
+                                            },
+                                            "vacuum" => {
+                                                if let Some(ref db) = db {
+                                                    match db.run(|db| db.vacuum()).await {
+                                                        Ok(stats) => println!(
+                                                            "Reclaimed {} row versions ({} deleted rows), {} index entries.",
+                                                            stats.versions, stats.rows, stats.entries
+                                                        ),
+                                                        Err(e) => println!("Error: {}", e),
+                                                    }
+                                                }
+                                            },
+                                            "restore" => {
+                                                if db.is_some() {
+                                                    println!("The restored database replaces this one, on a new encrypted disk.");
+                                                    let disk = Box::new(RamDisk::new(disk_size));
+                                                    match unlock_disk(disk, &mut scancodes, &mut keyboard).await {
+                                                        Ok(disk) => {
+                                                            let mut restore = Restore::new(Box::new(disk));
+                                                            println!("Waiting for a backup stream on :8025 (Esc cancels)...");
+                                                            let escape = || {
+                                                                while let Some(Some(scancode)) = scancodes.next().now_or_never() {
+                                                                    if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
+                                                                        if let Some(DecodedKey::Unicode('\u{1b}')) = keyboard.process_keyevent(key_event) {
+                                                                            return true;
+                                                                        }
+                                                                    }
+                                                                }
+                                                                false
+                                                            };
+                                                            let restored = super::net::receive_backup(8025, &mut restore, escape).await;
+                                                            let manifest = restore.manifest();
+                                                            // The old database stays until the new one is whole
+                                                            match restored.and_then(|_| restore.finish()) {
+                                                                Ok(disk) => {
+                                                                    // Nothing is kept open on the old database
+                                                                    drop(db.take());
+                                                                    match super::db::replace(disk) {
+                                                                        Ok(()) => {
+                                                                            if let Some(manifest) = manifest {
+                                                                                println!("Restored {} pages, {} tables.", manifest.pages, manifest.tables);
+                                                                            }
+                                                                            active_table = None;
+                                                                        }
+                                                                        Err(e) => println!("Restore failed: {}", e),
+                                                                    }
+                                                                    db = Session::open().and_then(Result::ok);
+                                                                }
+                                                                Err(e) => println!("Restore failed: {}", e),
+                                                            }
+                                                        }
+                                                        Err(e) => println!("Restore failed: {:?}", e),
+                                                    }
+                                                }
+                                            },
+                                            // Anything else is a SQL / SawitDB query
+                                            _ => {
+                                                if let Some(ref db) = db {
+                                                    run_query(db, command_line).await;
+                                                }
+                                            },
                                         }
                                         
                                     } else {
@@ -114,8 +356,14 @@ pub async fn shell_task() {
                                 }
                                 
                                 line_buffer.clear();
+                                let in_transaction = match db {
+                                    Some(ref db) => db.run(|db| Ok(db.transaction().is_some())).await.unwrap_or(false),
+                                    None => false,
+                                };
                                 if !in_management_mode {
                                     print!("Sawit> ");
+                                } else if in_transaction {
+                                    print!("SawitDB*> ");
                                 } else {
                                     print!("SawitDB> ");
                                 }
@@ -139,11 +387,128 @@ pub async fn shell_task() {
     }
 }
 
+// Asks for the passphrase of `disk` until it is right. A blank disk is
+// formatted with a new passphrase, typed twice, and a salt hashed from
+// the timing of the keystrokes.
+async fn unlock_disk(mut disk: Box<dyn BlockIO>, scancodes: &mut super::keyboard::ScancodeStream,
+                     keyboard: &mut Keys) -> Result<EncryptedDisk, BlockError> {
+    let mut entropy = Sha256::new();
+    if !EncryptedDisk::is_formatted(disk.as_ref()) {
+        println!("SawitDB disk is blank; choose a passphrase to encrypt it.");
+        loop {
+            let passphrase = read_secret("New passphrase: ", scancodes, keyboard, &mut entropy).await;
+            let again = read_secret("Repeat passphrase: ", scancodes, keyboard, &mut entropy).await;
+            if passphrase.is_empty() {
+                println!("Passphrase must not be empty.");
+            } else if passphrase != again {
+                println!("Passphrases do not match.");
+            } else {
+                let mut salt = [0u8; SALT_SIZE];
+                salt.copy_from_slice(&entropy.finish()[..SALT_SIZE]);
+                let key = EncryptedDisk::format(disk.as_mut(), passphrase.as_bytes(), salt, KDF_ITERATIONS)?;
+                return Ok(EncryptedDisk::new(disk, key));
+            }
+        }
+    }
+    loop {
+        let passphrase = read_secret("SawitDB passphrase: ", scancodes, keyboard, &mut entropy).await;
+        match EncryptedDisk::unlock(disk.as_ref(), passphrase.as_bytes()) {
+            Ok(key) => return Ok(EncryptedDisk::new(disk, key)),
+            Err(BlockError::BadKey) => println!("Wrong passphrase."),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Reads a line without echoing it, printing '*' per character. The time
+// of every keystroke is fed to `entropy`.
+async fn read_secret(prompt: &str, scancodes: &mut super::keyboard::ScancodeStream,
+                     keyboard: &mut Keys, entropy: &mut Sha256) -> String {
+    let mut secret = String::new();
+    print!("{}", prompt);
+    while let Some(scancode) = scancodes.next().await {
+        entropy.update(&unsafe { core::arch::x86_64::_rdtsc() }.to_le_bytes());
+        if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
+            if let Some(DecodedKey::Unicode(character)) = keyboard.process_keyevent(key_event) {
+                match character {
+                    '\n' => break,
+                    '\x08' => {
+                        if secret.pop().is_some() {
+                            print!("{}", '\x08');
+                        }
+                    }
+                    _ => {
+                        print!("*");
+                        secret.push(character);
+                    }
+                }
+            }
+        }
+    }
+    println!();
+    secret
+}
+
 fn print_management_menu() {
     println!("\n--- SawitDB Management ---");
     println!("meminfo           - Show Memory Stats");
-    println!("db_init <table>   - Create new Table Index");
-    println!("put <key> <val>   - Insert Data (Key=Int)");
-    println!("get <key>         - Query Data");
+    println!("db_init <table>   - Create new Table (id INT, val STRING)");
+    println!("use <table>       - Select Table for put/get");
+    println!("tables            - List Tables in Catalog");
+    println!("drop <table>      - Drop Table");
+    println!("put <key> <val>   - Insert Row (Key=Int)");
+    println!("get <key>         - Fetch Row by Key");
+    println!("del <key>         - Delete Row");
+    println!("scan [<lo> <hi>]  - List Rows in Key Order");
+    println!("dbstats           - Show Page & Buffer Pool Stats");
+    println!("vacuum            - Reclaim Old Row Versions");
+    println!("restore           - Replace the Database with a Backup sent to :8025");
+    println!("<query>           - Run SQL or SawitDB, e.g. PANEN * DARI t DIMANA id > 1");
+    println!("BEGIN / COMMIT / ROLLBACK - Group Queries into a Transaction (prompt shows *)");
     println!("exit              - Return to Shell");
 }
+
+// Runs one query and prints its result. Outside a transaction the query
+// is committed on its own. A query that waits for another session's lock
+// runs again once it is granted, so rows are printed only at the end.
+async fn run_query(db: &super::db::Session, src: &str) {
+    use crate::sawitdb::error::DbError;
+    use crate::sawitdb::query::{self, Output};
+    use crate::sawitdb::row::Row;
+    use alloc::vec::Vec;
+    enum Answer {
+        Rows(Vec<Row>),
+        Affected(usize),
+        Done,
+    }
+    let mut in_transaction = false;
+    let result = db.run(|db| {
+        in_transaction = db.transaction().is_some();
+        let result = query::run(db, src).and_then(|output| match output {
+            Output::Rows(rows) => rows.collect_rows(db).map(Answer::Rows),
+            Output::Affected(n) => Ok(Answer::Affected(n)),
+            Output::Done => Ok(Answer::Done),
+        });
+        db.autocommit(result)
+    }).await;
+    match result {
+        Ok(Answer::Rows(rows)) => {
+            for row in &rows {
+                println!("  {}", row);
+            }
+            println!("({} rows)", rows.len());
+        }
+        Ok(Answer::Affected(n)) => println!("{} rows affected.", n),
+        Ok(Answer::Done) => println!("OK."),
+        Err(DbError::Syntax(e)) => {
+            println!("Error: {}", e);
+            println!("{}", e.caret(src));
+        }
+        Err(e) => {
+            println!("Error: {}", e);
+            if in_transaction && !db.run(|db| Ok(db.transaction().is_some())).await.unwrap_or(false) {
+                println!("Transaction rolled back.");
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+# SawitDB Backup / Restore over TCP
+#
+# Receive: connects to the backup port (8024), saves the stream to a file
+#          and checks its manifest and SHA-256 checksum.
+# Restore: sends a saved stream to the restore port (8025); run `restore`
+#          in the SawitCore shell's management mode first.
+#
+# With user-mode networking, forward both ports as well as 8023:
+# cargo run -- -netdev user,id=u1,hostfwd=tcp::8023-:8023,hostfwd=tcp::8024-:8024,hostfwd=tcp::8025-:8025 -device virtio-net-pci,netdev=u1 -serial stdio
+#
+# Usage:
+#   ./tools/backup.ps1 -Action receive -File sawitdb.bak
+#   ./tools/backup.ps1 -Action restore -File sawitdb.bak
+
+param(
+    [ValidateSet("receive", "restore")]
+    [string]$Action = "receive",
+    [string]$File = "sawitdb.bak",
+    [string]$HostName = "127.0.0.1",
+    [int]$Port = 0
+)
+
+$ManifestSize = 32
+$DigestSize = 32
+
+# Checks a backup stream; returns the manifest fields or throws
+function Test-Backup([byte[]]$Data) {
+    if ($Data.Length -lt $ManifestSize + $DigestSize) {
+        throw "Stream too short ($($Data.Length) bytes)"
+    }
+    $magic = [System.Text.Encoding]::ASCII.GetString($Data, 0, 4)
+    if ($magic -ne "SWBK") {
+        throw "Not a backup stream: $([System.Text.Encoding]::ASCII.GetString($Data).Trim())"
+    }
+    $version = [BitConverter]::ToUInt32($Data, 4)
+    $pageSize = [BitConverter]::ToUInt32($Data, 8)
+    $pages = [BitConverter]::ToUInt32($Data, 12)
+    $compression = [BitConverter]::ToUInt32($Data, 16)
+    $tables = [BitConverter]::ToUInt32($Data, 20)
+    $expected = $ManifestSize + [int64]$pages * $pageSize + $DigestSize
+    if ($Data.Length -ne $expected) {
+        throw "Stream is $($Data.Length) bytes, manifest says $expected"
+    }
+    $body = $Data.Length - $DigestSize
+    $sha = [System.Security.Cryptography.SHA256]::Create()
+    $digest = $sha.ComputeHash($Data, 0, $body)
+    for ($i = 0; $i -lt $DigestSize; $i++) {
+        if ($digest[$i] -ne $Data[$body + $i]) {
+            throw "Checksum mismatch"
+        }
+    }
+    [PSCustomObject]@{
+        Version     = $version
+        PageSize    = $pageSize
+        Pages       = $pages
+        Compression = @("off", "LZ")[[Math]::Min($compression, 1)]
+        Tables      = $tables
+    }
+}
+
+if ($Action -eq "receive") {
+    if ($Port -eq 0) { $Port = 8024 }
+    Write-Host "Receiving backup from ${HostName}:$Port..." -ForegroundColor Cyan
+    $client = New-Object System.Net.Sockets.TcpClient($HostName, $Port)
+    $stream = $client.GetStream()
+    $buffer = New-Object System.IO.MemoryStream
+    $stream.CopyTo($buffer)
+    $client.Close()
+    $data = $buffer.ToArray()
+
+    try {
+        $manifest = Test-Backup $data
+    } catch {
+        Write-Host "[X] Backup rejected: $_" -ForegroundColor Red
+        exit 1
+    }
+    [System.IO.File]::WriteAllBytes((Join-Path (Get-Location) $File), $data)
+    Write-Host "[✓] Saved $($data.Length) bytes to $File" -ForegroundColor Green
+    Write-Host "    $($manifest.Pages) pages of $($manifest.PageSize) bytes, $($manifest.Tables) tables, compression $($manifest.Compression)" -ForegroundColor Gray
+} else {
+    if ($Port -eq 0) { $Port = 8025 }
+    $data = [System.IO.File]::ReadAllBytes((Resolve-Path $File))
+    try {
+        $manifest = Test-Backup $data
+    } catch {
+        Write-Host "[X] $File is not a valid backup: $_" -ForegroundColor Red
+        exit 1
+    }
+    Write-Host "Sending $($manifest.Pages) pages to ${HostName}:$Port..." -ForegroundColor Cyan
+    $client = New-Object System.Net.Sockets.TcpClient($HostName, $Port)
+    $stream = $client.GetStream()
+    $stream.Write($data, 0, $data.Length)
+    $stream.Flush()
+    $client.Close()
+    Write-Host "[✓] Sent $($data.Length) bytes; see the SawitCore shell for the result" -ForegroundColor Green
+}